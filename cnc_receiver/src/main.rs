@@ -1,20 +1,39 @@
 #[allow(unused)]
 
-use rs_space_core::ccsds_packet::FastCcsdsPacket;
+use rs_space_core::ccsds_packet::ErrorControlPolicy;
+use rs_space_core::codec::SpacePacketCodec;
+use rs_space_core::seq_monitor::SequenceMonitor;
 
 pub mod packet_processor;
 
-use tokio::io::BufReader;
+use rustop::opts;
+
+use futures::StreamExt;
 use tokio::io::{Error, ErrorKind};
 use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::FramedRead;
 
-use log::{error, info, LevelFilter};
+use log::{error, info, warn, LevelFilter};
 use log4rs::append::{console::ConsoleAppender, console::Target, file::FileAppender};
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    let (args, _rest) = opts! {
+        synopsis "Receiver for C&C packets";
+        opt error_control:Option<String>, desc: "Load the packet error control per APID from the given JSON file.";
+    }
+    .parse_or_exit();
+
+    let policy = match args.error_control {
+        Some(file) => {
+            let content = std::fs::read_to_string(&file)?;
+            serde_json::from_str::<ErrorControlPolicy>(&content)?
+        }
+        None => ErrorControlPolicy::default(),
+    };
+
     // initialise the logging
     let format_str = "{l} - {m}\n";
     let logfile = FileAppender::builder()
@@ -49,8 +68,9 @@ async fn main() -> Result<(), Error> {
 
         info!("Client connected: {:?}", addr);
 
+        let policy = policy.clone();
         tokio::spawn(async move {
-            match process(socket).await {
+            match process(socket, &policy).await {
                 Err(err) => {
                     error!("C&C Processor returned error, closing connection: {}", err);
                 }
@@ -62,20 +82,30 @@ async fn main() -> Result<(), Error> {
     }
 }
 
-async fn process(socket: TcpStream) -> Result<(), Error> {
-    let mut reader = BufReader::new(socket);
+async fn process(socket: TcpStream, policy: &ErrorControlPolicy) -> Result<(), Error> {
+    let mut reader = FramedRead::new(socket, SpacePacketCodec::new());
+    let mut monitor = SequenceMonitor::new();
 
-    loop {
-        let mut pkt = FastCcsdsPacket::new();
-        match pkt.read_from_async(&mut reader).await {
-            Ok(_) => match packet_processor::process_fast_packet(pkt) {
-                Ok(_) => (),
-                Err(err) => return Err(err),
-            },
-            Err(err) => {
+    let res = loop {
+        match reader.next().await {
+            Some(Ok(pkt)) => {
+                if let Some(event) = monitor.check_packet(&pkt) {
+                    warn!("Packet sequence: {:?}", event);
+                }
+                if let Err(err) = packet_processor::process_fast_packet(pkt, policy) {
+                    break Err(err);
+                }
+            }
+            Some(Err(err)) => {
                 error!("Got error from reading socket: {:?}", err);
-                return Err(err);
+                break Err(err);
             }
+            None => break Ok(()),
         }
+    };
+
+    for (apid, stats) in monitor.apids() {
+        info!("APID {}: {:?}", apid, stats);
     }
+    res
 }
@@ -4,21 +4,22 @@ use log::{debug, error, info, LevelFilter};
 
 use tokio::io::{Error, ErrorKind};
 
-use rs_space_core::ccsds_packet::{CcsdsPacket, FastCcsdsPacket};
+use rs_space_core::ccsds_packet::{CcsdsPacket, ErrorControlPolicy, FastCcsdsPacket};
 use rs_space_core::pus_packet::PUSPacket;
 use rs_space_core::pus_sec_hdr::gal_pus_c::GalSecHdrTC;
 use rs_space_core::pus_sec_hdr::pus_sec_hdr::*;
 
-pub fn process_fast_packet(pkt: FastCcsdsPacket) -> Result<(), Error> {
+pub fn process_fast_packet(pkt: FastCcsdsPacket, policy: &ErrorControlPolicy) -> Result<(), Error> {
     debug!("Received: {:?}", pkt);
-    if pkt.check_crc() {
-        let ccsds_pkt = CcsdsPacket::from_fast_ccsds_pkt(pkt);
+    let ec = policy.for_apid(pkt.apid());
+    if pkt.check_error_control(ec) {
+        let ccsds_pkt = CcsdsPacket::from_fast_ccsds_pkt_with(pkt, ec);
 
         debug!("CcsdsPacket: {:?}", ccsds_pkt);
 
         process_ccsds_packet(ccsds_pkt)
     } else {
-        Err(Error::new(ErrorKind::Other, "FastCcsdsPacket: CRC Error"))
+        Err(Error::new(ErrorKind::Other, format!("FastCcsdsPacket: {:?} Error", ec)))
     }
 }
 
@@ -6,6 +6,10 @@ use tokio::fs::{read_to_string};
 
 use tokio::net::TcpStream;
 
+use tokio_util::codec::FramedWrite;
+
+use futures::SinkExt;
+
 use tokio::sync::mpsc;
 
 use log::{info, error};
@@ -15,6 +19,7 @@ use async_recursion::async_recursion;
 use rs_space_core::edsl::{EDSL, Action};
 use rs_space_core::edsl::Action::{SendPkt, RepeatN, Log};
 use rs_space_core::ccsds_packet::{FastCcsdsPacket};
+use rs_space_core::codec::SpacePacketCodec;
 
 
 pub async fn run_app(script_file: &Path, address: String) -> Result<(), Error> {
@@ -37,11 +42,12 @@ pub async fn run_app(script_file: &Path, address: String) -> Result<(), Error> {
 async fn send_thread(address: String, mut chan: mpsc::Receiver<FastCcsdsPacket>) {
     // Connect to the specified socket
     match TcpStream::connect(address).await {
-        Ok(mut stream) => {
+        Ok(stream) => {
+            let mut stream = FramedWrite::new(stream, SpacePacketCodec::new());
             // receive data from the channel and send them to the 
             // socket 
             while let Some(pkt) = chan.recv().await {
-                match pkt.write_to_async(&mut stream).await {
+                match stream.send(pkt).await {
                     Ok(_) => {},
                     Err(err) => {
                         error!("Could not write to socket: {}", err)
@@ -67,7 +73,7 @@ async fn action_processor(tx: &mpsc::Sender<FastCcsdsPacket>, actions: &Action)
     match actions {
         SendPkt(pkt) => {
             let ccsds_pkt = pkt.to_ccsds_packet()?;  
-            let fast_pkt = ccsds_pkt.to_fast_ccsds_pkt();
+            let fast_pkt = ccsds_pkt.to_fast_ccsds_pkt()?;
 
             match tx.send(fast_pkt).await {
                 Ok(()) => {}, 
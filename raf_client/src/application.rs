@@ -1,9 +1,14 @@
 #[allow(unused)]
 use std::collections::BTreeSet;
+use std::sync::Arc;
 
 use rs_space_sle::asn1::UnbindReason;
 use rs_space_sle::raf::asn1::{RequestedFrameQuality, SleTMFrame};
+use rs_space_sle::raf::config::RAFConfig;
+use rs_space_sle::raf::frame_sink::FrameSinks;
+use rs_space_sle::raf::state::FrameCallback;
 use rs_space_sle::raf::user::RAFUser;
+use rs_space_sle::raf::user_supervisor::RAFUserSupervisor;
 use rs_space_sle::user::config::UserConfig;
 use tokio::io::Error;
 
@@ -13,15 +18,43 @@ fn frame_callback(frame: &SleTMFrame) {
     info!("Got Frame: {:?}", frame);
 }
 
+/// Opens the frame sinks of the RAF config. If none are configured, the frames are
+/// only logged.
+async fn open_sinks(raf_config: &RAFConfig) -> Result<(FrameSinks, FrameCallback), Error> {
+    let sinks = match FrameSinks::open(&raf_config.frame_sinks).await {
+        Ok(sinks) => sinks,
+        Err(err) => {
+            error!("Error opening frame sinks: {err}");
+            return Err(Error::new(std::io::ErrorKind::Other, err));
+        }
+    };
+
+    let callback: FrameCallback = if raf_config.frame_sinks.is_empty() {
+        Arc::new(frame_callback)
+    } else {
+        sinks.callback()
+    };
+    Ok((sinks, callback))
+}
+
 pub async fn run_app(config: &UserConfig) -> Result<(), Error> {
     for raf_config in &config.rafs {
         let config = (*config).clone();
         let raf_config = (*raf_config).clone();
 
+        let (sinks, callback) = open_sinks(&raf_config).await?;
+
+        if raf_config.reconnect.is_some() {
+            let res = run_supervised(&config, &raf_config, callback).await;
+            sinks.close().await;
+            res?;
+            continue;
+        }
+
         let address = format!("{}:{}", raf_config.hostname, raf_config.port);
         info!("Connecting to {}...", address);
 
-        let mut raf = RAFUser::new(&config.common, &raf_config, frame_callback);
+        let mut raf = RAFUser::new(&config.common, &raf_config, callback);
 
         //std::thread::sleep(std::time::Duration::from_secs(2));
 
@@ -63,6 +96,44 @@ pub async fn run_app(config: &UserConfig) -> Result<(), Error> {
         }
 
         raf.stop_processing().await;
+        sinks.close().await;
+    }
+    Ok(())
+}
+
+async fn run_supervised(
+    config: &UserConfig,
+    raf_config: &RAFConfig,
+    callback: FrameCallback,
+) -> Result<(), Error> {
+    info!(
+        "Connecting to {}:{} with automatic reconnect...",
+        raf_config.hostname, raf_config.port
+    );
+
+    let (mut supervisor, mut events) =
+        RAFUserSupervisor::new(&config.common, raf_config, callback);
+    let shutdown = supervisor.shutdown_token();
+
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            info!("Supervisor: {:?}", event);
+        }
+    });
+
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen to CTRL-C event");
+        shutdown.cancel();
+    });
+
+    if let Err(err) = supervisor
+        .run(None, None, RequestedFrameQuality::AllFrames)
+        .await
+    {
+        error!("RAF supervisor returned error: {err}");
+        return Err(Error::new(std::io::ErrorKind::ConnectionRefused, err));
     }
     Ok(())
 }
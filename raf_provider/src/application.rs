@@ -1,22 +1,21 @@
 #[allow(unused)]
 use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
 use log::{error, info};
-use rs_space_core::time::TimeEncoding;
 use rs_space_sle::asn1::UnbindReason;
-use rs_space_sle::raf::asn1::{FrameQuality, LockStatus, SleFrame};
-use rs_space_sle::raf::provider::RAFProvider;
-use rs_space_sle::sle::config::CommonConfig;
 use rs_space_sle::{
-    provider::{config::ProviderConfig, raf_interface::ProviderNotifier},
-    raf::config::RAFProviderConfig,
+    provider::{
+        config::ProviderConfig, frame_source::run_frame_source, raf_interface::ProviderNotifier,
+        supervisor::ProviderSupervisor,
+    },
     types::sle::{PeerAbortDiagnostic, SleVersion},
 };
 
-use bytes::Bytes;
-
 //use rs_space_sle::{asn1::UnbindReason, raf::client::RAFClient};
 use tokio::io::Error;
+use tokio_util::sync::CancellationToken;
 
 //use log::{error, info};
 
@@ -52,103 +51,73 @@ impl ProviderNotifier for Notifier {
     fn stop_succeeded(&self, sii: &str) {
         info!("RAF STOP SUCCEEDED for {sii}");
     }
-}
 
-pub async fn run_app(config: &ProviderConfig) -> Result<(), Error> {
-    for raf_config in &config.rafs {
-        run_service_instance(&config.common, raf_config.clone()).await;
+    fn connection_accepted(&self, sii: &str, peer: &SocketAddr) {
+        info!("CONNECTION ACCEPTED for {sii} from {peer}");
     }
-    //     let config = (*config).clone();
-    //     let raf_config = (*raf_config).clone();
-
-    //     let address = format!("{}:{}", raf_config.hostname, raf_config.port);
-    //     info!("Listening on {}...", address);
-
-    //     let mut raf = RAFClient::sle_connect_raf(&config.common, &raf_config, frame_callback).await?;
-
-    //     //std::thread::sleep(std::time::Duration::from_secs(2));
-
-    //     info!("Sending SLE BIND...");
-    //     match raf.bind(&config.common, &config.rafs[0]).await {
-    //         Ok(_) => {}
-    //         Err(err) => {
-    //             error!("Bind returned error: {err}");
-    //             return Err(Error::new(std::io::ErrorKind::ConnectionRefused, err));
-    //         }
-    //     }
-
-    //     std::thread::sleep(std::time::Duration::from_secs(2));
-
-    //     info!("Sending SLE UNBIND...");
-    //     match raf.unbind(&config.common, UnbindReason::End).await {
-    //         Ok(_) => {}
-    //         Err(err) => {
-    //             error!("UNBIND returned error: {err}");
-    //             return Err(Error::new(std::io::ErrorKind::ConnectionRefused, err));
-    //         }
-    //     }
-
-    //     tokio::signal::ctrl_c()
-    //         .await
-    //         .expect("failed to listen to CTRL-C event");
-
-    //     raf.stop_processing().await;
-    // }
-    Ok(())
-}
 
-async fn run_service_instance(common_config: &CommonConfig, config: RAFProviderConfig) {
-    let config2 = common_config.clone();
+    fn connection_closed(&self, sii: &str) {
+        info!("CONNECTION CLOSED for {sii}");
+    }
 
-    info!(
-        "Starting SLE instance {} on TCP port {} (SLE Port {})",
-        config.sii, config.port, config.responder_port
-    );
+    fn authentication_failed(&self, sii: &str, peer: &str, operation: &str) {
+        error!("AUTHENTICATION FAILED for {sii}: {operation} from {peer}");
+    }
 
-    info!("Creating new provider...");
-    let mut provider = RAFProvider::new(&config2, &config);
-    //let provider2 = provider.clone();
+    fn heartbeat_timeout(&self, sii: &str) {
+        error!("HEARTBEAT TIMEOUT for {sii}");
+    }
 
-    let notifier = Box::new(Notifier::new());
+    fn protocol_abort(&self, sii: &str, reason: &str) {
+        error!("PROTOCOL ABORT for {sii}: {reason}");
+    }
 
-    info!("Running provider...");
-    if let Err(err) = provider.run(notifier).await {
-        error!("Provider run returned error: {err}");
+    fn buffer_discarded(&self, sii: &str, frames: usize, notifications: usize) {
+        error!("BUFFER DISCARDED for {sii}: {frames} frames, {notifications} notifications");
+    }
+}
+
+pub async fn run_app(config: &ProviderConfig) -> Result<(), Error> {
+    let notifier = Arc::new(Notifier::new());
+
+    info!("Starting {} service instances...", config.rafs.len());
+    let mut supervisor = ProviderSupervisor::start(config, notifier);
+
+    let cancel_sources = CancellationToken::new();
+    let mut sources = Vec::new();
+
+    for handle in supervisor.handles() {
+        info!("Service instance {} is ready for BIND", handle.sii());
+
+        let source_config = config
+            .rafs
+            .iter()
+            .find(|raf| raf.sii == handle.sii())
+            .and_then(|raf| raf.frame_source.clone());
+
+        if let Some(source_config) = source_config {
+            let handle = handle.clone();
+            let cancel = cancel_sources.clone();
+            sources.push(tokio::spawn(async move {
+                let sii = handle.sii().to_string();
+                if let Err(err) = run_frame_source(source_config, handle, cancel).await {
+                    error!("Frame source for {sii} terminated with error: {err}");
+                }
+            }));
+        }
     }
 
-    info!("Waiting until provider is ACTIVE...");
-    let _ = provider.wait_active().await;
-    info!("Provider is ACTIVE!");
-
-    // tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-
-    // info!("Provider sending Notification for sync loss...");
-    // if let Err(err) = provider
-    //     .notify_sync_loss(
-    //         &rs_space_core::time::Time::now(TimeEncoding::CDS8),
-    //         LockStatus::OutOfLock,
-    //         LockStatus::OutOfLock,
-    //         LockStatus::OutOfLock,
-    //     )
-    //     .await
-    // {
-    //     error!("Error sending notification: {}", err);
-    //     return;
-    // }
-
-    // tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-
-    // let frame = SleFrame {
-    //     earth_receive_time: rs_space_core::time::Time::now(TimeEncoding::CDS8),
-    //     delivered_frame_quality: FrameQuality::Good,
-    //     data: Bytes::copy_from_slice(&[0x01, 0x02, 0x03, 0x04]),
-    // };
-
-    // info!("Provider sending frame...");
-    // if let Err(err) = provider.send_frame(frame).await {
-    //     error!("Error sending frame: {}", err);
-    //     return;
-    // }
-
-    provider.wait_for_termination().await;
+    tokio::signal::ctrl_c()
+        .await
+        .expect("failed to listen to CTRL-C event");
+
+    info!("Shutting down service instances...");
+    cancel_sources.cancel();
+    for source in sources {
+        let _ = source.await;
+    }
+    supervisor.shutdown();
+    supervisor.wait_for_termination().await;
+
+    Ok(())
 }
@@ -0,0 +1,136 @@
+//! Compares parsing packets from a receive buffer with the owned packet types and
+//! with the zero-copy views. Run with `cargo bench -p rs-space-core`.
+use std::hint::black_box;
+use std::time::Instant;
+
+use bytes::Bytes;
+
+use rs_space_core::ccsds_packet::{CcsdsPacket, ErrorControl};
+use rs_space_core::packet_view::{SpacePacketBytesIter, SpacePacketIter, SpacePacketView};
+use rs_space_core::pus_packet::PUSPacket;
+use rs_space_core::pus_sec_hdr::gal_pus_c::{GalSecHdrTC, GalSecHdrTCRef};
+use rs_space_core::pus_sec_hdr::pus_sec_hdr::*;
+use rs_space_core::pus_types::{CcsdsType, HexBytes, PktID, SegFlags, APID, SSC};
+
+const PACKETS: usize = 1000;
+const DATA_LEN: usize = 64;
+const ROUNDS: u32 = 200;
+
+/// A buffer with PUS TC packets, as received e.g. in the packet zones of frames
+fn packet_buffer() -> Vec<u8> {
+    let mut buf = Vec::new();
+    for i in 0..PACKETS {
+        let mut sec_hdr = GalSecHdrTC::new();
+        sec_hdr.set_pus_type(PUSType(17));
+        sec_hdr.set_pus_sub_type(PUSSubType(1));
+        let mut data = vec![0u8; sec_hdr.len()];
+        sec_hdr.to_bytes(&mut data).unwrap();
+        data.extend((0..DATA_LEN).map(|b| b as u8));
+
+        let pkt = CcsdsPacket {
+            pkt_id: PktID::new(0, CcsdsType::TC, true, APID::new(100 + (i % 10) as u16)),
+            ssc: SSC::new(SegFlags::Unsegmented, (i % 16384) as u16),
+            data: HexBytes(data),
+        };
+        buf.extend(pkt.to_fast_ccsds_pkt().unwrap().to_vec());
+    }
+    buf
+}
+
+fn bench<F: FnMut() -> usize>(name: &str, bytes: usize, mut f: F) {
+    // warm up
+    black_box(f());
+
+    let start = Instant::now();
+    let mut count = 0;
+    for _ in 0..ROUNDS {
+        count += black_box(f());
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(count, PACKETS * ROUNDS as usize);
+
+    println!(
+        "{name:<40} {:>8.1} ns/packet {:>10.1} MB/s",
+        elapsed.as_nanos() as f64 / count as f64,
+        (bytes as f64 * ROUNDS as f64) / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+fn owned(buf: &[u8]) -> usize {
+    use rs_space_core::ccsds_packet::FastCcsdsPacket;
+
+    let mut data = buf;
+    let mut count = 0;
+    while let Ok(pkt) = FastCcsdsPacket::from_slice(data) {
+        data = &data[pkt.total_length()..];
+        assert!(pkt.check_crc());
+        let ccsds = CcsdsPacket::from_fast_ccsds_pkt(pkt);
+        let pus =
+            PUSPacket::from_ccsds_packet(ccsds, PUSSecondaryHeader::GALTC(GalSecHdrTC::new()))
+                .unwrap();
+        black_box((pus.sec_hdr.pus_type(), pus.data().len()));
+        count += 1;
+    }
+    count
+}
+
+fn view_slice(buf: &[u8]) -> usize {
+    let mut count = 0;
+    for pkt in SpacePacketIter::new(buf) {
+        assert!(pkt.check_error_control(ErrorControl::Crc16));
+        let data = pkt.user_data(ErrorControl::Crc16);
+        let sec_hdr = GalSecHdrTCRef::new(data).unwrap();
+        black_box((sec_hdr.pus_type(), data[GalSecHdrTCRef::LEN..].len()));
+        count += 1;
+    }
+    count
+}
+
+fn view_bytes(buf: &Bytes) -> usize {
+    let mut count = 0;
+    for pkt in SpacePacketBytesIter::new(buf.clone()) {
+        assert!(pkt.check_error_control(ErrorControl::Crc16));
+        let data = pkt.user_data(ErrorControl::Crc16);
+        let sec_hdr = GalSecHdrTCRef::new(data).unwrap();
+        black_box((sec_hdr.pus_type(), data[GalSecHdrTCRef::LEN..].len()));
+        count += 1;
+    }
+    count
+}
+
+fn main() {
+    let buf = packet_buffer();
+    let shared = Bytes::from(buf.clone());
+    println!(
+        "{PACKETS} packets of {} bytes, {ROUNDS} rounds",
+        buf.len() / PACKETS
+    );
+
+    bench("FastCcsdsPacket -> PUSPacket", buf.len(), || owned(&buf));
+    bench("SpacePacketRef + GalSecHdrTCRef", buf.len(), || view_slice(&buf));
+    bench("SpacePacketBytes + GalSecHdrTCRef", buf.len(), || {
+        view_bytes(&shared)
+    });
+
+    // without the CRC check, which dominates the views
+    bench("FastCcsdsPacket headers only", buf.len(), || {
+        use rs_space_core::ccsds_packet::FastCcsdsPacket;
+
+        let mut data = &buf[..];
+        let mut count = 0;
+        while let Ok(pkt) = FastCcsdsPacket::from_slice(data) {
+            data = &data[pkt.total_length()..];
+            black_box(pkt.apid());
+            count += 1;
+        }
+        count
+    });
+    bench("SpacePacketRef headers only", buf.len(), || {
+        let mut count = 0;
+        for pkt in SpacePacketIter::new(&buf) {
+            black_box(pkt.apid());
+            count += 1;
+        }
+        count
+    });
+}
@@ -0,0 +1,474 @@
+//! AOS Space Data Link Protocol transfer frames (CCSDS 732.0-B).
+//!
+//! Most fields of AOS frames are managed parameters of the physical channel (frame
+//! header error control, insert zone, OCF and FECF) or the virtual channel (type of
+//! the data field). They are collected in an [AOSConfig]. [AOSFrame] is a zero-copy
+//! view on a frame, [AOSFrameBuilder] creates new frames.
+use std::io::{Error, ErrorKind};
+
+use crate::crc::calc_crc;
+
+/// The transfer frame version number of AOS frames
+pub const AOS_VERSION: u8 = 1;
+/// The virtual channel reserved for idle frames
+pub const AOS_IDLE_VCID: u8 = 63;
+/// M_PDU first header pointer: no packet starts in this frame
+pub const MPDU_NO_PACKET_START: u16 = 0x7FF;
+/// M_PDU first header pointer: the packet zone contains only idle data
+pub const MPDU_IDLE: u16 = 0x7FE;
+/// B_PDU bitstream data pointer: all data in the bitstream zone is valid
+pub const BPDU_ALL_VALID: u16 = 0x3FFF;
+/// B_PDU bitstream data pointer: the bitstream zone contains only idle data
+pub const BPDU_IDLE: u16 = 0x3FFE;
+
+/// The managed parameters of an AOS physical channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AOSConfig {
+    /// The primary header contains the frame header error control field
+    pub fhec: bool,
+    /// The length of the insert zone, 0 if there is no insert zone
+    pub insert_zone_len: usize,
+    /// The frames contain an operational control field
+    pub ocf: bool,
+    /// The frames contain a frame error control field
+    pub fecf: bool,
+}
+
+/// The content of the data field of an AOS frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AOSDataField<'a> {
+    /// Multiplexing protocol data unit, containing packets
+    MPDU {
+        first_header_pointer: u16,
+        packet_zone: &'a [u8],
+    },
+    /// Bitstream protocol data unit
+    BPDU {
+        data_pointer: u16,
+        bitstream: &'a [u8],
+    },
+}
+
+/// A view on an AOS transfer frame
+#[derive(Debug, Clone, Copy)]
+pub struct AOSFrame<'a> {
+    data: &'a [u8],
+    config: AOSConfig,
+}
+
+impl<'a> AOSFrame<'a> {
+    pub const PRIMARY_HDR_LEN: usize = 6;
+    pub const FHEC_LEN: usize = 2;
+    pub const OCF_LEN: usize = 4;
+    pub const FECF_LEN: usize = 2;
+    pub const MPDU_HDR_LEN: usize = 2;
+    pub const BPDU_HDR_LEN: usize = 2;
+
+    /// Creates a view on the frame contained in `data`. The slice must contain exactly
+    /// one frame.
+    pub fn new(data: &'a [u8], config: &AOSConfig) -> Result<AOSFrame<'a>, Error> {
+        let frame = AOSFrame {
+            data,
+            config: *config,
+        };
+
+        let min_len = frame.header_len() + frame.trailer_len();
+        if data.len() < min_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "AOS Frame: frame too short: {} bytes, need at least {min_len}",
+                    data.len()
+                ),
+            ));
+        }
+
+        if frame.version() != AOS_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("AOS Frame: wrong version number: {}", frame.version()),
+            ));
+        }
+
+        Ok(frame)
+    }
+
+    fn header_len(&self) -> usize {
+        let mut len = Self::PRIMARY_HDR_LEN + self.config.insert_zone_len;
+        if self.config.fhec {
+            len += Self::FHEC_LEN;
+        }
+        len
+    }
+
+    fn trailer_len(&self) -> usize {
+        let mut len = 0;
+        if self.config.ocf {
+            len += Self::OCF_LEN;
+        }
+        if self.config.fecf {
+            len += Self::FECF_LEN;
+        }
+        len
+    }
+
+    /// The complete frame
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 6
+    }
+
+    pub fn scid(&self) -> u8 {
+        (self.data[0] << 2) | (self.data[1] >> 6)
+    }
+
+    pub fn vcid(&self) -> u8 {
+        self.data[1] & 0x3F
+    }
+
+    /// The master channel ID (version and spacecraft ID)
+    pub fn mcid(&self) -> u16 {
+        ((self.version() as u16) << 8) | self.scid() as u16
+    }
+
+    /// The 24 bit virtual channel frame count
+    pub fn vc_frame_count(&self) -> u32 {
+        ((self.data[2] as u32) << 16) | ((self.data[3] as u32) << 8) | self.data[4] as u32
+    }
+
+    pub fn replay_flag(&self) -> bool {
+        (self.data[5] & 0x80) != 0
+    }
+
+    /// True, if the VC frame count cycle is used
+    pub fn vc_frame_count_usage_flag(&self) -> bool {
+        (self.data[5] & 0x40) != 0
+    }
+
+    /// The VC frame count cycle, if it is in use
+    pub fn vc_frame_count_cycle(&self) -> Option<u8> {
+        if self.vc_frame_count_usage_flag() {
+            Some(self.data[5] & 0x0F)
+        } else {
+            None
+        }
+    }
+
+    /// The VC frame count extended by the frame count cycle, if the cycle is used
+    pub fn extended_vc_frame_count(&self) -> u32 {
+        match self.vc_frame_count_cycle() {
+            Some(cycle) => ((cycle as u32) << 24) | self.vc_frame_count(),
+            None => self.vc_frame_count(),
+        }
+    }
+
+    /// True, if the frame is an idle frame
+    pub fn is_idle(&self) -> bool {
+        self.vcid() == AOS_IDLE_VCID
+    }
+
+    pub fn fhec(&self) -> Option<u16> {
+        if self.config.fhec {
+            Some(((self.data[6] as u16) << 8) | self.data[7] as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Checks the frame header error control. Returns true if there is no FHEC.
+    pub fn check_fhec(&self) -> bool {
+        match self.fhec() {
+            Some(fhec) => fhec == calc_fhec(self.data),
+            None => true,
+        }
+    }
+
+    pub fn insert_zone(&self) -> Option<&'a [u8]> {
+        if self.config.insert_zone_len > 0 {
+            let start = self.header_len() - self.config.insert_zone_len;
+            Some(&self.data[start..self.header_len()])
+        } else {
+            None
+        }
+    }
+
+    /// The data field of the frame (M_PDU or B_PDU including its header)
+    pub fn data_field(&self) -> &'a [u8] {
+        &self.data[self.header_len()..self.data.len() - self.trailer_len()]
+    }
+
+    /// Interprets the data field as M_PDU
+    pub fn m_pdu(&self) -> Result<AOSDataField<'a>, Error> {
+        let df = self.data_field();
+        if df.len() < Self::MPDU_HDR_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "AOS Frame: data field too short for M_PDU header",
+            ));
+        }
+        Ok(AOSDataField::MPDU {
+            first_header_pointer: ((df[0] as u16 & 0x07) << 8) | df[1] as u16,
+            packet_zone: &df[Self::MPDU_HDR_LEN..],
+        })
+    }
+
+    /// Interprets the data field as B_PDU
+    pub fn b_pdu(&self) -> Result<AOSDataField<'a>, Error> {
+        let df = self.data_field();
+        if df.len() < Self::BPDU_HDR_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "AOS Frame: data field too short for B_PDU header",
+            ));
+        }
+        Ok(AOSDataField::BPDU {
+            data_pointer: ((df[0] as u16 & 0x3F) << 8) | df[1] as u16,
+            bitstream: &df[Self::BPDU_HDR_LEN..],
+        })
+    }
+
+    /// The M_PDU first header pointer. Only valid for VCs carrying packets.
+    pub fn first_header_pointer(&self) -> Option<u16> {
+        match self.m_pdu() {
+            Ok(AOSDataField::MPDU {
+                first_header_pointer,
+                ..
+            }) => Some(first_header_pointer),
+            _ => None,
+        }
+    }
+
+    /// The M_PDU packet zone. Only valid for VCs carrying packets.
+    pub fn packet_zone(&self) -> Option<&'a [u8]> {
+        match self.m_pdu() {
+            Ok(AOSDataField::MPDU { packet_zone, .. }) => Some(packet_zone),
+            _ => None,
+        }
+    }
+
+    pub fn ocf(&self) -> Option<u32> {
+        if self.config.ocf {
+            let start = self.data.len() - self.trailer_len();
+            let mut arr = [0u8; 4];
+            arr.copy_from_slice(&self.data[start..start + Self::OCF_LEN]);
+            Some(u32::from_be_bytes(arr))
+        } else {
+            None
+        }
+    }
+
+    pub fn fecf(&self) -> Option<u16> {
+        if self.config.fecf {
+            let len = self.data.len();
+            Some(((self.data[len - 2] as u16) << 8) | self.data[len - 1] as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Calculates the CRC over the frame without the FECF
+    pub fn calc_fecf(&self) -> u16 {
+        let end = if self.config.fecf {
+            self.data.len() - Self::FECF_LEN
+        } else {
+            self.data.len()
+        };
+        calc_crc(&self.data[..end])
+    }
+
+    /// Checks the FECF. Frames without FECF are always valid.
+    pub fn check_fecf(&self) -> bool {
+        match self.fecf() {
+            Some(fecf) => fecf == self.calc_fecf(),
+            None => true,
+        }
+    }
+}
+
+// GF(16) with the field polynomial x^4 + x + 1, used for the frame header error
+// control, which is a Reed-Solomon (10,6) code over the first 6 header nibbles
+// (master channel ID, VCID and signaling field).
+const GF16_EXP: [u8; 15] = [1, 2, 4, 8, 3, 6, 12, 11, 5, 10, 7, 14, 15, 13, 9];
+
+fn gf16_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    let log_a = GF16_EXP.iter().position(|x| *x == a).unwrap_or(0);
+    let log_b = GF16_EXP.iter().position(|x| *x == b).unwrap_or(0);
+    GF16_EXP[(log_a + log_b) % 15]
+}
+
+/// The generator polynomial (x + a^6)(x + a^7)(x + a^8)(x + a^9), highest
+/// coefficient first
+fn fhec_generator() -> [u8; 5] {
+    let mut g = [0u8; 5];
+    g[0] = 1;
+    for (deg, root) in GF16_EXP[6..10].iter().enumerate() {
+        // multiply g by (x + root)
+        for i in (1..=deg + 1).rev() {
+            g[i] ^= gf16_mul(g[i - 1], *root);
+        }
+    }
+    g
+}
+
+/// Calculates the frame header error control for the header at the start of `hdr`
+fn calc_fhec(hdr: &[u8]) -> u16 {
+    let gen = fhec_generator();
+    let nibbles = [
+        hdr[0] >> 4,
+        hdr[0] & 0x0F,
+        hdr[1] >> 4,
+        hdr[1] & 0x0F,
+        hdr[5] >> 4,
+        hdr[5] & 0x0F,
+    ];
+
+    // systematic encoding: remainder of m(x) * x^4 divided by g(x)
+    let mut rem = [0u8; 4];
+    for n in nibbles {
+        let feedback = n ^ rem[0];
+        for i in 0..3 {
+            rem[i] = rem[i + 1] ^ gf16_mul(feedback, gen[i + 1]);
+        }
+        rem[3] = gf16_mul(feedback, gen[4]);
+    }
+
+    ((rem[0] as u16) << 12) | ((rem[1] as u16) << 8) | ((rem[2] as u16) << 4) | rem[3] as u16
+}
+
+/// Creates AOS transfer frames
+#[derive(Debug, Clone, Default)]
+pub struct AOSFrameBuilder {
+    config: AOSConfig,
+    scid: u8,
+    vcid: u8,
+    vc_frame_count: u32,
+    replay: bool,
+    vc_frame_count_cycle: Option<u8>,
+    insert_zone: Vec<u8>,
+    ocf: u32,
+}
+
+impl AOSFrameBuilder {
+    pub fn new(scid: u8, vcid: u8, config: &AOSConfig) -> AOSFrameBuilder {
+        AOSFrameBuilder {
+            config: *config,
+            scid,
+            vcid: vcid & 0x3F,
+            insert_zone: vec![0; config.insert_zone_len],
+            ..Default::default()
+        }
+    }
+
+    /// Set the 24 bit VC frame count
+    pub fn vc_frame_count(mut self, cnt: u32) -> Self {
+        self.vc_frame_count = cnt & 0xFF_FFFF;
+        self
+    }
+
+    /// Use the VC frame count cycle with the given value
+    pub fn vc_frame_count_cycle(mut self, cycle: Option<u8>) -> Self {
+        self.vc_frame_count_cycle = cycle.map(|c| c & 0x0F);
+        self
+    }
+
+    pub fn replay(mut self, flag: bool) -> Self {
+        self.replay = flag;
+        self
+    }
+
+    /// Set the content of the insert zone. It is truncated or padded with 0 to the
+    /// configured insert zone length.
+    pub fn insert_zone(mut self, data: &[u8]) -> Self {
+        let len = self.config.insert_zone_len;
+        self.insert_zone = data.iter().copied().chain(std::iter::repeat(0)).take(len).collect();
+        self
+    }
+
+    /// Set the OCF. Only used if the config contains an OCF.
+    pub fn ocf(mut self, ocf: u32) -> Self {
+        self.ocf = ocf;
+        self
+    }
+
+    /// The length of the frame with the given data field length
+    pub fn frame_length(&self, data_field_len: usize) -> usize {
+        let mut len = AOSFrame::PRIMARY_HDR_LEN + self.config.insert_zone_len + data_field_len;
+        if self.config.fhec {
+            len += AOSFrame::FHEC_LEN;
+        }
+        if self.config.ocf {
+            len += AOSFrame::OCF_LEN;
+        }
+        if self.config.fecf {
+            len += AOSFrame::FECF_LEN;
+        }
+        len
+    }
+
+    /// The length of the data field for a frame with the given total length
+    pub fn data_field_length(&self, frame_len: usize) -> Option<usize> {
+        frame_len.checked_sub(self.frame_length(0))
+    }
+
+    /// Builds a frame with the given data field
+    pub fn build(&self, data_field: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(self.frame_length(data_field.len()));
+
+        frame.push((AOS_VERSION << 6) | (self.scid >> 2));
+        frame.push((self.scid << 6) | self.vcid);
+        frame.extend_from_slice(&self.vc_frame_count.to_be_bytes()[1..]);
+
+        let mut signaling = (self.replay as u8) << 7;
+        if let Some(cycle) = self.vc_frame_count_cycle {
+            signaling |= 0x40 | cycle;
+        }
+        frame.push(signaling);
+
+        if self.config.fhec {
+            let fhec = calc_fhec(&frame);
+            frame.extend_from_slice(&fhec.to_be_bytes());
+        }
+
+        frame.extend_from_slice(&self.insert_zone);
+        frame.extend_from_slice(data_field);
+
+        if self.config.ocf {
+            frame.extend_from_slice(&self.ocf.to_be_bytes());
+        }
+        if self.config.fecf {
+            let crc = calc_crc(&frame);
+            frame.extend_from_slice(&crc.to_be_bytes());
+        }
+        frame
+    }
+
+    /// Builds a frame with an M_PDU data field
+    pub fn build_m_pdu(&self, first_header_pointer: u16, packet_zone: &[u8]) -> Vec<u8> {
+        let mut df = Vec::with_capacity(AOSFrame::MPDU_HDR_LEN + packet_zone.len());
+        df.extend_from_slice(&(first_header_pointer & 0x7FF).to_be_bytes());
+        df.extend_from_slice(packet_zone);
+        self.build(&df)
+    }
+
+    /// Builds a frame with a B_PDU data field
+    pub fn build_b_pdu(&self, data_pointer: u16, bitstream: &[u8]) -> Vec<u8> {
+        let mut df = Vec::with_capacity(AOSFrame::BPDU_HDR_LEN + bitstream.len());
+        df.extend_from_slice(&(data_pointer & 0x3FFF).to_be_bytes());
+        df.extend_from_slice(bitstream);
+        self.build(&df)
+    }
+}
@@ -1,8 +1,9 @@
 use crate::pus_types::{HexBytes, PktID, SSC};
 
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt, Error};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, Error, ErrorKind};
 
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +12,57 @@ pub enum PacketType {
     TC,
 }
 
+/// The packet error control field at the end of the packet data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ErrorControl {
+    /// The packet has no error control field
+    None,
+    /// CRC-16-CCITT
+    #[default]
+    Crc16,
+    /// The ISO 16 bit checksum of ECSS-E-ST-70-41
+    IsoChecksum,
+}
+
+impl ErrorControl {
+    /// The length of the error control field
+    pub fn field_length(&self) -> usize {
+        match self {
+            ErrorControl::None => 0,
+            ErrorControl::Crc16 | ErrorControl::IsoChecksum => 2,
+        }
+    }
+}
+
+/// The error control used per APID. APIDs which are not configured use the default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorControlPolicy {
+    #[serde(default)]
+    pub default: ErrorControl,
+    #[serde(default)]
+    pub apids: BTreeMap<u16, ErrorControl>,
+}
+
+impl ErrorControlPolicy {
+    /// A policy using the given error control for all APIDs
+    pub fn new(default: ErrorControl) -> ErrorControlPolicy {
+        ErrorControlPolicy {
+            default,
+            apids: BTreeMap::new(),
+        }
+    }
+
+    /// Use the error control for the APID
+    pub fn with_apid(mut self, apid: u16, ec: ErrorControl) -> Self {
+        self.apids.insert(apid, ec);
+        self
+    }
+
+    pub fn for_apid(&self, apid: u16) -> ErrorControl {
+        self.apids.get(&apid).copied().unwrap_or(self.default)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FastCcsdsPacket {
     pub hdr: [u8; 6],
@@ -35,16 +87,20 @@ impl FastCcsdsPacket {
         }
     }
 
-    pub fn length(&self) -> u16 {
+    pub fn length(&self) -> usize {
         let b1 = self.hdr[4];
         let b2 = self.hdr[5];
 
         // return the real length of the data. The field contains length - 1
-        (((b1 as u16) << 8) | (b2 as u16)) + 1
+        (((b1 as usize) << 8) | (b2 as usize)) + 1
     }
 
     pub fn total_length(&self) -> usize {
-        self.length() as usize + Self::HDR_LEN
+        self.length() + Self::HDR_LEN
+    }
+
+    pub fn apid(&self) -> u16 {
+        ((self.hdr[0] as u16 & 0x07) << 8) | self.hdr[1] as u16
     }
 
     pub fn crc_value(&self) -> u16 {
@@ -77,6 +133,83 @@ impl FastCcsdsPacket {
         self.data.0[len - 1] = (crc & 0xFF) as u8;
     }
 
+    /// The value of the error control field, None if the packet has none or is too
+    /// short to contain it
+    pub fn error_control_value(&self, ec: ErrorControl) -> Option<u16> {
+        if ec == ErrorControl::None || self.data.len() < ec.field_length() {
+            return None;
+        }
+        Some(self.crc_value())
+    }
+
+    /// Calculates the error control over the packet without the error control field
+    pub fn calc_error_control(&self, ec: ErrorControl) -> Option<u16> {
+        let len = self.data.len().checked_sub(ec.field_length())?;
+        let data = &self.data.0[..len];
+        match ec {
+            ErrorControl::None => None,
+            ErrorControl::Crc16 => Some(crate::crc::calc_crc2(&self.hdr, data)),
+            ErrorControl::IsoChecksum => Some(crate::crc::calc_iso_checksum2(&self.hdr, data)),
+        }
+    }
+
+    /// Checks the error control field. Packets without error control are always valid.
+    pub fn check_error_control(&self, ec: ErrorControl) -> bool {
+        match ec {
+            ErrorControl::None => true,
+            ErrorControl::Crc16 => self.data.len() >= 2 && self.check_crc(),
+            ErrorControl::IsoChecksum => {
+                self.data.len() >= 2 && crate::crc::check_iso_checksum2(&self.hdr, &self.data.0)
+            }
+        }
+    }
+
+    /// Calculates the error control over the packet and appends it. The length in
+    /// the header has to include the error control field already.
+    pub fn append_error_control(&mut self, ec: ErrorControl) {
+        let value = match ec {
+            ErrorControl::None => return,
+            ErrorControl::Crc16 => crate::crc::calc_crc2(&self.hdr, &self.data.0),
+            ErrorControl::IsoChecksum => crate::crc::calc_iso_checksum2(&self.hdr, &self.data.0),
+        };
+        self.data.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Parse a packet from the start of the slice. The slice may contain more data
+    /// after the packet, use [FastCcsdsPacket::total_length] to get the consumed length.
+    pub fn from_slice(arr: &[u8]) -> Result<FastCcsdsPacket, Error> {
+        if arr.len() < Self::HDR_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("CCSDS Packet: not enough data for header: {} bytes", arr.len()),
+            ));
+        }
+
+        let mut pkt = FastCcsdsPacket::new_header_only();
+        pkt.hdr.copy_from_slice(&arr[..Self::HDR_LEN]);
+
+        let total = pkt.total_length();
+        if arr.len() < total {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "CCSDS Packet: not enough data: {} bytes, packet length is {total}",
+                    arr.len()
+                ),
+            ));
+        }
+        pkt.data.0.extend_from_slice(&arr[Self::HDR_LEN..total]);
+        Ok(pkt)
+    }
+
+    /// Returns the encoded packet
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(self.total_length());
+        res.extend_from_slice(&self.hdr);
+        res.extend_from_slice(&self.data.0);
+        res
+    }
+
     pub async fn read_from_async<T: AsyncReadExt + Unpin>(
         &mut self,
         reader: &mut T,
@@ -88,7 +221,7 @@ impl FastCcsdsPacket {
         let len = self.length();
 
         // resize the data to contain the new data
-        self.data.0.resize(len as usize, 0);
+        self.data.0.resize(len, 0);
 
         // read in the data, returns the size of the data part or Error
         reader.read_exact(&mut self.data.0).await
@@ -102,7 +235,7 @@ impl FastCcsdsPacket {
         let len = self.length();
 
         // resize the data to contain the new data
-        self.data.0.resize(len as usize, 0);
+        self.data.0.resize(len, 0);
 
         // read in the data, returns the size of the data part or Error
         reader.read_exact(&mut self.data.0)
@@ -134,12 +267,22 @@ pub struct CcsdsPacket {
 }
 
 impl CcsdsPacket {
-    pub fn from_fast_ccsds_pkt(mut pkt: FastCcsdsPacket) -> CcsdsPacket {
+    /// The maximum length of the data field, including the error control
+    pub const MAX_DATA_LENGTH: usize = 65536;
+
+    /// Convert a packet which has a CRC
+    pub fn from_fast_ccsds_pkt(pkt: FastCcsdsPacket) -> CcsdsPacket {
+        Self::from_fast_ccsds_pkt_with(pkt, ErrorControl::Crc16)
+    }
+
+    /// Convert a packet with the given error control, which is removed from the data.
+    /// The error control is not checked.
+    pub fn from_fast_ccsds_pkt_with(mut pkt: FastCcsdsPacket, ec: ErrorControl) -> CcsdsPacket {
         let pkt_id = PktID::new_from_bytes(&pkt.hdr[0..2]);
         let ssc = SSC::new_from_bytes(&pkt.hdr[2..4]);
 
-        // store data without the CRC, so remove the last 2 bytes
-        pkt.data.0.truncate(pkt.data.0.len() - 2);
+        let len = pkt.data.0.len().saturating_sub(ec.field_length());
+        pkt.data.0.truncate(len);
 
         CcsdsPacket {
             pkt_id,
@@ -148,7 +291,25 @@ impl CcsdsPacket {
         }
     }
 
-    pub fn to_fast_ccsds_pkt(self) -> FastCcsdsPacket {
+    /// Convert the packet, appending a CRC
+    pub fn to_fast_ccsds_pkt(self) -> Result<FastCcsdsPacket, Error> {
+        self.to_fast_ccsds_pkt_with(ErrorControl::Crc16)
+    }
+
+    /// Convert the packet, appending the given error control. Fails, if the data
+    /// field including the error control is empty or longer than 65536 bytes.
+    pub fn to_fast_ccsds_pkt_with(self, ec: ErrorControl) -> Result<FastCcsdsPacket, Error> {
+        let len = self.data.0.len() + ec.field_length();
+        if len == 0 || len > Self::MAX_DATA_LENGTH {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "CCSDS Packet: invalid data field length {len}, must be 1 to {}",
+                    Self::MAX_DATA_LENGTH
+                ),
+            ));
+        }
+
         let mut pkt = FastCcsdsPacket::new_header_only();
 
         // set the header fields: pkt ID
@@ -156,15 +317,16 @@ impl CcsdsPacket {
         // set the SSC
         self.ssc.to_bytes(&mut pkt.hdr[2..4]);
 
-        // remember, in the header, the data length - 1 is stored;
-        let enc_len = (self.data.0.len() - 1) as u16;
+        // remember, in the header, the data length - 1 is stored. The data length
+        // includes the error control appended below
+        let enc_len = (len - 1) as u16;
 
         pkt.hdr[4] = (enc_len >> 8) as u8;
         pkt.hdr[5] = (enc_len & 0xFF) as u8;
         pkt.data = self.data;
 
-        pkt.calc_and_append_crc();
+        pkt.append_error_control(ec);
 
-        pkt
+        Ok(pkt)
     }
 }
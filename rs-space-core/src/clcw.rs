@@ -0,0 +1,142 @@
+//! The Communications Link Control Word (CCSDS 232.0-B), transported in the
+//! operational control field of TM and AOS frames.
+//!
+//! The CLCW reports the state of the FARM of a TC virtual channel back to the
+//! sending FOP.
+use std::io::{Error, ErrorKind};
+
+use crate::aos_frame::{AOSFrame, AOSFrameBuilder};
+use crate::tm_frame::{TMFrame, TMFrameBuilder};
+
+/// The value of the COP in effect field for COP-1
+pub const COP_1: u8 = 1;
+
+/// The contents of a CLCW
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clcw {
+    /// The CLCW version number, always 0
+    pub version: u8,
+    /// The mission specific status field (3 bits)
+    pub status: u8,
+    /// The COP in effect (2 bits), 1 for COP-1
+    pub cop_in_effect: u8,
+    /// The TC virtual channel the CLCW reports on (6 bits)
+    pub vcid: u8,
+    pub no_rf_available: bool,
+    pub no_bit_lock: bool,
+    pub lockout: bool,
+    pub wait: bool,
+    pub retransmit: bool,
+    /// The two least significant bits of the FARM-B counter
+    pub farm_b_counter: u8,
+    /// The next expected frame sequence number N(R)
+    pub report_value: u8,
+}
+
+impl Default for Clcw {
+    fn default() -> Self {
+        Clcw {
+            version: 0,
+            status: 0,
+            cop_in_effect: COP_1,
+            vcid: 0,
+            no_rf_available: false,
+            no_bit_lock: false,
+            lockout: false,
+            wait: false,
+            retransmit: false,
+            farm_b_counter: 0,
+            report_value: 0,
+        }
+    }
+}
+
+impl Clcw {
+    /// Create a COP-1 CLCW for the given virtual channel
+    pub fn new(vcid: u8) -> Clcw {
+        Clcw {
+            vcid: vcid & 0x3F,
+            ..Default::default()
+        }
+    }
+
+    /// True, if the control word type of the OCF indicates a CLCW
+    pub fn is_clcw(ocf: u32) -> bool {
+        (ocf & 0x8000_0000) == 0
+    }
+
+    /// Parse the CLCW from an OCF. Fails, if the OCF does not contain a CLCW.
+    pub fn from_ocf(ocf: u32) -> Result<Clcw, Error> {
+        if !Self::is_clcw(ocf) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("CLCW: OCF {ocf:08X} is not a CLCW"),
+            ));
+        }
+
+        let version = ((ocf >> 29) & 0x03) as u8;
+        if version != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("CLCW: unsupported version {version}"),
+            ));
+        }
+
+        Ok(Clcw {
+            version,
+            status: ((ocf >> 26) & 0x07) as u8,
+            cop_in_effect: ((ocf >> 24) & 0x03) as u8,
+            vcid: ((ocf >> 18) & 0x3F) as u8,
+            no_rf_available: (ocf & 0x8000) != 0,
+            no_bit_lock: (ocf & 0x4000) != 0,
+            lockout: (ocf & 0x2000) != 0,
+            wait: (ocf & 0x1000) != 0,
+            retransmit: (ocf & 0x0800) != 0,
+            farm_b_counter: ((ocf >> 9) & 0x03) as u8,
+            report_value: (ocf & 0xFF) as u8,
+        })
+    }
+
+    /// Encode the CLCW as OCF. The spare bits are set to 0.
+    pub fn to_ocf(&self) -> u32 {
+        (((self.version & 0x03) as u32) << 29)
+            | (((self.status & 0x07) as u32) << 26)
+            | (((self.cop_in_effect & 0x03) as u32) << 24)
+            | (((self.vcid & 0x3F) as u32) << 18)
+            | ((self.no_rf_available as u32) << 15)
+            | ((self.no_bit_lock as u32) << 14)
+            | ((self.lockout as u32) << 13)
+            | ((self.wait as u32) << 12)
+            | ((self.retransmit as u32) << 11)
+            | (((self.farm_b_counter & 0x03) as u32) << 9)
+            | self.report_value as u32
+    }
+}
+
+impl TMFrame<'_> {
+    /// The CLCW, if the frame has an OCF containing a CLCW
+    pub fn clcw(&self) -> Option<Clcw> {
+        self.ocf().and_then(|ocf| Clcw::from_ocf(ocf).ok())
+    }
+}
+
+impl AOSFrame<'_> {
+    /// The CLCW, if the frame has an OCF containing a CLCW
+    pub fn clcw(&self) -> Option<Clcw> {
+        self.ocf().and_then(|ocf| Clcw::from_ocf(ocf).ok())
+    }
+}
+
+impl TMFrameBuilder {
+    /// Put the CLCW into the OCF
+    pub fn clcw(self, clcw: &Clcw) -> Self {
+        self.ocf(clcw.to_ocf())
+    }
+}
+
+impl AOSFrameBuilder {
+    /// Put the CLCW into the OCF. Only used if the config contains an OCF.
+    pub fn clcw(self, clcw: &Clcw) -> Self {
+        self.ocf(clcw.to_ocf())
+    }
+}
@@ -0,0 +1,301 @@
+//! Communications Link Transmission Units (CCSDS 231.0-B).
+//!
+//! A CLTU consists of the start sequence, the TC frame(s) encoded in BCH(63,56)
+//! code blocks and the tail sequence. The last code block is padded with fill
+//! bytes. [decode] removes the coding again, correcting single bit errors per
+//! code block if requested.
+use std::io::{Error, ErrorKind};
+
+use crate::tc_frame::TCFrame;
+
+/// The start sequence of a CLTU
+pub const START_SEQUENCE: [u8; 2] = [0xEB, 0x90];
+/// The tail sequence of a CLTU
+pub const TAIL_SEQUENCE: [u8; 8] = [0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0xC5, 0x79];
+/// The fill byte used to complete the last code block
+pub const FILL_BYTE: u8 = 0x55;
+
+/// The number of information bytes in a code block
+pub const CODE_BLOCK_INFO_LEN: usize = 7;
+/// The length of a complete code block including the parity byte
+pub const CODE_BLOCK_LEN: usize = CODE_BLOCK_INFO_LEN + 1;
+
+// the generator polynomial g(x) = x^7 + x^6 + x^2 + 1 without the x^7 term
+const GENERATOR: u8 = 0b0100_0101;
+
+// maps a syndrome to the erroneous bit position in the 63 bit code word plus 1,
+// 0 if the syndrome does not belong to a single bit error
+const SYNDROME_TABLE: [u8; 128] = build_syndrome_table();
+
+/// Calculate the 7 parity bits of the information bytes (not complemented)
+const fn parity_bits(info: &[u8; CODE_BLOCK_INFO_LEN]) -> u8 {
+    let mut sr: u8 = 0;
+    let mut i = 0;
+    while i < CODE_BLOCK_INFO_LEN {
+        let mut bit = 8;
+        while bit > 0 {
+            bit -= 1;
+            let feedback = ((sr >> 6) ^ (info[i] >> bit)) & 1;
+            sr = (sr << 1) & 0x7F;
+            if feedback != 0 {
+                sr ^= GENERATOR;
+            }
+        }
+        i += 1;
+    }
+    sr
+}
+
+const fn build_syndrome_table() -> [u8; 128] {
+    let mut table = [0u8; 128];
+
+    // errors in the information bits
+    let mut pos = 0;
+    while pos < CODE_BLOCK_INFO_LEN * 8 {
+        let mut info = [0u8; CODE_BLOCK_INFO_LEN];
+        info[pos / 8] = 0x80 >> (pos % 8);
+        table[parity_bits(&info) as usize] = pos as u8 + 1;
+        pos += 1;
+    }
+    // errors in the parity bits
+    let mut bit = 0;
+    while bit < 7 {
+        table[1 << (6 - bit)] = (CODE_BLOCK_INFO_LEN * 8 + bit) as u8 + 1;
+        bit += 1;
+    }
+    table
+}
+
+/// Encode the information bytes into a code block. The parity bits are
+/// complemented and followed by the filler bit 0.
+pub fn encode_code_block(info: &[u8; CODE_BLOCK_INFO_LEN]) -> [u8; CODE_BLOCK_LEN] {
+    let mut block = [0u8; CODE_BLOCK_LEN];
+    block[..CODE_BLOCK_INFO_LEN].copy_from_slice(info);
+    block[CODE_BLOCK_INFO_LEN] = (!parity_bits(info) & 0x7F) << 1;
+    block
+}
+
+/// The result of decoding a single code block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeBlockStatus {
+    /// The code block was received without errors
+    Ok,
+    /// A single bit error at the given bit position of the code block was corrected
+    Corrected(usize),
+    /// The code block contains errors which could not be corrected
+    Uncorrectable,
+}
+
+/// How errors in code blocks are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodeMode {
+    /// Single bit errors are corrected
+    #[default]
+    ErrorCorrection,
+    /// All errors are treated as uncorrectable
+    ErrorDetection,
+}
+
+/// Decode a code block in place. Only the information bytes are corrected.
+pub fn decode_code_block(block: &mut [u8; CODE_BLOCK_LEN], mode: DecodeMode) -> CodeBlockStatus {
+    let mut info = [0u8; CODE_BLOCK_INFO_LEN];
+    info.copy_from_slice(&block[..CODE_BLOCK_INFO_LEN]);
+
+    let received = !(block[CODE_BLOCK_INFO_LEN] >> 1) & 0x7F;
+    let syndrome = parity_bits(&info) ^ received;
+    if syndrome == 0 {
+        return CodeBlockStatus::Ok;
+    }
+    if mode == DecodeMode::ErrorDetection {
+        return CodeBlockStatus::Uncorrectable;
+    }
+
+    match SYNDROME_TABLE[syndrome as usize] {
+        0 => CodeBlockStatus::Uncorrectable,
+        pos => {
+            let pos = (pos - 1) as usize;
+            if pos < CODE_BLOCK_INFO_LEN * 8 {
+                block[pos / 8] ^= 0x80 >> (pos % 8);
+            }
+            CodeBlockStatus::Corrected(pos)
+        }
+    }
+}
+
+/// Encode the data (one or more TC frames) into a CLTU
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(CODE_BLOCK_INFO_LEN);
+    let mut cltu = Vec::with_capacity(
+        START_SEQUENCE.len() + blocks * CODE_BLOCK_LEN + TAIL_SEQUENCE.len(),
+    );
+
+    cltu.extend_from_slice(&START_SEQUENCE);
+    for chunk in data.chunks(CODE_BLOCK_INFO_LEN) {
+        let mut info = [FILL_BYTE; CODE_BLOCK_INFO_LEN];
+        info[..chunk.len()].copy_from_slice(chunk);
+        cltu.extend_from_slice(&encode_code_block(&info));
+    }
+    cltu.extend_from_slice(&TAIL_SEQUENCE);
+    cltu
+}
+
+/// The result of decoding a CLTU
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DecodedCltu {
+    /// The information bytes of all code blocks before the tail sequence or the
+    /// first uncorrectable code block, including the fill bytes
+    pub data: Vec<u8>,
+    /// The indices of the code blocks in which a bit error was corrected
+    pub corrected_blocks: Vec<usize>,
+    /// The index of the uncorrectable code block which ended the decoding, if any
+    pub uncorrectable_block: Option<usize>,
+    /// True, if the tail sequence was found
+    pub tail_found: bool,
+}
+
+impl DecodedCltu {
+    /// True, if all code blocks up to the tail sequence could be decoded
+    pub fn is_complete(&self) -> bool {
+        self.tail_found && self.uncorrectable_block.is_none()
+    }
+
+    /// Split the decoded data into TC frames, using the frame length from the frame
+    /// headers. Trailing fill bytes are dropped.
+    pub fn frames(&self) -> Vec<&[u8]> {
+        let mut frames = Vec::new();
+        let mut data = &self.data[..];
+        while let Ok(frame) = TCFrame::new(data, false, false) {
+            let len = frame.as_bytes().len();
+            frames.push(&data[..len]);
+            data = &data[len..];
+        }
+        frames
+    }
+}
+
+/// Decode a CLTU, which must start with the start sequence. Decoding stops at the
+/// tail sequence or at the first uncorrectable code block, as a decoder on
+/// board would do.
+pub fn decode(cltu: &[u8], mode: DecodeMode) -> Result<DecodedCltu, Error> {
+    if !cltu.starts_with(&START_SEQUENCE) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "CLTU: start sequence not found".to_string(),
+        ));
+    }
+
+    let mut result = DecodedCltu::default();
+    for (idx, chunk) in cltu[START_SEQUENCE.len()..]
+        .chunks_exact(CODE_BLOCK_LEN)
+        .enumerate()
+    {
+        if chunk == TAIL_SEQUENCE {
+            result.tail_found = true;
+            break;
+        }
+
+        let mut block = [0u8; CODE_BLOCK_LEN];
+        block.copy_from_slice(chunk);
+        match decode_code_block(&mut block, mode) {
+            CodeBlockStatus::Ok => {}
+            CodeBlockStatus::Corrected(_) => result.corrected_blocks.push(idx),
+            CodeBlockStatus::Uncorrectable => {
+                result.uncorrectable_block = Some(idx);
+                break;
+            }
+        }
+        result.data.extend_from_slice(&block[..CODE_BLOCK_INFO_LEN]);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tc_frame::TCFrameBuilder;
+
+    fn frame() -> Vec<u8> {
+        TCFrameBuilder::new(0x123, 5)
+            .sequence_number(7)
+            .fecf(true)
+            .build(&[0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x02, 0x03, 0x04, 0x05])
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let frame = frame();
+        let cltu = encode(&frame);
+        let blocks = frame.len().div_ceil(CODE_BLOCK_INFO_LEN);
+        assert_eq!(
+            cltu.len(),
+            START_SEQUENCE.len() + blocks * CODE_BLOCK_LEN + TAIL_SEQUENCE.len()
+        );
+        assert!(cltu.starts_with(&START_SEQUENCE));
+        assert!(cltu.ends_with(&TAIL_SEQUENCE));
+
+        let decoded = decode(&cltu, DecodeMode::ErrorCorrection).unwrap();
+        assert!(decoded.is_complete());
+        assert!(decoded.corrected_blocks.is_empty());
+        assert_eq!(&decoded.data[..frame.len()], &frame[..]);
+        assert!(decoded.data[frame.len()..].iter().all(|b| *b == FILL_BYTE));
+        assert_eq!(decoded.frames(), vec![&frame[..]]);
+    }
+
+    #[test]
+    fn code_block_parity() {
+        // the complemented parity of all zero information bits, followed by the
+        // filler bit
+        assert_eq!(encode_code_block(&[0; CODE_BLOCK_INFO_LEN])[7], 0xFE);
+        let mut block = encode_code_block(&[0xFF; CODE_BLOCK_INFO_LEN]);
+        assert_eq!(
+            decode_code_block(&mut block, DecodeMode::ErrorCorrection),
+            CodeBlockStatus::Ok
+        );
+    }
+
+    #[test]
+    fn corrects_every_single_bit_error() {
+        let frame = frame();
+        let cltu = encode(&frame);
+        let expected = decode(&cltu, DecodeMode::ErrorCorrection).unwrap().data;
+        let coded = START_SEQUENCE.len()..cltu.len() - TAIL_SEQUENCE.len();
+
+        for bit in coded.start * 8..coded.end * 8 {
+            let mut received = cltu.clone();
+            received[bit / 8] ^= 0x80 >> (bit % 8);
+            let block_bit = bit - coded.start * 8;
+            let block = block_bit / (CODE_BLOCK_LEN * 8);
+            // the last bit of a code block is the filler bit, which is not coded
+            let filler = block_bit % (CODE_BLOCK_LEN * 8) == CODE_BLOCK_LEN * 8 - 1;
+
+            let decoded = decode(&received, DecodeMode::ErrorCorrection).unwrap();
+            assert!(decoded.is_complete(), "bit {bit}");
+            assert_eq!(decoded.data, expected, "bit {bit}");
+            if filler {
+                assert!(decoded.corrected_blocks.is_empty(), "bit {bit}");
+            } else {
+                assert_eq!(decoded.corrected_blocks, vec![block], "bit {bit}");
+            }
+
+            let decoded = decode(&received, DecodeMode::ErrorDetection).unwrap();
+            if filler {
+                assert!(decoded.is_complete(), "bit {bit}");
+            } else {
+                assert_eq!(decoded.uncorrectable_block, Some(block), "bit {bit}");
+                assert_eq!(decoded.data.len(), block * CODE_BLOCK_INFO_LEN);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_double_bit_errors() {
+        let mut block = encode_code_block(&[0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE]);
+        block[0] ^= 0x81;
+        assert_eq!(
+            decode_code_block(&mut block, DecodeMode::ErrorCorrection),
+            CodeBlockStatus::Uncorrectable
+        );
+        assert!(decode(&[0x00, 0x01], DecodeMode::ErrorCorrection).is_err());
+    }
+}
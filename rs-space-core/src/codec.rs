@@ -0,0 +1,223 @@
+//! [tokio_util::codec] implementations for packet and CADU streams.
+//!
+//! The codecs can be used with `FramedRead`, `FramedWrite` or `Framed` on any
+//! `AsyncRead`/`AsyncWrite` (e.g. a TCP socket). In contrast to
+//! [FastCcsdsPacket::read_from_async], reading is cancel safe and the size of the
+//! received items is limited, so a corrupted length field can not make the reader
+//! allocate arbitrary amounts of memory.
+use std::io::{Error, ErrorKind};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::ccsds_packet::FastCcsdsPacket;
+use crate::frame_sync::DEFAULT_ASM;
+use crate::pus_types::HexBytes;
+
+const HDR_LEN: usize = FastCcsdsPacket::HDR_LEN;
+
+/// Codec for a stream of space packets without any additional framing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpacePacketCodec {
+    max_length: usize,
+}
+
+impl SpacePacketCodec {
+    /// The maximum length of a space packet including the header
+    pub const MAX_PACKET_LENGTH: usize = HDR_LEN + 65536;
+
+    /// A codec accepting all valid packet lengths
+    pub fn new() -> SpacePacketCodec {
+        SpacePacketCodec {
+            max_length: Self::MAX_PACKET_LENGTH,
+        }
+    }
+
+    /// Limits the total length (including the header) of decoded and encoded packets
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    fn check_length(&self, len: usize) -> Result<(), Error> {
+        if len > self.max_length {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Space Packet Codec: packet length {len} exceeds maximum of {}",
+                    self.max_length
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SpacePacketCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for SpacePacketCodec {
+    type Item = FastCcsdsPacket;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < HDR_LEN {
+            return Ok(None);
+        }
+        let total = (((src[4] as usize) << 8) | src[5] as usize) + 1 + HDR_LEN;
+        self.check_length(total)?;
+
+        if src.len() < total {
+            src.reserve(total - src.len());
+            return Ok(None);
+        }
+
+        let mut pkt = FastCcsdsPacket::new_header_only();
+        pkt.hdr.copy_from_slice(&src[..HDR_LEN]);
+        pkt.data = HexBytes(src[HDR_LEN..total].to_vec());
+        src.advance(total);
+        Ok(Some(pkt))
+    }
+}
+
+impl Encoder<FastCcsdsPacket> for SpacePacketCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: FastCcsdsPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let total = HDR_LEN + item.data.len();
+        self.check_length(total)?;
+        if total != item.total_length() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Space Packet Codec: header specifies {} bytes, packet has {total}",
+                    item.total_length()
+                ),
+            ));
+        }
+
+        dst.reserve(total);
+        dst.put_slice(&item.hdr);
+        dst.put_slice(&item.data.0);
+        Ok(())
+    }
+}
+
+/// Codec for a byte aligned stream of CADUs with a fixed length.
+///
+/// Decoding returns the frames without the attached sync marker. If the stream does
+/// not start with a marker, the data up to the next marker is skipped. For streams
+/// which are not byte aligned or contain bit errors in the marker, use the
+/// [FrameSynchronizer](crate::frame_sync::FrameSynchronizer) instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaduCodec {
+    asm: [u8; 4],
+    frame_length: usize,
+    skipped: u64,
+}
+
+impl CaduCodec {
+    /// Creates a codec for frames of the given length (without the marker)
+    pub fn new(frame_length: usize) -> Result<CaduCodec, Error> {
+        if frame_length == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "CADU Codec: frame length must not be 0".to_string(),
+            ));
+        }
+        Ok(CaduCodec {
+            asm: DEFAULT_ASM.to_be_bytes(),
+            frame_length,
+            skipped: 0,
+        })
+    }
+
+    /// Use another attached sync marker than the default
+    pub fn asm(mut self, asm: u32) -> Self {
+        self.asm = asm.to_be_bytes();
+        self
+    }
+
+    pub fn frame_length(&self) -> usize {
+        self.frame_length
+    }
+
+    /// The number of bytes which have been skipped while searching for a marker
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped
+    }
+
+    fn cadu_length(&self) -> usize {
+        self.asm.len() + self.frame_length
+    }
+}
+
+impl Decoder for CaduCodec {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let asm_len = self.asm.len();
+        if src.len() < asm_len {
+            return Ok(None);
+        }
+
+        if src[..asm_len] != self.asm {
+            // skip to the next marker. If there is none, keep the last bytes, which
+            // could be the start of a marker
+            let pos = src
+                .windows(asm_len)
+                .position(|w| w == self.asm)
+                .unwrap_or(src.len() - (asm_len - 1));
+            src.advance(pos);
+            self.skipped += pos as u64;
+            if src.len() < asm_len {
+                return Ok(None);
+            }
+        }
+
+        let total = self.cadu_length();
+        if src.len() < total {
+            src.reserve(total - src.len());
+            return Ok(None);
+        }
+
+        let mut cadu = src.split_to(total);
+        cadu.advance(asm_len);
+        Ok(Some(cadu.freeze()))
+    }
+}
+
+impl Encoder<&[u8]> for CaduCodec {
+    type Error = Error;
+
+    /// Encodes a frame by prepending the marker. The frame needs to have the
+    /// configured frame length.
+    fn encode(&mut self, item: &[u8], dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.len() != self.frame_length {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "CADU Codec: frame has {} bytes, expected {}",
+                    item.len(),
+                    self.frame_length
+                ),
+            ));
+        }
+        dst.reserve(self.cadu_length());
+        dst.put_slice(&self.asm);
+        dst.put_slice(item);
+        Ok(())
+    }
+}
+
+impl Encoder<Bytes> for CaduCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&item[..], dst)
+    }
+}
@@ -48,4 +48,71 @@ pub fn calc_crc2(slc1: &[u8], slc2: &[u8]) -> u16 {
     }
     crc as u16
 
-}
\ No newline at end of file
+}
+
+/// Generator polynomial of the 32 bit CRC used e.g. for the USLP FECF:
+/// x^32 + x^23 + x^21 + x^11 + x^2 + 1
+const CRC32_POLY: u32 = 0x00A0_0805;
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ CRC32_POLY
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// Calculates the CCSDS 32 bit CRC (register preset to all ones, no reflection and
+/// no final XOR, like the 16 bit CRC)
+pub fn calc_crc32(slc: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+
+    for data in slc {
+        let idx = ((crc >> 24) ^ (*data as u32)) as usize;
+        crc = (crc << 8) ^ CRC32_TABLE[idx];
+    }
+    crc
+}
+
+/// Calculates the ISO 16 bit checksum (ECSS-E-ST-70-41, Annex B) over both slices.
+/// The result contains CK1 in the high and CK2 in the low byte.
+pub fn calc_iso_checksum2(slc1: &[u8], slc2: &[u8]) -> u16 {
+    let mut c0: u32 = 0;
+    let mut c1: u32 = 0;
+
+    for data in slc1.iter().chain(slc2) {
+        c0 = (c0 + *data as u32) % 255;
+        c1 = (c1 + c0) % 255;
+    }
+    // one's complement arithmetic, zero is represented as 0xFF
+    let ck1 = 255 - (c0 + c1) % 255;
+    let ck2 = if c1 == 0 { 255 } else { c1 };
+    ((ck1 as u16) << 8) | ck2 as u16
+}
+
+/// Checks data with an appended ISO 16 bit checksum: both running sums over the
+/// data including the checksum have to be 0
+pub fn check_iso_checksum2(slc1: &[u8], slc2: &[u8]) -> bool {
+    let mut c0: u32 = 0;
+    let mut c1: u32 = 0;
+
+    for data in slc1.iter().chain(slc2) {
+        c0 = (c0 + *data as u32) % 255;
+        c1 = (c1 + c0) % 255;
+    }
+    c0 == 0 && c1 == 0
+}
@@ -0,0 +1,323 @@
+//! CCSDS Encapsulation Packets (CCSDS 133.1-B).
+//!
+//! Encapsulation packets have the packet version number 7 and a variable length
+//! header: the length of length field selects a header of 1 (idle packets only),
+//! 2, 4 or 8 bytes with a packet length field of 0, 1, 2 or 4 bytes. The 4 and 8
+//! byte headers also contain the user defined field and the protocol ID extension,
+//! the 8 byte header additionally the CCSDS defined field. The packet length is the
+//! total length of the packet including the header.
+use std::io::{Error, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pus_types::HexBytes;
+
+/// The packet version number of encapsulation packets
+pub const ENCAP_VERSION: u8 = 7;
+
+/// Encapsulation idle packet
+pub const PID_IDLE: u8 = 0b000;
+/// Internet Protocol Extension, used to carry IP datagrams
+pub const PID_IPE: u8 = 0b010;
+/// The protocol is given in the protocol ID extension
+pub const PID_EXTENDED: u8 = 0b110;
+/// Mission specific, privately defined data
+pub const PID_MISSION_SPECIFIC: u8 = 0b111;
+
+/// The length of the packet length field, which determines the header length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LengthOfLength {
+    /// 1 byte header without length, only for idle packets
+    NoLength,
+    /// 2 byte header with a 1 byte length
+    OneOctet,
+    /// 4 byte header with a 2 byte length
+    TwoOctets,
+    /// 8 byte header with a 4 byte length
+    FourOctets,
+}
+
+impl LengthOfLength {
+    pub fn from_bits(val: u8) -> LengthOfLength {
+        match val & 0x03 {
+            0b00 => LengthOfLength::NoLength,
+            0b01 => LengthOfLength::OneOctet,
+            0b10 => LengthOfLength::TwoOctets,
+            _ => LengthOfLength::FourOctets,
+        }
+    }
+
+    pub fn to_bits(&self) -> u8 {
+        match self {
+            LengthOfLength::NoLength => 0b00,
+            LengthOfLength::OneOctet => 0b01,
+            LengthOfLength::TwoOctets => 0b10,
+            LengthOfLength::FourOctets => 0b11,
+        }
+    }
+
+    pub fn header_length(&self) -> usize {
+        match self {
+            LengthOfLength::NoLength => 1,
+            LengthOfLength::OneOctet => 2,
+            LengthOfLength::TwoOctets => 4,
+            LengthOfLength::FourOctets => 8,
+        }
+    }
+
+    /// The maximum total length of a packet with this header
+    pub fn max_packet_length(&self) -> usize {
+        match self {
+            LengthOfLength::NoLength => 1,
+            LengthOfLength::OneOctet => u8::MAX as usize,
+            LengthOfLength::TwoOctets => u16::MAX as usize,
+            LengthOfLength::FourOctets => u32::MAX as usize,
+        }
+    }
+
+    /// The smallest header (with length field) for a packet with the given data length
+    fn for_data_length(len: usize) -> LengthOfLength {
+        [
+            LengthOfLength::OneOctet,
+            LengthOfLength::TwoOctets,
+            LengthOfLength::FourOctets,
+        ]
+        .into_iter()
+        .find(|lol| len + lol.header_length() <= lol.max_packet_length())
+        .unwrap_or(LengthOfLength::FourOctets)
+    }
+}
+
+/// The total length of the encapsulation packet at the start of the data according
+/// to its header. None, if the data is too short for the header.
+pub fn packet_length(data: &[u8]) -> Option<usize> {
+    let lol = LengthOfLength::from_bits(*data.first()?);
+    let hdr = data.get(..lol.header_length())?;
+    let len = match lol {
+        LengthOfLength::NoLength => 1,
+        LengthOfLength::OneOctet => hdr[1] as usize,
+        LengthOfLength::TwoOctets => u16::from_be_bytes([hdr[2], hdr[3]]) as usize,
+        LengthOfLength::FourOctets => {
+            u32::from_be_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]) as usize
+        }
+    };
+    Some(len)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncapsulationPacket {
+    pub protocol_id: u8,
+    pub length_of_length: LengthOfLength,
+    /// 4 bits, only present in 4 and 8 byte headers
+    pub user_defined: u8,
+    /// 4 bits, only present in 4 and 8 byte headers
+    pub protocol_id_extension: u8,
+    /// Only present in 8 byte headers
+    pub ccsds_defined: u16,
+    pub data: HexBytes,
+}
+
+impl EncapsulationPacket {
+    /// Creates a packet with the smallest header which can hold the data
+    pub fn new(protocol_id: u8, data: Vec<u8>) -> EncapsulationPacket {
+        EncapsulationPacket {
+            protocol_id: protocol_id & 0x07,
+            length_of_length: LengthOfLength::for_data_length(data.len()),
+            user_defined: 0,
+            protocol_id_extension: 0,
+            ccsds_defined: 0,
+            data: HexBytes(data),
+        }
+    }
+
+    /// Creates an idle packet with the given total length, e.g. to fill a frame
+    pub fn idle(length: usize) -> Result<EncapsulationPacket, Error> {
+        if length == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Encapsulation Packet: idle packet length must not be 0".to_string(),
+            ));
+        }
+        if length == 1 {
+            let mut pkt = EncapsulationPacket::new(PID_IDLE, Vec::new());
+            pkt.length_of_length = LengthOfLength::NoLength;
+            return Ok(pkt);
+        }
+        let lol = [
+            LengthOfLength::OneOctet,
+            LengthOfLength::TwoOctets,
+            LengthOfLength::FourOctets,
+        ]
+        .into_iter()
+        .find(|lol| length >= lol.header_length() && length <= lol.max_packet_length())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Encapsulation Packet: no header for idle packet of length {length}"),
+            )
+        })?;
+        let mut pkt =
+            EncapsulationPacket::new(PID_IDLE, vec![0; length - lol.header_length()]);
+        pkt.length_of_length = lol;
+        Ok(pkt)
+    }
+
+    /// Use a larger header than needed
+    pub fn length_of_length(mut self, lol: LengthOfLength) -> Self {
+        self.length_of_length = lol;
+        self
+    }
+
+    /// Sets the user defined field, which requires at least a 4 byte header
+    pub fn user_defined(mut self, val: u8) -> Self {
+        self.user_defined = val & 0x0F;
+        self.length_of_length = self.length_of_length.max(LengthOfLength::TwoOctets);
+        self
+    }
+
+    /// Sets the protocol ID extension, which requires at least a 4 byte header
+    pub fn protocol_id_extension(mut self, val: u8) -> Self {
+        self.protocol_id_extension = val & 0x0F;
+        self.length_of_length = self.length_of_length.max(LengthOfLength::TwoOctets);
+        self
+    }
+
+    /// Sets the CCSDS defined field, which requires an 8 byte header
+    pub fn ccsds_defined(mut self, val: u16) -> Self {
+        self.ccsds_defined = val;
+        self.length_of_length = LengthOfLength::FourOctets;
+        self
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.protocol_id == PID_IDLE
+    }
+
+    pub fn header_length(&self) -> usize {
+        self.length_of_length.header_length()
+    }
+
+    pub fn total_length(&self) -> usize {
+        self.header_length() + self.data.len()
+    }
+
+    /// Parse a packet from the start of the slice. The slice may contain more data
+    /// after the packet, use [EncapsulationPacket::total_length] to get the consumed
+    /// length.
+    pub fn from_slice(arr: &[u8]) -> Result<EncapsulationPacket, Error> {
+        let total = packet_length(arr).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Encapsulation Packet: not enough data for header: {} bytes",
+                    arr.len()
+                ),
+            )
+        })?;
+
+        let version = arr[0] >> 5;
+        if version != ENCAP_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Encapsulation Packet: wrong version number: {version}"),
+            ));
+        }
+
+        let protocol_id = (arr[0] >> 2) & 0x07;
+        let lol = LengthOfLength::from_bits(arr[0]);
+        let hdr_len = lol.header_length();
+        if lol == LengthOfLength::NoLength && protocol_id != PID_IDLE {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Encapsulation Packet: 1 byte header with protocol ID {protocol_id}"),
+            ));
+        }
+        if total < hdr_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Encapsulation Packet: packet length {total} is shorter than header ({hdr_len} bytes)"
+                ),
+            ));
+        }
+        if arr.len() < total {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Encapsulation Packet: not enough data: {} bytes, packet length is {total}",
+                    arr.len()
+                ),
+            ));
+        }
+
+        let (user_defined, protocol_id_extension) = if hdr_len >= 4 {
+            (arr[1] >> 4, arr[1] & 0x0F)
+        } else {
+            (0, 0)
+        };
+        let ccsds_defined = if hdr_len == 8 {
+            u16::from_be_bytes([arr[2], arr[3]])
+        } else {
+            0
+        };
+
+        Ok(EncapsulationPacket {
+            protocol_id,
+            length_of_length: lol,
+            user_defined,
+            protocol_id_extension,
+            ccsds_defined,
+            data: HexBytes(arr[hdr_len..total].to_vec()),
+        })
+    }
+
+    /// Returns the encoded packet. Fails, if the packet does not fit the selected
+    /// header.
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        let lol = self.length_of_length;
+        let total = self.total_length();
+        if total > lol.max_packet_length() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Encapsulation Packet: length {total} does not fit into header {lol:?}"),
+            ));
+        }
+        if lol == LengthOfLength::NoLength && !self.is_idle() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Encapsulation Packet: 1 byte header only allowed for idle packets".to_string(),
+            ));
+        }
+        if lol < LengthOfLength::TwoOctets
+            && (self.user_defined != 0 || self.protocol_id_extension != 0)
+            || lol < LengthOfLength::FourOctets && self.ccsds_defined != 0
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Encapsulation Packet: header {lol:?} can not hold the header fields"),
+            ));
+        }
+
+        let mut res = Vec::with_capacity(total);
+        res.push((ENCAP_VERSION << 5) | ((self.protocol_id & 0x07) << 2) | lol.to_bits());
+        match lol {
+            LengthOfLength::NoLength => {}
+            LengthOfLength::OneOctet => res.push(total as u8),
+            LengthOfLength::TwoOctets => {
+                res.push(self.ext_octet());
+                res.extend_from_slice(&(total as u16).to_be_bytes());
+            }
+            LengthOfLength::FourOctets => {
+                res.push(self.ext_octet());
+                res.extend_from_slice(&self.ccsds_defined.to_be_bytes());
+                res.extend_from_slice(&(total as u32).to_be_bytes());
+            }
+        }
+        res.extend_from_slice(&self.data.0);
+        Ok(res)
+    }
+
+    fn ext_octet(&self) -> u8 {
+        ((self.user_defined & 0x0F) << 4) | (self.protocol_id_extension & 0x0F)
+    }
+}
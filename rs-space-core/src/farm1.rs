@@ -0,0 +1,204 @@
+//! The receiving side of COP-1, the Frame Acceptance and Reporting Mechanism FARM-1
+//! (CCSDS 232.1-B).
+//!
+//! [Farm1] decides for every TC frame of its virtual channel whether it is accepted
+//! and keeps the state reported back to the FOP in the CLCW. Accepted frames are put
+//! into a buffer, from which they are taken with [Farm1::take_frames]. Type-AD frames
+//! are only accepted while there is room in the buffer, otherwise the FARM signals
+//! wait until frames have been taken.
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+
+use crate::clcw::Clcw;
+use crate::fop1::{BC_SET_VR, BC_UNLOCK};
+use crate::tc_frame::TCFrame;
+
+/// The states of the FARM-1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FarmState {
+    /// S1: AD frames are accepted
+    Open,
+    /// S2: no buffer is available for AD frames
+    Wait,
+    /// S3: an AD frame outside of the sliding window was received
+    Lockout,
+}
+
+/// The result of processing a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FarmResult {
+    Accepted,
+    Discarded,
+}
+
+/// The FARM-1 of a single virtual channel
+#[derive(Debug, Clone)]
+pub struct Farm1 {
+    vcid: u8,
+    // the FARM sliding window width W, split in equal positive and negative windows
+    window_width: u8,
+    buffer_size: usize,
+    state: FarmState,
+    vr: u8,
+    retransmit: bool,
+    farm_b_counter: u8,
+    buffer: VecDeque<Vec<u8>>,
+    clcw: Clcw,
+}
+
+impl Farm1 {
+    /// Create a FARM for the virtual channel. The window width must be even and
+    /// between 2 and 254, `buffer_size` is the number of frames which can be buffered.
+    pub fn new(vcid: u8, window_width: u8, buffer_size: usize) -> Result<Farm1, Error> {
+        if !(2..=254).contains(&window_width) || !window_width.is_multiple_of(2) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("FARM-1: invalid sliding window width {window_width}"),
+            ));
+        }
+        if buffer_size == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "FARM-1: buffer size must not be 0".to_string(),
+            ));
+        }
+        Ok(Farm1 {
+            vcid: vcid & 0x3F,
+            window_width,
+            buffer_size,
+            state: FarmState::Open,
+            vr: 0,
+            retransmit: false,
+            farm_b_counter: 0,
+            buffer: VecDeque::new(),
+            clcw: Clcw::new(vcid),
+        })
+    }
+
+    pub fn state(&self) -> FarmState {
+        self.state
+    }
+
+    /// The sequence number V(R) of the next expected AD frame
+    pub fn vr(&self) -> u8 {
+        self.vr
+    }
+
+    pub fn farm_b_counter(&self) -> u8 {
+        self.farm_b_counter
+    }
+
+    /// Set the mission specific status field of the CLCW
+    pub fn set_status(&mut self, status: u8) {
+        self.clcw.status = status;
+    }
+
+    /// Set the flags of the physical layer reported in the CLCW
+    pub fn set_link_flags(&mut self, no_rf_available: bool, no_bit_lock: bool) {
+        self.clcw.no_rf_available = no_rf_available;
+        self.clcw.no_bit_lock = no_bit_lock;
+    }
+
+    /// The CLCW reflecting the current state
+    pub fn clcw(&self) -> Clcw {
+        Clcw {
+            lockout: self.state == FarmState::Lockout,
+            wait: self.state == FarmState::Wait,
+            retransmit: self.retransmit,
+            farm_b_counter: self.farm_b_counter & 0x03,
+            report_value: self.vr,
+            ..self.clcw
+        }
+    }
+
+    /// The number of accepted frames in the buffer
+    pub fn buffered_frames(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Take the accepted frames out of the buffer. This releases the buffer, so
+    /// the FARM leaves the wait state.
+    pub fn take_frames(&mut self) -> Vec<Vec<u8>> {
+        let frames = self.buffer.drain(..).collect();
+        if self.state == FarmState::Wait {
+            self.state = FarmState::Open;
+        }
+        frames
+    }
+
+    /// Process a received frame. Frames of other virtual channels or with a wrong
+    /// FECF are discarded without changing the state.
+    pub fn process_frame(&mut self, frame: &TCFrame) -> FarmResult {
+        if frame.vcid() != self.vcid || !frame.check_fecf() {
+            return FarmResult::Discarded;
+        }
+
+        match (frame.bypass_flag(), frame.control_command_flag()) {
+            (false, false) => self.process_ad(frame),
+            // type BD frames are discarded if there is no buffer space
+            (true, false) if self.buffer.len() >= self.buffer_size => FarmResult::Discarded,
+            (true, false) => {
+                self.farm_b_counter = self.farm_b_counter.wrapping_add(1);
+                self.buffer.push_back(frame.as_bytes().to_vec());
+                FarmResult::Accepted
+            }
+            (true, true) => self.process_bc(frame.data_field()),
+            // type AC frames are not defined
+            (false, true) => FarmResult::Discarded,
+        }
+    }
+
+    fn process_ad(&mut self, frame: &TCFrame) -> FarmResult {
+        let ns = frame.frame_sequence_number();
+        let pw = self.window_width / 2;
+        let nw = self.window_width / 2;
+
+        if ns == self.vr {
+            match self.state {
+                FarmState::Open if self.buffer.len() < self.buffer_size => {
+                    self.buffer.push_back(frame.as_bytes().to_vec());
+                    self.vr = self.vr.wrapping_add(1);
+                    self.retransmit = false;
+                    FarmResult::Accepted
+                }
+                FarmState::Open => {
+                    self.retransmit = true;
+                    self.state = FarmState::Wait;
+                    FarmResult::Discarded
+                }
+                FarmState::Wait | FarmState::Lockout => FarmResult::Discarded,
+            }
+        } else if ns.wrapping_sub(self.vr) < pw {
+            // a frame is missing, request its retransmission
+            if self.state == FarmState::Open {
+                self.retransmit = true;
+            }
+            FarmResult::Discarded
+        } else if self.vr.wrapping_sub(ns) <= nw {
+            // already accepted
+            FarmResult::Discarded
+        } else {
+            self.state = FarmState::Lockout;
+            FarmResult::Discarded
+        }
+    }
+
+    fn process_bc(&mut self, command: &[u8]) -> FarmResult {
+        if command == BC_UNLOCK {
+            self.farm_b_counter = self.farm_b_counter.wrapping_add(1);
+            self.retransmit = false;
+            self.state = FarmState::Open;
+            FarmResult::Accepted
+        } else if command.len() == 3 && command[..2] == BC_SET_VR {
+            self.farm_b_counter = self.farm_b_counter.wrapping_add(1);
+            if self.state != FarmState::Lockout {
+                self.retransmit = false;
+                self.vr = command[2];
+                self.state = FarmState::Open;
+            }
+            FarmResult::Accepted
+        } else {
+            FarmResult::Discarded
+        }
+    }
+}
@@ -0,0 +1,1023 @@
+//! The sending side of COP-1, the Frame Operation Procedure FOP-1 (CCSDS 232.1-B).
+//!
+//! [Fop1] manages one TC virtual channel. Type-AD frames are numbered with V(S),
+//! kept in the sent queue until the FARM acknowledges them through the CLCW and
+//! retransmitted when the FARM requests it or the timer T1 expires. Type-BD frames
+//! bypass the procedure, Type-BC frames carry the Unlock and Set V(R) control
+//! commands used to initialise the AD service.
+//!
+//! The FOP does not run on its own: it is driven by [Fop1::process_clcw] for every
+//! CLCW received and by [Fop1::check_timer], which must be called when
+//! [Fop1::t1_deadline] has passed. The frames are handed to a [TCFrameSink]. What
+//! happened to FDUs and directives is reported as [FopNotification]s.
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Write};
+use std::time::{Duration, Instant};
+
+use crate::clcw::{Clcw, COP_1};
+use crate::cltu;
+use crate::randomizer::Randomizer;
+use crate::tc_frame::{TCFrameBuilder, TC_MAX_FRAME_LEN};
+
+/// The control command of a Type-BC Unlock frame
+pub const BC_UNLOCK: [u8; 1] = [0x00];
+/// The first two bytes of a Type-BC Set V(R) frame, followed by the new V(R)
+pub const BC_SET_VR: [u8; 2] = [0x82, 0x00];
+
+/// Receives the frames generated by the FOP, e.g. to put them into CLTUs
+pub trait TCFrameSink {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error>;
+}
+
+impl<F> TCFrameSink for F
+where
+    F: FnMut(&[u8]) -> Result<(), Error>,
+{
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        self(frame)
+    }
+}
+
+/// A sink which writes every frame as a CLTU, e.g. to a TCP stream
+#[derive(Debug)]
+pub struct CltuWriter<W: Write> {
+    writer: W,
+    randomizer: Option<Randomizer>,
+}
+
+impl<W: Write> CltuWriter<W> {
+    pub fn new(writer: W) -> CltuWriter<W> {
+        CltuWriter {
+            writer,
+            randomizer: None,
+        }
+    }
+
+    /// Randomize the frames before they are encoded
+    pub fn randomizer(mut self, randomizer: Option<Randomizer>) -> Self {
+        self.randomizer = randomizer;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> TCFrameSink for CltuWriter<W> {
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let cltu = match &self.randomizer {
+            Some(rand) => {
+                let mut frame = frame.to_vec();
+                rand.apply(&mut frame);
+                cltu::encode(&frame)
+            }
+            None => cltu::encode(frame),
+        };
+        self.writer.write_all(&cltu)?;
+        self.writer.flush()
+    }
+}
+
+/// The states of the FOP-1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FopState {
+    /// S1: the AD service is active
+    Active,
+    /// S2: frames are retransmitted
+    RetransmitWithoutWait,
+    /// S3: retransmission was requested, but the FARM signals wait
+    RetransmitWithWait,
+    /// S4: waiting for a CLCW to initialise the AD service
+    InitialisingWithoutBC,
+    /// S5: waiting for a BC frame to be accepted to initialise the AD service
+    InitialisingWithBC,
+    /// S6: the AD service is not active
+    Initial,
+}
+
+/// What happens if T1 expires and the transmission limit is reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeoutType {
+    /// Raise an alert, which discards all pending frames
+    #[default]
+    Alert,
+    /// Suspend the AD service, it can be resumed with [Directive::ResumeAd]
+    Suspend,
+}
+
+/// The reasons for an alert, which terminates the AD service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertReason {
+    /// The transmission limit was reached on a retransmission request
+    Limit,
+    /// T1 expired and the transmission limit was reached
+    T1,
+    /// The FARM is in lockout
+    Lockout,
+    /// The CLCW requests a retransmission although all frames are acknowledged
+    Synch,
+    /// The N(R) of the CLCW is outside of the sent frames
+    NNR,
+    /// The CLCW flags are inconsistent
+    Clcw,
+    /// The frame sink failed
+    LLIF,
+    /// The AD service was terminated by a directive
+    Term,
+}
+
+/// The directives to control the FOP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+    InitiateAdWithoutClcw,
+    InitiateAdWithClcw,
+    InitiateAdWithUnlock,
+    InitiateAdWithSetVR(u8),
+    TerminateAd,
+    ResumeAd,
+    SetVS(u8),
+    SetWindowWidth(u8),
+    SetT1Initial(Duration),
+    SetTransmissionLimit(u32),
+    SetTimeoutType(TimeoutType),
+}
+
+/// The identifier returned for an accepted AD FDU
+pub type FduId = u64;
+
+/// Reports of the FOP to its user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FopNotification {
+    /// The FDU was acknowledged by the FARM
+    Acknowledged(FduId),
+    /// The FDU was discarded without being acknowledged
+    NotAcknowledged(FduId),
+    /// The directive has been completed
+    DirectiveConfirmed(Directive),
+    /// The directive could not be completed
+    DirectiveFailed(Directive),
+    Alert(AlertReason),
+    /// The AD service was suspended in the given state
+    Suspended(FopState),
+}
+
+/// The parameters of the FOP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fop1Config {
+    pub scid: u16,
+    pub vcid: u8,
+    /// If set, a FECF is appended to the frames
+    pub fecf: bool,
+    /// The FOP sliding window width K, the maximum number of unacknowledged frames
+    pub window_width: u8,
+    /// The initial value of the timer T1
+    pub t1_initial: Duration,
+    /// The maximum number of transmissions of a frame
+    pub transmission_limit: u32,
+    pub timeout_type: TimeoutType,
+}
+
+impl Fop1Config {
+    pub fn new(scid: u16, vcid: u8) -> Fop1Config {
+        Fop1Config {
+            scid,
+            vcid,
+            fecf: true,
+            window_width: 10,
+            t1_initial: Duration::from_secs(5),
+            transmission_limit: 3,
+            timeout_type: TimeoutType::Alert,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SentFrame {
+    id: FduId,
+    seq: u8,
+    frame: Vec<u8>,
+}
+
+/// The FOP-1 of a single virtual channel
+#[derive(Debug)]
+pub struct Fop1<S: TCFrameSink> {
+    config: Fop1Config,
+    sink: S,
+    state: FopState,
+    // the state the service was suspended in
+    suspend_state: Option<FopState>,
+    vs: u8,
+    nnr: u8,
+    transmission_count: u32,
+    t1: Option<Instant>,
+    wait_queue: VecDeque<(FduId, Vec<u8>)>,
+    sent_queue: VecDeque<SentFrame>,
+    bc_frame: Option<Vec<u8>>,
+    pending_directive: Option<Directive>,
+    next_id: FduId,
+    notifications: Vec<FopNotification>,
+}
+
+impl<S: TCFrameSink> Fop1<S> {
+    pub fn new(config: Fop1Config, sink: S) -> Result<Fop1<S>, Error> {
+        check_window_width(config.window_width)?;
+        check_transmission_limit(config.transmission_limit)?;
+        Ok(Fop1 {
+            config,
+            sink,
+            state: FopState::Initial,
+            suspend_state: None,
+            vs: 0,
+            nnr: 0,
+            transmission_count: 0,
+            t1: None,
+            wait_queue: VecDeque::new(),
+            sent_queue: VecDeque::new(),
+            bc_frame: None,
+            pending_directive: None,
+            next_id: 0,
+            notifications: Vec::new(),
+        })
+    }
+
+    pub fn config(&self) -> &Fop1Config {
+        &self.config
+    }
+
+    pub fn state(&self) -> FopState {
+        self.state
+    }
+
+    pub fn suspend_state(&self) -> Option<FopState> {
+        self.suspend_state
+    }
+
+    /// The sequence number V(S) of the next AD frame
+    pub fn vs(&self) -> u8 {
+        self.vs
+    }
+
+    /// The sequence number NN(R) of the oldest unacknowledged AD frame
+    pub fn nnr(&self) -> u8 {
+        self.nnr
+    }
+
+    pub fn transmission_count(&self) -> u32 {
+        self.transmission_count
+    }
+
+    /// The number of FDUs waiting for the sliding window to open
+    pub fn wait_queue_len(&self) -> usize {
+        self.wait_queue.len()
+    }
+
+    /// The number of sent and not yet acknowledged AD frames
+    pub fn sent_queue_len(&self) -> usize {
+        self.sent_queue.len()
+    }
+
+    /// The time when T1 expires, if it is running
+    pub fn t1_deadline(&self) -> Option<Instant> {
+        self.t1
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Returns the notifications since the last call
+    pub fn take_notifications(&mut self) -> Vec<FopNotification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// Request the transfer of an FDU with the AD service. The FDU is the content of
+    /// the frame data field, including the segment header if the virtual channel
+    /// uses one. The FDU is rejected if the AD service is not initiated.
+    pub fn transfer_ad(&mut self, fdu: Vec<u8>) -> Result<FduId, Error> {
+        if self.state == FopState::Initial {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "FOP-1: AD service is not initiated".to_string(),
+            ));
+        }
+        self.check_fdu_length(&fdu)?;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.wait_queue.push_back((id, fdu));
+        self.look_for_fdu();
+        Ok(id)
+    }
+
+    /// Transfer an FDU with the BD service. The frame is sent immediately in all
+    /// states.
+    pub fn transfer_bd(&mut self, fdu: &[u8]) -> Result<(), Error> {
+        let frame = self.builder().bypass(true).build(fdu)?;
+        self.sink.send_frame(&frame)
+    }
+
+    /// Execute a directive. Returns an error if the directive is rejected. Directives
+    /// which complete later are confirmed with a notification.
+    pub fn directive(&mut self, directive: Directive) -> Result<(), Error> {
+        match directive {
+            Directive::InitiateAdWithoutClcw => {
+                self.check_initial(directive)?;
+                self.initialise();
+                self.state = FopState::Active;
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+            }
+            Directive::InitiateAdWithClcw => {
+                self.check_initial(directive)?;
+                self.initialise();
+                self.transmission_count = 1;
+                self.start_timer();
+                self.pending_directive = Some(directive);
+                self.state = FopState::InitialisingWithoutBC;
+            }
+            Directive::InitiateAdWithUnlock => {
+                self.check_initial(directive)?;
+                self.initialise();
+                self.initiate_bc(directive, &BC_UNLOCK)?;
+            }
+            Directive::InitiateAdWithSetVR(vr) => {
+                self.check_initial(directive)?;
+                self.initialise();
+                self.vs = vr;
+                self.nnr = vr;
+                self.initiate_bc(directive, &[BC_SET_VR[0], BC_SET_VR[1], vr])?;
+            }
+            Directive::TerminateAd => {
+                self.alert(AlertReason::Term);
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+            }
+            Directive::ResumeAd => {
+                let Some(state) = self.suspend_state.filter(|_| self.state == FopState::Initial)
+                else {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "FOP-1: AD service is not suspended".to_string(),
+                    ));
+                };
+                self.suspend_state = None;
+                self.state = state;
+                self.start_timer();
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+            }
+            Directive::SetVS(vs) => {
+                self.check_initial(directive)?;
+                self.suspend_state = None;
+                self.vs = vs;
+                self.nnr = vs;
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+            }
+            Directive::SetWindowWidth(width) => {
+                check_window_width(width)?;
+                self.config.window_width = width;
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+                self.look_for_fdu();
+            }
+            Directive::SetT1Initial(t1) => {
+                self.config.t1_initial = t1;
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+            }
+            Directive::SetTransmissionLimit(limit) => {
+                check_transmission_limit(limit)?;
+                self.config.transmission_limit = limit;
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+            }
+            Directive::SetTimeoutType(timeout_type) => {
+                self.config.timeout_type = timeout_type;
+                self.notify(FopNotification::DirectiveConfirmed(directive));
+            }
+        }
+        Ok(())
+    }
+
+    /// Process a received CLCW. CLCWs for other virtual channels or COPs are ignored.
+    pub fn process_clcw(&mut self, clcw: &Clcw) {
+        if clcw.cop_in_effect != COP_1 || clcw.vcid != self.config.vcid {
+            return;
+        }
+
+        let nr = clcw.report_value;
+        match self.state {
+            FopState::Initial => {}
+            FopState::InitialisingWithBC => {
+                // the FARM may still be in lockout or have the old V(R) until the
+                // BC frame is accepted, so everything else is ignored
+                if !clcw.lockout && !clcw.retransmit && !clcw.wait && nr == self.vs {
+                    self.bc_frame = None;
+                    self.complete_initialisation();
+                }
+            }
+            FopState::InitialisingWithoutBC => {
+                if clcw.lockout {
+                    self.alert(AlertReason::Lockout);
+                } else if nr != self.vs {
+                    self.alert(AlertReason::NNR);
+                } else if !clcw.retransmit && !clcw.wait {
+                    self.complete_initialisation();
+                }
+            }
+            FopState::Active
+            | FopState::RetransmitWithoutWait
+            | FopState::RetransmitWithWait => self.process_clcw_active(clcw),
+        }
+    }
+
+    fn process_clcw_active(&mut self, clcw: &Clcw) {
+        let nr = clcw.report_value;
+        if clcw.lockout {
+            self.alert(AlertReason::Lockout);
+            return;
+        }
+        if !self.is_valid_nr(nr) {
+            self.alert(AlertReason::NNR);
+            return;
+        }
+
+        if nr == self.vs {
+            // all frames are acknowledged
+            if clcw.retransmit {
+                self.alert(AlertReason::Synch);
+            } else if clcw.wait {
+                self.alert(AlertReason::Clcw);
+            } else {
+                self.remove_acknowledged(nr);
+                self.state = FopState::Active;
+                self.look_for_fdu();
+            }
+            return;
+        }
+
+        let progress = nr != self.nnr;
+        if !clcw.retransmit {
+            if clcw.wait {
+                self.alert(AlertReason::Clcw);
+                return;
+            }
+            if progress || self.state == FopState::RetransmitWithWait {
+                self.remove_acknowledged(nr);
+                self.state = FopState::Active;
+            }
+        } else {
+            self.remove_acknowledged(nr);
+            if clcw.wait {
+                self.state = FopState::RetransmitWithWait;
+                return;
+            }
+            // in S2 without progress, the retransmission is already running
+            if progress || self.state != FopState::RetransmitWithoutWait {
+                if self.transmission_count >= self.config.transmission_limit {
+                    self.alert(AlertReason::Limit);
+                    return;
+                }
+                self.state = FopState::RetransmitWithoutWait;
+                if !self.retransmit() {
+                    return;
+                }
+            }
+        }
+        self.look_for_fdu();
+    }
+
+    /// Handle the expiry of T1. Does nothing if T1 is not running or has not yet
+    /// expired at `now`.
+    pub fn check_timer(&mut self, now: Instant) {
+        match self.t1 {
+            Some(deadline) if deadline <= now => {}
+            _ => return,
+        }
+        self.t1 = None;
+
+        let limit_reached = self.transmission_count >= self.config.transmission_limit;
+        match self.state {
+            FopState::Initial => {}
+            FopState::Active | FopState::RetransmitWithoutWait => {
+                if limit_reached {
+                    self.timeout();
+                } else {
+                    self.state = FopState::RetransmitWithoutWait;
+                    if self.retransmit() {
+                        self.look_for_fdu();
+                    }
+                }
+            }
+            FopState::RetransmitWithWait => {
+                // nothing is sent while the FARM signals wait, but the time waited
+                // counts against the transmission limit
+                if limit_reached {
+                    self.timeout();
+                } else {
+                    self.transmission_count += 1;
+                    self.start_timer();
+                }
+            }
+            FopState::InitialisingWithoutBC => self.timeout(),
+            FopState::InitialisingWithBC => {
+                if limit_reached {
+                    self.timeout();
+                } else if let Some(frame) = self.bc_frame.clone() {
+                    self.transmission_count += 1;
+                    self.start_timer();
+                    if self.sink.send_frame(&frame).is_err() {
+                        self.alert(AlertReason::LLIF);
+                    }
+                }
+            }
+        }
+    }
+
+    fn builder(&self) -> TCFrameBuilder {
+        TCFrameBuilder::new(self.config.scid, self.config.vcid).fecf(self.config.fecf)
+    }
+
+    fn check_fdu_length(&self, fdu: &[u8]) -> Result<(), Error> {
+        let len = self.builder().frame_length(fdu.len());
+        if len > TC_MAX_FRAME_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("FOP-1: frame length {len} exceeds maximum of {TC_MAX_FRAME_LEN}"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_initial(&self, directive: Directive) -> Result<(), Error> {
+        if self.state != FopState::Initial {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("FOP-1: directive {directive:?} rejected in state {:?}", self.state),
+            ));
+        }
+        Ok(())
+    }
+
+    fn notify(&mut self, notification: FopNotification) {
+        self.notifications.push(notification);
+    }
+
+    fn start_timer(&mut self) {
+        self.t1 = Some(Instant::now() + self.config.t1_initial);
+    }
+
+    /// True, if NN(R) <= N(R) <= V(S), modulo 256
+    fn is_valid_nr(&self, nr: u8) -> bool {
+        nr.wrapping_sub(self.nnr) <= self.vs.wrapping_sub(self.nnr)
+    }
+
+    /// Purge the queues and reset the state variables for a new AD service
+    fn initialise(&mut self) {
+        self.purge_queues();
+        self.t1 = None;
+        self.suspend_state = None;
+        self.transmission_count = 0;
+        self.nnr = self.vs;
+    }
+
+    fn purge_queues(&mut self) {
+        let ids: Vec<FduId> = self
+            .sent_queue
+            .drain(..)
+            .map(|f| f.id)
+            .chain(self.wait_queue.drain(..).map(|(id, _)| id))
+            .collect();
+        for id in ids {
+            self.notify(FopNotification::NotAcknowledged(id));
+        }
+        self.bc_frame = None;
+    }
+
+    fn initiate_bc(&mut self, directive: Directive, command: &[u8]) -> Result<(), Error> {
+        let frame = self.builder().bypass(true).control_command(true).build(command)?;
+        self.sink.send_frame(&frame)?;
+        self.bc_frame = Some(frame);
+        self.transmission_count = 1;
+        self.start_timer();
+        self.pending_directive = Some(directive);
+        self.state = FopState::InitialisingWithBC;
+        Ok(())
+    }
+
+    fn complete_initialisation(&mut self) {
+        self.t1 = None;
+        self.state = FopState::Active;
+        if let Some(directive) = self.pending_directive.take() {
+            self.notify(FopNotification::DirectiveConfirmed(directive));
+        }
+        self.look_for_fdu();
+    }
+
+    /// Terminate the AD service and discard all pending FDUs
+    fn alert(&mut self, reason: AlertReason) {
+        self.t1 = None;
+        self.purge_queues();
+        if let Some(directive) = self.pending_directive.take() {
+            self.notify(FopNotification::DirectiveFailed(directive));
+        }
+        self.state = FopState::Initial;
+        self.notify(FopNotification::Alert(reason));
+    }
+
+    fn suspend(&mut self) {
+        self.t1 = None;
+        self.suspend_state = Some(self.state);
+        self.notify(FopNotification::Suspended(self.state));
+        self.state = FopState::Initial;
+    }
+
+    fn timeout(&mut self) {
+        match self.config.timeout_type {
+            TimeoutType::Alert => self.alert(AlertReason::T1),
+            TimeoutType::Suspend => self.suspend(),
+        }
+    }
+
+    /// Remove the frames acknowledged by N(R) from the sent queue
+    fn remove_acknowledged(&mut self, nr: u8) {
+        if nr == self.nnr {
+            return;
+        }
+        while let Some(front) = self.sent_queue.front() {
+            if front.seq == nr {
+                break;
+            }
+            let id = front.id;
+            self.sent_queue.pop_front();
+            self.notify(FopNotification::Acknowledged(id));
+        }
+        self.nnr = nr;
+        self.transmission_count = 1;
+        if self.sent_queue.is_empty() {
+            self.t1 = None;
+        } else {
+            self.start_timer();
+        }
+    }
+
+    /// Send all frames of the sent queue again. Returns false, if the sink failed.
+    fn retransmit(&mut self) -> bool {
+        self.transmission_count += 1;
+        self.start_timer();
+        for idx in 0..self.sent_queue.len() {
+            if self.sink.send_frame(&self.sent_queue[idx].frame).is_err() {
+                self.alert(AlertReason::LLIF);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Send FDUs from the wait queue as long as the sliding window is open
+    fn look_for_fdu(&mut self) {
+        if !matches!(
+            self.state,
+            FopState::Active | FopState::RetransmitWithoutWait
+        ) {
+            return;
+        }
+
+        while self.sent_queue.len() < self.config.window_width as usize {
+            let Some((id, fdu)) = self.wait_queue.pop_front() else {
+                break;
+            };
+            let frame = match self.builder().sequence_number(self.vs).build(&fdu) {
+                Ok(frame) => frame,
+                Err(_) => {
+                    // the length was checked on acceptance
+                    self.notify(FopNotification::NotAcknowledged(id));
+                    continue;
+                }
+            };
+
+            if self.sent_queue.is_empty() {
+                self.transmission_count = 1;
+            }
+            self.sent_queue.push_back(SentFrame {
+                id,
+                seq: self.vs,
+                frame,
+            });
+            self.vs = self.vs.wrapping_add(1);
+            self.start_timer();
+
+            let frame = &self.sent_queue[self.sent_queue.len() - 1].frame;
+            if self.sink.send_frame(frame).is_err() {
+                self.alert(AlertReason::LLIF);
+                return;
+            }
+        }
+    }
+}
+
+fn check_window_width(width: u8) -> Result<(), Error> {
+    if width == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("FOP-1: invalid sliding window width {width}"),
+        ));
+    }
+    Ok(())
+}
+
+fn check_transmission_limit(limit: u32) -> Result<(), Error> {
+    if limit == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "FOP-1: transmission limit must not be 0".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::farm1::{Farm1, FarmState};
+    use crate::tc_frame::TCFrame;
+
+    /// The link between the FOP and the FARM, which can lose frames
+    struct Link {
+        farm: Rc<RefCell<Farm1>>,
+        // the AD frame with this sequence number is lost once
+        lose: Rc<Cell<Option<u8>>>,
+        // all frames are lost while set
+        down: Rc<Cell<bool>>,
+    }
+
+    impl Link {
+        fn clcw(&self) -> Clcw {
+            self.farm.borrow().clcw()
+        }
+
+        fn farm_state(&self) -> FarmState {
+            self.farm.borrow().state()
+        }
+    }
+
+    fn connect(config: Fop1Config, buffer_size: usize) -> (Fop1<impl TCFrameSink>, Link) {
+        let link = Link {
+            farm: Rc::new(RefCell::new(
+                Farm1::new(config.vcid, 10, buffer_size).unwrap(),
+            )),
+            lose: Rc::new(Cell::new(None)),
+            down: Rc::new(Cell::new(false)),
+        };
+        let (farm, lose, down) = (link.farm.clone(), link.lose.clone(), link.down.clone());
+        let sink = move |frame: &[u8]| -> Result<(), Error> {
+            let frame = TCFrame::new(frame, false, true)?;
+            if down.get() {
+                return Ok(());
+            }
+            if !frame.bypass_flag() && lose.get() == Some(frame.frame_sequence_number()) {
+                lose.set(None);
+                return Ok(());
+            }
+            farm.borrow_mut().process_frame(&frame);
+            Ok(())
+        };
+        (Fop1::new(config, sink).unwrap(), link)
+    }
+
+    /// Report the CLCW of the FARM to the FOP
+    fn report(fop: &mut Fop1<impl TCFrameSink>, link: &Link) {
+        fop.process_clcw(&link.clcw());
+    }
+
+    fn acknowledged(notifications: &[FopNotification]) -> Vec<FduId> {
+        notifications
+            .iter()
+            .filter_map(|n| match n {
+                FopNotification::Acknowledged(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lost_ad_frame_is_retransmitted() {
+        let (mut fop, link) = connect(Fop1Config::new(1, 2), 10);
+        fop.directive(Directive::InitiateAdWithoutClcw).unwrap();
+        link.lose.set(Some(1));
+        for i in 0..3u8 {
+            fop.transfer_ad(vec![i; 4]).unwrap();
+        }
+        assert_eq!(fop.sent_queue_len(), 3);
+
+        // frame 0 is accepted, frame 2 is out of sequence
+        let clcw = link.clcw();
+        assert_eq!(clcw.report_value, 1);
+        assert!(clcw.retransmit);
+        fop.process_clcw(&clcw);
+        assert_eq!(fop.state(), FopState::RetransmitWithoutWait);
+        assert_eq!(fop.transmission_count(), 2);
+
+        // frames 1 and 2 have been sent again
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::Active);
+        assert_eq!(fop.sent_queue_len(), 0);
+        assert_eq!(fop.t1_deadline(), None);
+        assert_eq!(acknowledged(&fop.take_notifications()), vec![0, 1, 2]);
+
+        let frames = link.farm.borrow_mut().take_frames();
+        let seqs: Vec<u8> = frames
+            .iter()
+            .map(|f| {
+                TCFrame::new(f, false, true)
+                    .unwrap()
+                    .frame_sequence_number()
+            })
+            .collect();
+        assert_eq!(seqs, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn full_buffer_waits() {
+        let (mut fop, link) = connect(Fop1Config::new(1, 2), 2);
+        fop.directive(Directive::InitiateAdWithoutClcw).unwrap();
+        for i in 0..3u8 {
+            fop.transfer_ad(vec![i; 4]).unwrap();
+        }
+        assert_eq!(link.farm_state(), FarmState::Wait);
+        let clcw = link.clcw();
+        assert!(clcw.wait && clcw.retransmit);
+        assert_eq!(clcw.report_value, 2);
+
+        fop.process_clcw(&clcw);
+        assert_eq!(fop.state(), FopState::RetransmitWithWait);
+        assert_eq!(fop.sent_queue_len(), 1);
+
+        // BD frames are discarded as well while the buffer is full
+        fop.transfer_bd(&[0xBD]).unwrap();
+        assert_eq!(link.farm.borrow().buffered_frames(), 2);
+        assert_eq!(link.farm.borrow().farm_b_counter(), 0);
+
+        // releasing the buffer opens the FARM, the FOP retransmits frame 2
+        assert_eq!(link.farm.borrow_mut().take_frames().len(), 2);
+        assert_eq!(link.farm_state(), FarmState::Open);
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::RetransmitWithoutWait);
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::Active);
+        assert_eq!(fop.sent_queue_len(), 0);
+        assert_eq!(acknowledged(&fop.take_notifications()), vec![0, 1, 2]);
+        assert_eq!(link.farm.borrow().buffered_frames(), 1);
+    }
+
+    #[test]
+    fn lockout_and_unlock() {
+        let (mut fop, link) = connect(Fop1Config::new(1, 2), 10);
+        fop.directive(Directive::SetVS(100)).unwrap();
+        fop.directive(Directive::InitiateAdWithoutClcw).unwrap();
+        let id = fop.transfer_ad(vec![1; 4]).unwrap();
+
+        // N(S) 100 is outside of both FARM windows around V(R) 0
+        assert_eq!(link.farm_state(), FarmState::Lockout);
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::Initial);
+        let notifications = fop.take_notifications();
+        assert!(notifications.contains(&FopNotification::NotAcknowledged(id)));
+        assert!(notifications.contains(&FopNotification::Alert(AlertReason::Lockout)));
+        assert!(fop.transfer_ad(vec![2; 4]).is_err());
+
+        fop.directive(Directive::SetVS(0)).unwrap();
+        fop.directive(Directive::InitiateAdWithUnlock).unwrap();
+        assert_eq!(fop.state(), FopState::InitialisingWithBC);
+        assert_eq!(link.farm_state(), FarmState::Open);
+        assert_eq!(link.farm.borrow().farm_b_counter(), 1);
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::Active);
+        assert!(fop
+            .take_notifications()
+            .contains(&FopNotification::DirectiveConfirmed(
+                Directive::InitiateAdWithUnlock
+            )));
+
+        let id = fop.transfer_ad(vec![3; 4]).unwrap();
+        report(&mut fop, &link);
+        assert_eq!(acknowledged(&fop.take_notifications()), vec![id]);
+        assert_eq!(link.farm.borrow().vr(), 1);
+    }
+
+    #[test]
+    fn sequence_numbers_wrap() {
+        let (mut fop, link) = connect(Fop1Config::new(1, 2), 20);
+        fop.directive(Directive::InitiateAdWithSetVR(250)).unwrap();
+        assert_eq!(link.farm.borrow().vr(), 250);
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::Active);
+
+        // the window of 10 frames covers the wrap from 255 to 0
+        for i in 0..15u8 {
+            fop.transfer_ad(vec![i; 4]).unwrap();
+        }
+        assert_eq!(fop.sent_queue_len(), 10);
+        assert_eq!(fop.wait_queue_len(), 5);
+        assert_eq!(fop.vs(), 4);
+        report(&mut fop, &link);
+        assert_eq!(fop.nnr(), 4);
+        assert_eq!(fop.vs(), 9);
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::Active);
+        assert_eq!(fop.sent_queue_len(), 0);
+        assert_eq!(link.farm.borrow().vr(), 9);
+        assert_eq!(
+            acknowledged(&fop.take_notifications()),
+            (0..15).collect::<Vec<_>>()
+        );
+
+        // an N(R) outside of NN(R)..=V(S) terminates the service
+        let clcw = Clcw {
+            report_value: 20,
+            ..link.clcw()
+        };
+        fop.process_clcw(&clcw);
+        assert_eq!(fop.state(), FopState::Initial);
+        assert!(fop
+            .take_notifications()
+            .contains(&FopNotification::Alert(AlertReason::NNR)));
+    }
+
+    fn config_with_timeout(timeout_type: TimeoutType) -> Fop1Config {
+        Fop1Config {
+            transmission_limit: 2,
+            timeout_type,
+            ..Fop1Config::new(1, 2)
+        }
+    }
+
+    /// Let T1 expire
+    fn expire(fop: &mut Fop1<impl TCFrameSink>) {
+        let deadline = fop.t1_deadline().expect("T1 running");
+        fop.check_timer(deadline);
+    }
+
+    #[test]
+    fn t1_expiry_raises_alert() {
+        let (mut fop, link) = connect(config_with_timeout(TimeoutType::Alert), 10);
+        fop.directive(Directive::InitiateAdWithoutClcw).unwrap();
+        link.down.set(true);
+        let id = fop.transfer_ad(vec![1; 4]).unwrap();
+        assert_eq!(fop.transmission_count(), 1);
+
+        // not yet expired
+        fop.check_timer(Instant::now());
+        assert_eq!(fop.state(), FopState::Active);
+
+        expire(&mut fop);
+        assert_eq!(fop.state(), FopState::RetransmitWithoutWait);
+        assert_eq!(fop.transmission_count(), 2);
+
+        expire(&mut fop);
+        assert_eq!(fop.state(), FopState::Initial);
+        assert_eq!(fop.sent_queue_len(), 0);
+        assert_eq!(fop.t1_deadline(), None);
+        let notifications = fop.take_notifications();
+        assert!(notifications.contains(&FopNotification::NotAcknowledged(id)));
+        assert!(notifications.contains(&FopNotification::Alert(AlertReason::T1)));
+        assert!(fop.directive(Directive::ResumeAd).is_err());
+    }
+
+    #[test]
+    fn t1_expiry_suspends() {
+        let (mut fop, link) = connect(config_with_timeout(TimeoutType::Suspend), 10);
+        fop.directive(Directive::InitiateAdWithoutClcw).unwrap();
+        fop.take_notifications();
+        link.down.set(true);
+        let id = fop.transfer_ad(vec![1; 4]).unwrap();
+
+        expire(&mut fop);
+        expire(&mut fop);
+        assert_eq!(fop.state(), FopState::Initial);
+        assert_eq!(fop.suspend_state(), Some(FopState::RetransmitWithoutWait));
+        // the frame is kept for the resumed service
+        assert_eq!(fop.sent_queue_len(), 1);
+        assert_eq!(
+            fop.take_notifications(),
+            vec![FopNotification::Suspended(FopState::RetransmitWithoutWait)]
+        );
+
+        link.down.set(false);
+        fop.directive(Directive::ResumeAd).unwrap();
+        assert_eq!(fop.state(), FopState::RetransmitWithoutWait);
+        assert!(fop.t1_deadline().is_some());
+
+        // with a higher limit, the next expiry retransmits the frame
+        fop.directive(Directive::SetTransmissionLimit(3)).unwrap();
+        expire(&mut fop);
+        assert_eq!(fop.transmission_count(), 3);
+        report(&mut fop, &link);
+        assert_eq!(fop.state(), FopState::Active);
+        assert!(fop
+            .take_notifications()
+            .contains(&FopNotification::Acknowledged(id)));
+    }
+}
@@ -0,0 +1,315 @@
+//! Frame synchronization of CADU streams.
+//!
+//! The [FrameSynchronizer] searches a byte stream bit by bit for the attached sync
+//! marker (ASM), so the CADUs do not need to be byte aligned. After a marker has been
+//! found, the synchronizer checks for the next markers at the expected positions
+//! (SEARCH -> CHECK -> LOCK). In LOCK, small bit slips are followed and missing
+//! markers are bridged (FLYWHEEL) until too many markers in a row are missing.
+use std::io::{Error, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+
+/// The standard CCSDS attached sync marker
+pub const DEFAULT_ASM: u32 = 0x1ACF_FC1D;
+
+const ASM_BITS: usize = 32;
+
+/// The parameters of the synchronizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameSyncConfig {
+    /// The sync marker to search for
+    pub asm: u32,
+    /// The number of bit errors allowed in the marker while searching
+    pub search_tolerance: u32,
+    /// The number of bit errors allowed in the marker while in lock
+    pub lock_tolerance: u32,
+    /// The number of markers found at the expected position needed for lock
+    pub verify_count: u32,
+    /// The number of consecutive missing markers bridged in lock
+    pub flywheel_count: u32,
+    /// The maximum bit slip followed in lock
+    pub bit_slip: u32,
+}
+
+impl Default for FrameSyncConfig {
+    fn default() -> Self {
+        FrameSyncConfig {
+            asm: DEFAULT_ASM,
+            search_tolerance: 0,
+            lock_tolerance: 2,
+            verify_count: 1,
+            flywheel_count: 3,
+            bit_slip: 1,
+        }
+    }
+}
+
+/// The state of the synchronizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncState {
+    /// Searching for a marker
+    Search,
+    /// A marker was found, verifying the following ones
+    Check,
+    /// In lock
+    Lock,
+    /// In lock, but the last marker(s) were missing
+    Flywheel,
+}
+
+impl SyncState {
+    /// True for the states in which frames are considered synchronized
+    pub fn is_locked(&self) -> bool {
+        matches!(self, SyncState::Lock | SyncState::Flywheel)
+    }
+}
+
+/// A frame extracted from the stream, without the sync marker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncedFrame {
+    pub data: Vec<u8>,
+    /// The state of the synchronizer after this frame
+    pub state: SyncState,
+    /// The number of bit errors in the marker in front of the frame
+    pub asm_errors: u32,
+    /// The bit slip against the expected position of the marker
+    pub bit_slip: i32,
+}
+
+/// The output of the synchronizer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncEvent {
+    Frame(SyncedFrame),
+    /// The synchronizer went into lock
+    LockAcquired,
+    /// The synchronizer lost the lock and searches again
+    LockLost,
+}
+
+/// Extracts frames of a fixed length from a stream of CADUs
+#[derive(Debug, Clone)]
+pub struct FrameSynchronizer {
+    config: FrameSyncConfig,
+    frame_length: usize,
+    buffer: Vec<u8>,
+    // the bit position in the buffer where the next marker is expected (in CHECK,
+    // LOCK and FLYWHEEL) or where the search continues (in SEARCH)
+    pos: usize,
+    state: SyncState,
+    verified: u32,
+    missed: u32,
+}
+
+impl FrameSynchronizer {
+    /// Creates a synchronizer for frames of the given length (without the marker)
+    pub fn new(frame_length: usize, config: FrameSyncConfig) -> Result<FrameSynchronizer, Error> {
+        if frame_length == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Frame Sync: frame length must not be 0".to_string(),
+            ));
+        }
+        Ok(FrameSynchronizer {
+            config,
+            frame_length,
+            buffer: Vec::new(),
+            pos: 0,
+            state: SyncState::Search,
+            verified: 0,
+            missed: 0,
+        })
+    }
+
+    pub fn state(&self) -> SyncState {
+        self.state
+    }
+
+    pub fn frame_length(&self) -> usize {
+        self.frame_length
+    }
+
+    /// Drop all buffered data and start searching again
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.pos = 0;
+        self.state = SyncState::Search;
+        self.verified = 0;
+        self.missed = 0;
+    }
+
+    fn cadu_bits(&self) -> usize {
+        ASM_BITS + 8 * self.frame_length
+    }
+
+    fn available_bits(&self) -> usize {
+        self.buffer.len() * 8
+    }
+
+    /// Add the data to the stream and return the frames and lock changes found
+    pub fn push(&mut self, data: &[u8]) -> Vec<SyncEvent> {
+        self.buffer.extend_from_slice(data);
+
+        let mut events = Vec::new();
+        while self.step(&mut events) {}
+        self.compact();
+        events
+    }
+
+    /// Process the next marker, returns false if more data is needed
+    fn step(&mut self, events: &mut Vec<SyncEvent>) -> bool {
+        match self.state {
+            SyncState::Search => {
+                let Some((pos, errors)) = self.search() else {
+                    return false;
+                };
+                self.pos = pos;
+                if self.pos + self.cadu_bits() > self.available_bits() {
+                    return false;
+                }
+
+                if self.config.verify_count == 0 {
+                    self.state = SyncState::Lock;
+                    events.push(SyncEvent::LockAcquired);
+                } else {
+                    self.state = SyncState::Check;
+                    self.verified = 0;
+                }
+                self.emit(errors, 0, events);
+                true
+            }
+            SyncState::Check | SyncState::Lock | SyncState::Flywheel => {
+                let slip = if self.state == SyncState::Check {
+                    0
+                } else {
+                    self.config.bit_slip as usize
+                };
+                if self.pos + slip + self.cadu_bits() > self.available_bits() {
+                    return false;
+                }
+
+                match self.find_marker(slip) {
+                    Some((bit_slip, errors)) => {
+                        self.pos = (self.pos as isize + bit_slip) as usize;
+                        match self.state {
+                            SyncState::Check => {
+                                self.verified += 1;
+                                if self.verified >= self.config.verify_count {
+                                    self.state = SyncState::Lock;
+                                    events.push(SyncEvent::LockAcquired);
+                                }
+                            }
+                            _ => {
+                                self.state = SyncState::Lock;
+                                self.missed = 0;
+                            }
+                        }
+                        self.emit(errors, bit_slip as i32, events);
+                    }
+                    None if self.state == SyncState::Check => {
+                        // the marker found was a false one, continue searching after it
+                        self.state = SyncState::Search;
+                        self.pos = self.pos.saturating_sub(self.cadu_bits()) + 1;
+                    }
+                    None => {
+                        self.missed += 1;
+                        if self.missed > self.config.flywheel_count {
+                            self.state = SyncState::Search;
+                            self.missed = 0;
+                            events.push(SyncEvent::LockLost);
+                        } else {
+                            self.state = SyncState::Flywheel;
+                            let errors = (self.bits32(self.pos) ^ self.config.asm).count_ones();
+                            self.emit(errors, 0, events);
+                        }
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// Search for a marker starting at the current position. If none is found, the
+    /// position is advanced to the end of the searched data.
+    fn search(&mut self) -> Option<(usize, u32)> {
+        let end = self.available_bits().saturating_sub(ASM_BITS - 1);
+        for pos in self.pos..end {
+            let errors = (self.bits32(pos) ^ self.config.asm).count_ones();
+            if errors <= self.config.search_tolerance {
+                return Some((pos, errors));
+            }
+        }
+        self.pos = self.pos.max(end);
+        None
+    }
+
+    /// Look for the marker at the expected position, allowing the given bit slip.
+    /// Returns the slip and the number of bit errors in the marker.
+    fn find_marker(&self, max_slip: usize) -> Option<(isize, u32)> {
+        // check the expected position first, then increasing slips to both sides
+        for i in 0..=2 * max_slip as isize {
+            let slip = if i % 2 == 1 { -(i + 1) / 2 } else { i / 2 };
+            let pos = self.pos as isize + slip;
+            if pos < 0 {
+                continue;
+            }
+            let errors = (self.bits32(pos as usize) ^ self.config.asm).count_ones();
+            if errors <= self.config.lock_tolerance {
+                return Some((slip, errors));
+            }
+        }
+        None
+    }
+
+    /// Emit the frame following the marker at the current position and advance to
+    /// the next expected marker
+    fn emit(&mut self, asm_errors: u32, bit_slip: i32, events: &mut Vec<SyncEvent>) {
+        let start = self.pos + ASM_BITS;
+        let data = (0..self.frame_length)
+            .map(|i| self.byte_at(start + 8 * i))
+            .collect();
+        events.push(SyncEvent::Frame(SyncedFrame {
+            data,
+            state: self.state,
+            asm_errors,
+            bit_slip,
+        }));
+        self.pos += self.cadu_bits();
+    }
+
+    /// Drop the data which is not needed any more. In CHECK, the last frame is kept,
+    /// as the search may have to continue after its marker.
+    fn compact(&mut self) {
+        let keep = if self.state == SyncState::Check {
+            self.cadu_bits()
+        } else {
+            self.config.bit_slip as usize
+        };
+        let bytes = self.pos.saturating_sub(keep) / 8;
+        if bytes > 0 {
+            self.buffer.drain(..bytes);
+            self.pos -= bytes * 8;
+        }
+    }
+
+    /// The 32 bits starting at the bit position, missing bits are 0
+    fn bits32(&self, pos: usize) -> u32 {
+        let idx = pos / 8;
+        let shift = pos % 8;
+        let mut val: u64 = 0;
+        for i in 0..5 {
+            val = (val << 8) | self.buffer.get(idx + i).copied().unwrap_or(0) as u64;
+        }
+        (val >> (8 - shift)) as u32
+    }
+
+    fn byte_at(&self, pos: usize) -> u8 {
+        let idx = pos / 8;
+        let shift = pos % 8;
+        if shift == 0 {
+            self.buffer[idx]
+        } else {
+            (self.buffer[idx] << shift) | (self.buffer[idx + 1] >> (8 - shift))
+        }
+    }
+}
@@ -7,4 +7,23 @@ pub mod pus_sec_hdr;
 pub mod crc;
 pub mod time;
 pub mod edsl;
-pub mod timed_buffer;
\ No newline at end of file
+pub mod timed_buffer;
+pub mod tm_frame;
+pub mod tc_frame;
+pub mod aos_frame;
+pub mod uslp_frame;
+pub mod vc_demux;
+pub mod vc_mux;
+pub mod cltu;
+pub mod randomizer;
+pub mod reed_solomon;
+pub mod frame_sync;
+pub mod clcw;
+pub mod fop1;
+pub mod farm1;
+pub mod sdls;
+pub mod packet_view;
+pub mod codec;
+pub mod segmentation;
+pub mod seq_monitor;
+pub mod encap_packet;
@@ -0,0 +1,242 @@
+//! Zero-copy views on space packets.
+//!
+//! [SpacePacketRef] is a view over a borrowed slice, [SpacePacketBytes] a view over
+//! [Bytes], which can be kept without borrowing the receive buffer. Both provide the
+//! header fields through [SpacePacketView]. [SpacePacketIter] and
+//! [SpacePacketBytesIter] split a buffer (e.g. the packet zone of a frame) into
+//! packets without allocating.
+use std::io::{Error, ErrorKind};
+
+use bytes::Bytes;
+
+use crate::ccsds_packet::{ErrorControl, FastCcsdsPacket};
+use crate::crc::{calc_crc2, check_iso_checksum2};
+use crate::pus_types::{CcsdsType, HexBytes, SegFlags};
+
+const HDR_LEN: usize = FastCcsdsPacket::HDR_LEN;
+
+/// The total length of the packet at the start of the data according to its header.
+/// Fails, if the data is too short for the header or the packet.
+fn packet_length(data: &[u8]) -> Result<usize, Error> {
+    if data.len() < HDR_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("CCSDS Packet: not enough data for header: {} bytes", data.len()),
+        ));
+    }
+    let total = (((data[4] as usize) << 8) | data[5] as usize) + 1 + HDR_LEN;
+    if data.len() < total {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "CCSDS Packet: not enough data: {} bytes, packet length is {total}",
+                data.len()
+            ),
+        ));
+    }
+    Ok(total)
+}
+
+/// Accessors to the fields of an encoded space packet
+pub trait SpacePacketView {
+    /// The complete packet
+    fn as_bytes(&self) -> &[u8];
+
+    fn header(&self) -> &[u8] {
+        &self.as_bytes()[..HDR_LEN]
+    }
+
+    fn version(&self) -> u8 {
+        self.as_bytes()[0] >> 5
+    }
+
+    fn packet_type(&self) -> CcsdsType {
+        if (self.as_bytes()[0] & 0x10) != 0 {
+            CcsdsType::TC
+        } else {
+            CcsdsType::TM
+        }
+    }
+
+    /// True, if the packet has a secondary header
+    fn sec_hdr_flag(&self) -> bool {
+        (self.as_bytes()[0] & 0x08) != 0
+    }
+
+    fn apid(&self) -> u16 {
+        let data = self.as_bytes();
+        ((data[0] as u16 & 0x07) << 8) | data[1] as u16
+    }
+
+    fn seq_flags(&self) -> SegFlags {
+        SegFlags::from_bits(self.as_bytes()[2] >> 6)
+    }
+
+    /// The 14 bit sequence count
+    fn seq_count(&self) -> u16 {
+        let data = self.as_bytes();
+        ((data[2] as u16 & 0x3F) << 8) | data[3] as u16
+    }
+
+    /// The length of the data field
+    fn data_length(&self) -> usize {
+        self.as_bytes().len() - HDR_LEN
+    }
+
+    /// The data field including secondary header and error control
+    fn data_field(&self) -> &[u8] {
+        &self.as_bytes()[HDR_LEN..]
+    }
+
+    /// The data field without the error control field
+    fn user_data(&self, ec: ErrorControl) -> &[u8] {
+        let data = self.data_field();
+        &data[..data.len().saturating_sub(ec.field_length())]
+    }
+
+    /// Checks the error control field. Packets without error control are always valid.
+    fn check_error_control(&self, ec: ErrorControl) -> bool {
+        let data = self.data_field();
+        if data.len() < ec.field_length() {
+            return false;
+        }
+        match ec {
+            ErrorControl::None => true,
+            ErrorControl::Crc16 => {
+                let len = data.len() - 2;
+                calc_crc2(self.header(), &data[..len])
+                    == ((data[len] as u16) << 8) | data[len + 1] as u16
+            }
+            ErrorControl::IsoChecksum => check_iso_checksum2(self.header(), data),
+        }
+    }
+
+    /// Copy the packet into a [FastCcsdsPacket]
+    fn to_fast_ccsds_pkt(&self) -> FastCcsdsPacket {
+        let mut hdr = [0u8; HDR_LEN];
+        hdr.copy_from_slice(self.header());
+        FastCcsdsPacket {
+            hdr,
+            data: HexBytes(self.data_field().to_vec()),
+        }
+    }
+}
+
+/// A view on a packet in a borrowed buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpacePacketRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SpacePacketRef<'a> {
+    /// Creates a view on the packet at the start of `data`. The packet length is
+    /// taken from the header, additional bytes after the packet are ignored.
+    pub fn new(data: &'a [u8]) -> Result<SpacePacketRef<'a>, Error> {
+        let len = packet_length(data)?;
+        Ok(SpacePacketRef { data: &data[..len] })
+    }
+
+    /// The complete packet, with the lifetime of the buffer
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The data field, with the lifetime of the buffer
+    pub fn data_slice(&self) -> &'a [u8] {
+        &self.data[HDR_LEN..]
+    }
+}
+
+impl SpacePacketView for SpacePacketRef<'_> {
+    fn as_bytes(&self) -> &[u8] {
+        self.data
+    }
+}
+
+/// Iterates over the packets in a buffer. Iteration stops at the first incomplete
+/// packet, which is available with [SpacePacketIter::remainder].
+#[derive(Debug, Clone)]
+pub struct SpacePacketIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SpacePacketIter<'a> {
+    pub fn new(data: &'a [u8]) -> SpacePacketIter<'a> {
+        SpacePacketIter { data }
+    }
+
+    /// The data which has not been returned as packet
+    pub fn remainder(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl<'a> Iterator for SpacePacketIter<'a> {
+    type Item = SpacePacketRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pkt = SpacePacketRef::new(self.data).ok()?;
+        self.data = &self.data[pkt.data.len()..];
+        Some(pkt)
+    }
+}
+
+/// A view on a packet in a reference counted buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpacePacketBytes {
+    data: Bytes,
+}
+
+impl SpacePacketBytes {
+    /// Creates a view on the packet at the start of `data`. Additional bytes after
+    /// the packet are not part of the view.
+    pub fn new(data: Bytes) -> Result<SpacePacketBytes, Error> {
+        let len = packet_length(&data)?;
+        Ok(SpacePacketBytes {
+            data: data.slice(..len),
+        })
+    }
+
+    /// The data field as [Bytes], sharing the buffer
+    pub fn data_bytes(&self) -> Bytes {
+        self.data.slice(HDR_LEN..)
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.data
+    }
+}
+
+impl SpacePacketView for SpacePacketBytes {
+    fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Splits a reference counted buffer into packets, like [SpacePacketIter]
+#[derive(Debug, Clone)]
+pub struct SpacePacketBytesIter {
+    data: Bytes,
+}
+
+impl SpacePacketBytesIter {
+    pub fn new(data: Bytes) -> SpacePacketBytesIter {
+        SpacePacketBytesIter { data }
+    }
+
+    /// The data which has not been returned as packet
+    pub fn remainder(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+impl Iterator for SpacePacketBytesIter {
+    type Item = SpacePacketBytes;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = packet_length(&self.data).ok()?;
+        Some(SpacePacketBytes {
+            data: self.data.split_to(len),
+        })
+    }
+}
@@ -1,10 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-use crate::ccsds_packet::CcsdsPacket;
+use crate::ccsds_packet::{CcsdsPacket, ErrorControlPolicy, FastCcsdsPacket};
 use crate::pus_sec_hdr::pus_sec_hdr::*;
 use crate::pus_types::{CcsdsType, HexBytes, PktID, APID, SSC};
 
-use std::io::Error;
+use std::io::{Error, ErrorKind};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PUSPacket {
@@ -51,6 +51,29 @@ impl PUSPacket {
         }
     }
 
+    /// Create a new PUSPacket from a received packet. The error control configured
+    /// for the APID of the packet is checked and removed.
+    pub fn from_fast_ccsds_pkt(
+        pkt: FastCcsdsPacket,
+        policy: &ErrorControlPolicy,
+        pus_sec_hdr: PUSSecondaryHeader,
+    ) -> Result<PUSPacket, Error> {
+        let ec = policy.for_apid(pkt.apid());
+        if !pkt.check_error_control(ec) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("PUS Packet: APID {}: {:?} check failed", pkt.apid(), ec),
+            ));
+        }
+        Self::from_ccsds_packet(CcsdsPacket::from_fast_ccsds_pkt_with(pkt, ec), pus_sec_hdr)
+    }
+
+    /// Encode the packet with the error control configured for its APID
+    pub fn to_fast_ccsds_pkt(&self, policy: &ErrorControlPolicy) -> Result<FastCcsdsPacket, Error> {
+        let ec = policy.for_apid(self.pkt_id.apid.raw());
+        self.to_ccsds_packet()?.to_fast_ccsds_pkt_with(ec)
+    }
+
     pub fn to_ccsds_packet(&self) -> Result<CcsdsPacket, std::io::Error> {
         let sec_hdr_len = self.sec_hdr.len();
         let mut content: Vec<u8> = Vec::new();
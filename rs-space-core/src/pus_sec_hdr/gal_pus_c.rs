@@ -214,3 +214,122 @@ impl PUSSecHeader for GalSecHdrTM {
         self.pus_dest_id = src_id;
     }
 }
+
+fn check_view(arr: &[u8], len: usize) -> Result<(), Error> {
+    if arr.len() < len {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "GAL PUS-C Secondary Header: not enough data: {} bytes, need {len}",
+                arr.len()
+            ),
+        ));
+    }
+    let vers = arr[0] & 0xF0;
+    if vers != 0b0010_0000 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "GAL PUS-C Secondary Header: wrong version number: {}",
+                (vers >> 4)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// A zero-copy view on an encoded [GalSecHdrTC]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GalSecHdrTCRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> GalSecHdrTCRef<'a> {
+    /// The length of the header
+    pub const LEN: usize = GalSecHdrTC::HDR_SIZE;
+
+    /// Creates a view on the header at the start of `arr`, e.g. a packet data field
+    pub fn new(arr: &'a [u8]) -> Result<GalSecHdrTCRef<'a>, Error> {
+        check_view(arr, GalSecHdrTC::HDR_SIZE)?;
+        Ok(GalSecHdrTCRef {
+            data: &arr[..GalSecHdrTC::HDR_SIZE],
+        })
+    }
+
+    pub fn pus_type(&self) -> PUSType {
+        PUSType(self.data[1])
+    }
+
+    pub fn pus_sub_type(&self) -> PUSSubType {
+        PUSSubType(self.data[2])
+    }
+
+    pub fn pus_src_id(&self) -> PUSSrcID {
+        PUSSrcID(((self.data[3] as u16) << 8) | (self.data[4] as u16))
+    }
+
+    pub fn ack_accept(&self) -> bool {
+        (self.data[0] & 0b0000_1000) != 0
+    }
+
+    pub fn ack_start(&self) -> bool {
+        (self.data[0] & 0b0000_0100) != 0
+    }
+
+    pub fn ack_progress(&self) -> bool {
+        (self.data[0] & 0b0000_0010) != 0
+    }
+
+    pub fn ack_complete(&self) -> bool {
+        (self.data[0] & 0b0000_0001) != 0
+    }
+}
+
+/// A zero-copy view on an encoded [GalSecHdrTM]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GalSecHdrTMRef<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> GalSecHdrTMRef<'a> {
+    /// The length of the header
+    pub const LEN: usize = GalSecHdrTM::HDR_SIZE;
+
+    /// Creates a view on the header at the start of `arr`, e.g. a packet data field
+    pub fn new(arr: &'a [u8]) -> Result<GalSecHdrTMRef<'a>, Error> {
+        check_view(arr, GalSecHdrTM::HDR_SIZE)?;
+        Ok(GalSecHdrTMRef {
+            data: &arr[..GalSecHdrTM::HDR_SIZE],
+        })
+    }
+
+    pub fn time_reference(&self) -> u8 {
+        self.data[0] & 0x0F
+    }
+
+    pub fn pus_type(&self) -> PUSType {
+        PUSType(self.data[1])
+    }
+
+    pub fn pus_sub_type(&self) -> PUSSubType {
+        PUSSubType(self.data[2])
+    }
+
+    pub fn msg_type_cntr(&self) -> u16 {
+        ((self.data[3] as u16) << 8) | (self.data[4] as u16)
+    }
+
+    pub fn pus_dest_id(&self) -> PUSSrcID {
+        PUSSrcID(((self.data[5] as u16) << 8) | (self.data[6] as u16))
+    }
+
+    /// The raw CUC time field
+    pub fn time_field(&self) -> &'a [u8] {
+        &self.data[7..7 + time_length(TimeEncoding::CUC42)]
+    }
+
+    /// Decode the time field
+    pub fn time(&self) -> Result<Time, Error> {
+        Time::decode_from_enc(TimeEncoding::CUC42, self.time_field())
+    }
+}
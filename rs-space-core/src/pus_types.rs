@@ -23,7 +23,7 @@ impl APID {
     }
 
     pub fn to_bytes(&self, arr: &mut [u8]) {
-        arr[0] = arr[0] | ((self.0 & 0b0000_0111_0000_000) >> 8) as u8;
+        arr[0] = arr[0] | ((self.0 & 0b0000_0111_0000_0000) >> 8) as u8;
         arr[1] = (self.0 & 0xff) as u8;
     }
 }
@@ -62,6 +62,29 @@ impl std::fmt::Display for SegFlags {
     }
 }
 
+impl SegFlags {
+    /// Decode the flags from the lower 2 bits of the given value. This encoding is
+    /// shared by the packet sequence flags and the TC segment header.
+    pub fn from_bits(val: u8) -> SegFlags {
+        match val & 0b11 {
+            0b00 => SegFlags::Continuation,
+            0b01 => SegFlags::First,
+            0b10 => SegFlags::Last,
+            _ => SegFlags::Unsegmented,
+        }
+    }
+
+    /// Encode the flags into the lower 2 bits
+    pub fn to_bits(&self) -> u8 {
+        match self {
+            SegFlags::Continuation => 0b00,
+            SegFlags::First => 0b01,
+            SegFlags::Last => 0b10,
+            SegFlags::Unsegmented => 0b11,
+        }
+    }
+}
+
 impl SSC {
     pub fn new(flags: SegFlags, val: u16) -> SSC {
         SSC {
@@ -159,7 +182,7 @@ impl PktID {
             version: vers,
             ccsds_type: t,
             dfh: d,
-            apid: APID(val),
+            apid: APID::new(val),
         }
     }
 
@@ -197,8 +220,9 @@ impl PktID {
         };
         let d: u8 = if self.dfh { 0b0000_1000 } else { 0 };
 
-        self.apid.to_bytes(arr);
+        // the APID ORs its upper bits into the first byte, so it has to come last
         arr[0] = (self.version << 5) | t | d;
+        self.apid.to_bytes(arr);
     }
 }
 
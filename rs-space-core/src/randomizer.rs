@@ -0,0 +1,98 @@
+//! The CCSDS pseudo-randomizers (CCSDS 131.0-B and 231.0-B).
+//!
+//! Randomization is an XOR of the frame with a pseudo-random sequence, which starts
+//! anew with every frame. Applying it twice restores the original data, so the same
+//! function is used for randomization and derandomization. All transformations work
+//! in place and do not allocate.
+use serde::{Deserialize, Serialize};
+
+/// The available pseudo-random sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Randomizer {
+    /// The TM randomizer with h(x) = x^8 + x^7 + x^5 + x^3 + 1 and period 255
+    Tm255,
+    /// The TM randomizer with h(x) = x^17 + x^14 + 1 and period 2^17 - 1
+    Tm131071,
+    /// The TC randomizer with h(x) = x^8 + x^6 + x^4 + x^3 + x^2 + x + 1 and period 255
+    Tc,
+}
+
+// feedback taps of the generators, expressed on the shift register which outputs
+// its most significant bit
+const TM255_TAPS: u32 = 0b1001_0101;
+const TC_TAPS: u32 = 0b1111_1010;
+const TM131071_TAPS: u32 = (1 << 16) | (1 << 2);
+
+/// The first 255 bytes of the TM sequence, after which it repeats
+pub const TM255_SEQUENCE: [u8; 255] = build_sequence(TM255_TAPS);
+/// The first 255 bytes of the TC sequence, after which it repeats
+pub const TC_SEQUENCE: [u8; 255] = build_sequence(TC_TAPS);
+
+/// A linear feedback shift register, initialised with all ones
+#[derive(Debug, Clone, Copy)]
+struct Lfsr {
+    state: u32,
+    len: u32,
+    taps: u32,
+}
+
+impl Lfsr {
+    const fn new(len: u32, taps: u32) -> Lfsr {
+        Lfsr {
+            state: (1 << len) - 1,
+            len,
+            taps,
+        }
+    }
+
+    const fn next_byte(&mut self) -> u8 {
+        let mask = (1 << self.len) - 1;
+        let mut byte = 0u8;
+        let mut i = 0;
+        while i < 8 {
+            byte = (byte << 1) | ((self.state >> (self.len - 1)) & 1) as u8;
+            let feedback = (self.state & self.taps).count_ones() & 1;
+            self.state = ((self.state << 1) | feedback) & mask;
+            i += 1;
+        }
+        byte
+    }
+}
+
+const fn build_sequence(taps: u32) -> [u8; 255] {
+    let mut lfsr = Lfsr::new(8, taps);
+    let mut seq = [0u8; 255];
+    let mut i = 0;
+    while i < seq.len() {
+        seq[i] = lfsr.next_byte();
+        i += 1;
+    }
+    seq
+}
+
+impl Randomizer {
+    /// Randomize or derandomize the data in place. The sequence starts with the
+    /// first byte of `data`, so it has to be called with a single complete frame
+    /// (without the attached sync marker for TM, without the CLTU start sequence
+    /// for TC).
+    pub fn apply(&self, data: &mut [u8]) {
+        match self {
+            Randomizer::Tm255 => xor_cyclic(data, &TM255_SEQUENCE),
+            Randomizer::Tc => xor_cyclic(data, &TC_SEQUENCE),
+            Randomizer::Tm131071 => {
+                let mut lfsr = Lfsr::new(17, TM131071_TAPS);
+                for b in data.iter_mut() {
+                    *b ^= lfsr.next_byte();
+                }
+            }
+        }
+    }
+}
+
+fn xor_cyclic(data: &mut [u8], seq: &[u8]) {
+    for chunk in data.chunks_mut(seq.len()) {
+        for (b, s) in chunk.iter_mut().zip(seq) {
+            *b ^= s;
+        }
+    }
+}
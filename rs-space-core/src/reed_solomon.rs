@@ -0,0 +1,535 @@
+//! The CCSDS Reed-Solomon codes (255,223) and (255,239) (CCSDS 131.0-B), in the
+//! dual basis representation, with interleaving depths 1 to 8.
+//!
+//! A codeblock consists of the transfer frame followed by the check symbols of all
+//! interleaved codewords. Symbol `i` of the frame belongs to codeword `i % I`.
+//! Frames shorter than `I * k` are encoded as shortened codewords (virtual fill).
+use std::io::{Error, ErrorKind};
+
+use serde::{Deserialize, Serialize};
+
+/// The supported Reed-Solomon codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RSCode {
+    /// Corrects up to 16 symbol errors per codeword
+    RS255_223,
+    /// Corrects up to 8 symbol errors per codeword
+    RS255_239,
+}
+
+impl RSCode {
+    /// The number of symbol errors which can be corrected per codeword
+    pub fn correctable_errors(&self) -> usize {
+        match self {
+            RSCode::RS255_223 => 16,
+            RSCode::RS255_239 => 8,
+        }
+    }
+
+    /// The number of check symbols per codeword
+    pub fn parity_length(&self) -> usize {
+        2 * self.correctable_errors()
+    }
+
+    /// The number of information symbols per (not shortened) codeword
+    pub fn data_length(&self) -> usize {
+        N - self.parity_length()
+    }
+
+    /// The first root of the generator polynomial, as a power of alpha^11
+    fn first_root(&self) -> i64 {
+        128 - self.correctable_errors() as i64
+    }
+
+    fn generator(&self) -> &'static [u8] {
+        match self {
+            RSCode::RS255_223 => &GEN_E16[..=32],
+            RSCode::RS255_239 => &GEN_E8[..=16],
+        }
+    }
+}
+
+/// The result of decoding a codeblock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RSStatus {
+    /// The number of symbols corrected in all codewords of the codeblock
+    Corrected(usize),
+    /// At least one codeword contained more errors than could be corrected. The
+    /// frame is returned as received.
+    Uncorrectable,
+}
+
+/// A Reed-Solomon codec for a code and interleaving depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReedSolomon {
+    pub code: RSCode,
+    pub interleave: u8,
+}
+
+// the length of a codeword
+const N: usize = 255;
+const MAX_PARITY: usize = 32;
+const MAX_INTERLEAVE: u8 = 8;
+
+// the field generator polynomial x^8 + x^7 + x^2 + x + 1
+const FIELD_POLY: u16 = 0x187;
+// the roots of the code generator polynomial are powers of alpha^11
+const PRIM: i64 = 11;
+
+const GF: GaloisField = GaloisField::new();
+const GEN_E16: [u8; MAX_PARITY + 1] = generator_poly(16);
+const GEN_E8: [u8; MAX_PARITY + 1] = generator_poly(8);
+
+// the rows of the transformation matrix from the conventional to the dual basis
+const TAL: [u8; 8] = [0x8d, 0xef, 0xec, 0x86, 0xfa, 0x99, 0xaf, 0x7b];
+const TO_DUAL: [u8; 256] = dual_basis_table();
+const FROM_DUAL: [u8; 256] = invert_table(&TO_DUAL);
+
+struct GaloisField {
+    exp: [u8; 256],
+    log: [u8; 256],
+}
+
+impl GaloisField {
+    const fn new() -> GaloisField {
+        let mut exp = [0u8; 256];
+        let mut log = [0u8; 256];
+        let mut x: u16 = 1;
+        let mut i = 0;
+        while i < 255 {
+            exp[i] = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= FIELD_POLY;
+            }
+            i += 1;
+        }
+        exp[255] = exp[0];
+        GaloisField { exp, log }
+    }
+
+    /// alpha to the power of e
+    const fn pow(&self, e: i64) -> u8 {
+        self.exp[e.rem_euclid(255) as usize]
+    }
+
+    const fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            self.exp[(self.log[a as usize] as usize + self.log[b as usize] as usize) % 255]
+        }
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        if a == 0 {
+            0
+        } else {
+            self.pow(self.log[a as usize] as i64 - self.log[b as usize] as i64)
+        }
+    }
+
+    /// Evaluate the polynomial with coefficients in ascending order at x
+    fn eval(&self, poly: &[u8], x: u8) -> u8 {
+        poly.iter().rev().fold(0, |acc, c| self.mul(acc, x) ^ c)
+    }
+}
+
+/// The generator polynomial with coefficients in ascending order
+const fn generator_poly(e: usize) -> [u8; MAX_PARITY + 1] {
+    let mut gen = [0u8; MAX_PARITY + 1];
+    gen[0] = 1;
+    let first = 128 - e as i64;
+    let mut j = 0;
+    while j < 2 * e {
+        // multiply with (x + root)
+        let root = GF.pow(PRIM * (first + j as i64));
+        let mut i = j + 1;
+        while i > 0 {
+            gen[i] = gen[i - 1] ^ GF.mul(gen[i], root);
+            i -= 1;
+        }
+        gen[0] = GF.mul(gen[0], root);
+        j += 1;
+    }
+    gen
+}
+
+const fn dual_basis_table() -> [u8; 256] {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut k = 0;
+        while k < 8 {
+            if i & (1 << k) != 0 {
+                table[i] ^= TAL[7 - k];
+            }
+            k += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+const fn invert_table(table: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[table[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+impl ReedSolomon {
+    pub fn new(code: RSCode, interleave: u8) -> Result<ReedSolomon, Error> {
+        let rs = ReedSolomon { code, interleave };
+        rs.check_interleave()?;
+        Ok(rs)
+    }
+
+    fn check_interleave(&self) -> Result<(), Error> {
+        if self.interleave == 0 || self.interleave > MAX_INTERLEAVE {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Reed-Solomon: invalid interleaving depth {}", self.interleave),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The length of the check symbols of a codeblock
+    pub fn parity_length(&self) -> usize {
+        self.interleave as usize * self.code.parity_length()
+    }
+
+    /// The maximum frame length which can be encoded
+    pub fn max_frame_length(&self) -> usize {
+        self.interleave as usize * self.code.data_length()
+    }
+
+    /// The length of the codeblock for the given frame length
+    pub fn codeblock_length(&self, frame_len: usize) -> usize {
+        frame_len + self.parity_length()
+    }
+
+    /// Check, if frames of the given length can be encoded with the interleaving depth
+    pub fn check_frame_length(&self, frame_len: usize) -> Result<(), Error> {
+        self.check_interleave()?;
+        if frame_len == 0
+            || frame_len > self.max_frame_length()
+            || !frame_len.is_multiple_of(self.interleave as usize)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Reed-Solomon: invalid frame length {frame_len} for interleaving depth {}",
+                    self.interleave
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encode the frame and return the codeblock
+    pub fn encode(&self, frame: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut codeblock = vec![0; self.codeblock_length(frame.len())];
+        codeblock[..frame.len()].copy_from_slice(frame);
+        self.encode_in_place(&mut codeblock)?;
+        Ok(codeblock)
+    }
+
+    /// Calculate the check symbols for the frame at the start of the codeblock and
+    /// write them to its end
+    pub fn encode_in_place(&self, codeblock: &mut [u8]) -> Result<(), Error> {
+        let frame_len = codeblock.len().saturating_sub(self.parity_length());
+        self.check_frame_length(frame_len)?;
+
+        let depth = self.interleave as usize;
+        let nroots = self.code.parity_length();
+        let gen = self.code.generator();
+
+        for cw in 0..depth {
+            let mut parity = [0u8; MAX_PARITY];
+            for i in (cw..frame_len).step_by(depth) {
+                let feedback = FROM_DUAL[codeblock[i] as usize] ^ parity[0];
+                for j in 0..nroots - 1 {
+                    parity[j] = parity[j + 1] ^ GF.mul(feedback, gen[nroots - 1 - j]);
+                }
+                parity[nroots - 1] = GF.mul(feedback, gen[0]);
+            }
+            for (j, p) in parity[..nroots].iter().enumerate() {
+                codeblock[frame_len + j * depth + cw] = TO_DUAL[*p as usize];
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the codeblock in place. If all codewords could be decoded, the errors
+    /// are corrected, otherwise the codeblock is left unchanged.
+    pub fn decode(&self, codeblock: &mut [u8]) -> Result<RSStatus, Error> {
+        let frame_len = codeblock.len().saturating_sub(self.parity_length());
+        self.check_frame_length(frame_len)?;
+
+        let depth = self.interleave as usize;
+        let len = codeblock.len() / depth;
+
+        // corrections are only applied, if all codewords are decodable
+        let mut corrections = Vec::new();
+        for cw in 0..depth {
+            let mut word = [0u8; N];
+            for (k, i) in (cw..codeblock.len()).step_by(depth).enumerate() {
+                word[k] = FROM_DUAL[codeblock[i] as usize];
+            }
+            match self.decode_codeword(&word[..len]) {
+                Some(errors) => {
+                    for (pos, value) in errors {
+                        corrections.push((pos * depth + cw, value));
+                    }
+                }
+                None => return Ok(RSStatus::Uncorrectable),
+            }
+        }
+
+        for (i, value) in &corrections {
+            let conventional = FROM_DUAL[codeblock[*i] as usize] ^ value;
+            codeblock[*i] = TO_DUAL[conventional as usize];
+        }
+        Ok(RSStatus::Corrected(corrections.len()))
+    }
+
+    /// Decode a (possibly shortened) codeword in the conventional representation.
+    /// Returns the positions and values of the errors or None, if the codeword is
+    /// not decodable.
+    fn decode_codeword(&self, word: &[u8]) -> Option<Vec<(usize, u8)>> {
+        let nroots = self.code.parity_length();
+        let first = self.code.first_root();
+        let len = word.len();
+
+        // the symbol at index t is the coefficient of x^(len - 1 - t)
+        let mut syndromes = [0u8; MAX_PARITY];
+        for (j, s) in syndromes[..nroots].iter_mut().enumerate() {
+            let x = GF.pow(PRIM * (first + j as i64));
+            *s = word.iter().fold(0, |acc, c| GF.mul(acc, x) ^ c);
+        }
+        if syndromes.iter().all(|s| *s == 0) {
+            return Some(Vec::new());
+        }
+
+        // Berlekamp-Massey algorithm for the error locator polynomial
+        let mut lambda = [0u8; MAX_PARITY + 1];
+        let mut prev = [0u8; MAX_PARITY + 1];
+        lambda[0] = 1;
+        prev[0] = 1;
+        let mut num_errors = 0;
+        let mut shift = 1;
+        let mut prev_discrepancy = 1u8;
+
+        for n in 0..nroots {
+            let mut discrepancy = syndromes[n];
+            for i in 1..=num_errors {
+                discrepancy ^= GF.mul(lambda[i], syndromes[n - i]);
+            }
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+
+            let factor = GF.div(discrepancy, prev_discrepancy);
+            let old = lambda;
+            for i in shift..=nroots {
+                lambda[i] ^= GF.mul(factor, prev[i - shift]);
+            }
+            if 2 * num_errors <= n {
+                num_errors = n + 1 - num_errors;
+                prev = old;
+                prev_discrepancy = discrepancy;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+        }
+
+        let degree = lambda.iter().rposition(|c| *c != 0).unwrap_or(0);
+        if degree != num_errors || num_errors > self.code.correctable_errors() {
+            return None;
+        }
+
+        // error evaluator polynomial omega = syndromes * lambda mod x^nroots
+        let mut omega = [0u8; MAX_PARITY];
+        for (i, o) in omega[..nroots].iter_mut().enumerate() {
+            for j in 0..=i.min(degree) {
+                *o ^= GF.mul(lambda[j], syndromes[i - j]);
+            }
+        }
+        // formal derivative of lambda
+        let mut lambda_deriv = [0u8; MAX_PARITY];
+        for i in (1..=degree).step_by(2) {
+            lambda_deriv[i - 1] = lambda[i];
+        }
+
+        // Chien search for the error positions and Forney algorithm for the values
+        let mut errors = Vec::with_capacity(num_errors);
+        for power in 0..len {
+            let x_inv = GF.pow(-PRIM * power as i64);
+            if GF.eval(&lambda[..=degree], x_inv) != 0 {
+                continue;
+            }
+            let num = GF.mul(
+                GF.pow(PRIM * power as i64 * (1 - first)),
+                GF.eval(&omega[..nroots], x_inv),
+            );
+            let den = GF.eval(&lambda_deriv[..degree], x_inv);
+            if den == 0 {
+                return None;
+            }
+            errors.push((len - 1 - power, GF.div(num, den)));
+        }
+
+        // roots in the virtual fill mean the codeword is not decodable
+        if errors.len() != num_errors {
+            return None;
+        }
+        Some(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the check symbols of the frame 0x00, 0x01, .., 0xDE (dual basis) with
+    // RS(255,223), as calculated by encode_rs_ccsds of libfec
+    const PARITY_0_TO_222: [u8; 32] = [
+        0x4F, 0xFB, 0x92, 0xDD, 0x55, 0x7E, 0xC6, 0x7F, 0x27, 0xFB, 0x89, 0x82, 0xCF, 0x58, 0xF8,
+        0xFD, 0x02, 0x8A, 0xD1, 0x17, 0xFC, 0xEF, 0x6B, 0x27, 0x93, 0xD0, 0x41, 0x88, 0x26, 0x57,
+        0x86, 0x51,
+    ];
+
+    /// Pseudo random test data
+    fn test_data(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn known_parity() {
+        let rs = ReedSolomon::new(RSCode::RS255_223, 1).unwrap();
+        let frame: Vec<u8> = (0..223).map(|i| i as u8).collect();
+        let codeblock = rs.encode(&frame).unwrap();
+        assert_eq!(&codeblock[..223], &frame[..]);
+        assert_eq!(codeblock[223..], PARITY_0_TO_222);
+    }
+
+    #[test]
+    fn interleaved_and_shortened_codewords() {
+        for code in [RSCode::RS255_223, RSCode::RS255_239] {
+            let k = code.data_length();
+            let nroots = code.parity_length();
+            let single = ReedSolomon::new(code, 1).unwrap();
+
+            // a shortened codeword has the check symbols of the full codeword with
+            // leading zeros
+            let frame = test_data(100, 1);
+            let mut full = vec![0; k - frame.len()];
+            full.extend_from_slice(&frame);
+            assert_eq!(
+                single.encode(&frame).unwrap()[frame.len()..],
+                single.encode(&full).unwrap()[k..]
+            );
+
+            // the codewords of an interleaved codeblock are independent
+            let depth = 5;
+            let rs = ReedSolomon::new(code, depth as u8).unwrap();
+            let frame = test_data(depth * k, 2);
+            let codeblock = rs.encode(&frame).unwrap();
+            for cw in 0..depth {
+                let data: Vec<u8> = frame.iter().skip(cw).step_by(depth).copied().collect();
+                let parity: Vec<u8> = codeblock[frame.len()..]
+                    .iter()
+                    .skip(cw)
+                    .step_by(depth)
+                    .copied()
+                    .collect();
+                assert_eq!(parity.len(), nroots);
+                assert_eq!(single.encode(&data).unwrap()[k..], parity[..]);
+            }
+        }
+    }
+
+    /// Flip `count` symbols of codeword `cw`, spread over data and check symbols
+    fn inject_errors(codeblock: &mut [u8], depth: usize, cw: usize, count: usize) {
+        let len = codeblock.len() / depth;
+        for e in 0..count {
+            let pos = (e * 37 + cw * 11) % len;
+            codeblock[pos * depth + cw] ^= (e as u8).wrapping_mul(29) | 1;
+        }
+    }
+
+    #[test]
+    fn corrects_up_to_t_errors() {
+        for code in [RSCode::RS255_223, RSCode::RS255_239] {
+            let t = code.correctable_errors();
+            for depth in [1, 4, 5] {
+                let rs = ReedSolomon::new(code, depth as u8).unwrap();
+                let frame = test_data(depth * code.data_length(), depth as u32);
+                let codeblock = rs.encode(&frame).unwrap();
+
+                let mut received = codeblock.clone();
+                assert_eq!(rs.decode(&mut received).unwrap(), RSStatus::Corrected(0));
+
+                for cw in 0..depth {
+                    inject_errors(&mut received, depth, cw, t);
+                }
+                assert_eq!(
+                    rs.decode(&mut received).unwrap(),
+                    RSStatus::Corrected(depth * t),
+                    "{code:?} depth {depth}"
+                );
+                assert_eq!(received, codeblock);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_t_plus_one_errors() {
+        for code in [RSCode::RS255_223, RSCode::RS255_239] {
+            let t = code.correctable_errors();
+            for depth in [1, 4, 5] {
+                let rs = ReedSolomon::new(code, depth as u8).unwrap();
+                let frame = test_data(depth * code.data_length(), 10 + depth as u32);
+                let mut received = rs.encode(&frame).unwrap();
+
+                // the other codewords are correctable, but the codeblock is left
+                // unchanged
+                for cw in 0..depth {
+                    let count = if cw == depth - 1 { t + 1 } else { t };
+                    inject_errors(&mut received, depth, cw, count);
+                }
+                let corrupted = received.clone();
+                assert_eq!(
+                    rs.decode(&mut received).unwrap(),
+                    RSStatus::Uncorrectable,
+                    "{code:?} depth {depth}"
+                );
+                assert_eq!(received, corrupted);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_lengths() {
+        assert!(ReedSolomon::new(RSCode::RS255_223, 0).is_err());
+        assert!(ReedSolomon::new(RSCode::RS255_223, 9).is_err());
+        let rs = ReedSolomon::new(RSCode::RS255_223, 4).unwrap();
+        assert!(rs.encode(&[0; 4 * 223 + 4]).is_err());
+        assert!(rs.encode(&[0; 10]).is_err());
+        assert!(rs.decode(&mut [0; 10]).is_err());
+    }
+}
@@ -0,0 +1,510 @@
+//! Space Data Link Security (CCSDS 355.0-B) for TC, TM and AOS frames.
+//!
+//! A protected frame carries a security header at the start of its data field
+//! (after the segment header for TC, after the secondary header for TM and after the
+//! insert zone for AOS) and a security trailer with the MAC at its end, in front of
+//! the OCF and FECF. The security header contains the security parameter index
+//! (SPI), which selects the [SecurityAssociation], the IV and the anti-replay
+//! sequence number (ARSN).
+//!
+//! The only supported algorithm is AES-256-GCM with a 12 byte IV and a 16 byte MAC.
+//! Frames are protected in place: the frame is built with
+//! [SecurityAssociationTable::prepare_data_field] as data field and then passed to
+//! [SecurityAssociationTable::apply_security]. Received frames are verified and
+//! decrypted in place with [SecurityAssociationTable::process_security].
+//!
+//! Both ARSNs start at the values configured in [SAConfig]. To keep the anti-replay
+//! protection over a restart, the current values ([SecurityAssociation::send_arsn],
+//! [SecurityAssociation::recv_arsn]) have to be persisted and restored.
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use aes_gcm::aead::{AeadCore, AeadInPlace, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce, Tag};
+use serde::{Deserialize, Serialize};
+
+use crate::aos_frame::{AOSConfig, AOSFrame};
+use crate::crc::calc_crc;
+use crate::tc_frame::TCFrame;
+use crate::tm_frame::TMFrame;
+
+/// The length of the security parameter index
+pub const SPI_LEN: usize = 2;
+/// The length of the IV used with AES-GCM
+pub const IV_LEN: usize = 12;
+/// The length of the MAC used with AES-GCM
+pub const MAC_LEN: usize = 16;
+/// The length of an AES-256 key
+pub const KEY_LEN: usize = 32;
+
+const MAX_ARSN_LEN: usize = 8;
+
+/// The cryptographic service provided by a security association
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServiceType {
+    /// The frame is authenticated, the data is transmitted in clear
+    Authentication,
+    /// The data is encrypted and the frame is authenticated
+    AuthenticatedEncryption,
+}
+
+/// The kind of frame and its managed parameters, needed to locate the data field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    TC { segment_header: bool, fecf: bool },
+    TM { fecf: bool },
+    AOS(AOSConfig),
+}
+
+impl FrameType {
+    fn has_fecf(&self) -> bool {
+        match self {
+            FrameType::TC { fecf, .. } | FrameType::TM { fecf } => *fecf,
+            FrameType::AOS(config) => config.fecf,
+        }
+    }
+
+    /// The position of the part of the data field which contains the security
+    /// header, the data and the security trailer, and the length of the frame
+    fn secured_range(&self, frame: &[u8]) -> Result<(Range<usize>, usize), Error> {
+        let (data, frame) = match self {
+            FrameType::TC {
+                segment_header,
+                fecf,
+            } => {
+                let tc = TCFrame::new(frame, *segment_header, *fecf)?;
+                (tc.user_data(), tc.as_bytes())
+            }
+            FrameType::TM { fecf } => {
+                let tm = TMFrame::new(frame, *fecf)?;
+                (tm.data_field(), tm.as_bytes())
+            }
+            FrameType::AOS(config) => {
+                let aos = AOSFrame::new(frame, config)?;
+                (aos.data_field(), aos.as_bytes())
+            }
+        };
+        let start = data.as_ptr() as usize - frame.as_ptr() as usize;
+        Ok((start..start + data.len(), frame.len()))
+    }
+}
+
+fn default_arsn_length() -> usize {
+    4
+}
+
+fn default_arsn_window() -> u64 {
+    100
+}
+
+/// The configuration of a security association
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SAConfig {
+    pub spi: u16,
+    pub service: ServiceType,
+    /// A file containing the hex encoded 256 bit key
+    pub key_file: PathBuf,
+    /// The length of the ARSN in the security header, 0 disables anti-replay checks
+    #[serde(default = "default_arsn_length")]
+    pub arsn_length: usize,
+    /// Received frames are only accepted if their ARSN is greater than the last one
+    /// received, by at most this value
+    #[serde(default = "default_arsn_window")]
+    pub arsn_window: u64,
+    /// The ARSN of the last frame accepted, e.g. persisted before a restart
+    #[serde(default)]
+    pub recv_arsn: u64,
+    /// The ARSN of the last frame sent, e.g. persisted before a restart
+    #[serde(default)]
+    pub send_arsn: u64,
+    /// Accept the first frame received with any ARSN instead of checking it
+    /// against `recv_arsn`. This allows replays until the first frame is accepted.
+    #[serde(default)]
+    pub accept_any_initial_arsn: bool,
+}
+
+/// The configuration of the security association table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SdlsConfig {
+    pub security_associations: Vec<SAConfig>,
+}
+
+/// A security association with its keys and sequence numbers
+pub struct SecurityAssociation {
+    spi: u16,
+    service: ServiceType,
+    cipher: Aes256Gcm,
+    arsn_length: usize,
+    arsn_window: u64,
+    // the IV of the last frame sent, incremented for every frame
+    iv: [u8; IV_LEN],
+    // the ARSN of the last frame sent
+    send_arsn: u64,
+    // the ARSN of the last frame accepted, None accepts any ARSN
+    recv_arsn: Option<u64>,
+}
+
+impl std::fmt::Debug for SecurityAssociation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the key is left out on purpose
+        f.debug_struct("SecurityAssociation")
+            .field("spi", &self.spi)
+            .field("service", &self.service)
+            .field("arsn_length", &self.arsn_length)
+            .field("arsn_window", &self.arsn_window)
+            .field("send_arsn", &self.send_arsn)
+            .field("recv_arsn", &self.recv_arsn)
+            .finish()
+    }
+}
+
+impl SecurityAssociation {
+    /// Create a security association with the given key. The IV starts at a random
+    /// value, so it is not reused after a restart. Both ARSNs start at 0, use
+    /// [SecurityAssociation::set_recv_arsn] and [SecurityAssociation::set_send_arsn]
+    /// to restore persisted values.
+    pub fn new(
+        spi: u16,
+        service: ServiceType,
+        key: &[u8],
+        arsn_length: usize,
+        arsn_window: u64,
+    ) -> Result<SecurityAssociation, Error> {
+        if key.len() != KEY_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("SDLS: SPI {spi}: key has {} bytes, need {KEY_LEN}", key.len()),
+            ));
+        }
+        if arsn_length > MAX_ARSN_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("SDLS: SPI {spi}: ARSN length {arsn_length} exceeds {MAX_ARSN_LEN}"),
+            ));
+        }
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| {
+            Error::new(ErrorKind::InvalidInput, format!("SDLS: SPI {spi}: {e}"))
+        })?;
+
+        let mut iv = [0u8; IV_LEN];
+        iv.copy_from_slice(&Aes256Gcm::generate_nonce(&mut OsRng));
+        Ok(SecurityAssociation {
+            spi,
+            service,
+            cipher,
+            arsn_length,
+            arsn_window,
+            iv,
+            send_arsn: 0,
+            recv_arsn: Some(0),
+        })
+    }
+
+    /// Create the security association from its config, reading the key file
+    pub fn from_config(config: &SAConfig) -> Result<SecurityAssociation, Error> {
+        let content = std::fs::read_to_string(&config.key_file)?;
+        let key = hex::decode(content.trim()).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("SDLS: key file {}: {e}", config.key_file.display()),
+            )
+        })?;
+        let mut sa = Self::new(
+            config.spi,
+            config.service,
+            &key,
+            config.arsn_length,
+            config.arsn_window,
+        )?;
+        sa.set_send_arsn(config.send_arsn)?;
+        if config.accept_any_initial_arsn {
+            sa.set_recv_arsn(None)?;
+        } else {
+            sa.set_recv_arsn(Some(config.recv_arsn))?;
+        }
+        Ok(sa)
+    }
+
+    pub fn spi(&self) -> u16 {
+        self.spi
+    }
+
+    pub fn service(&self) -> ServiceType {
+        self.service
+    }
+
+    /// The length of the security header
+    pub fn header_length(&self) -> usize {
+        SPI_LEN + IV_LEN + self.arsn_length
+    }
+
+    /// The length of the security trailer
+    pub fn trailer_length(&self) -> usize {
+        MAC_LEN
+    }
+
+    /// The ARSN of the last frame sent
+    pub fn send_arsn(&self) -> u64 {
+        self.send_arsn
+    }
+
+    /// The ARSN of the last frame accepted. None, if the next frame is accepted
+    /// with any ARSN.
+    pub fn recv_arsn(&self) -> Option<u64> {
+        self.recv_arsn
+    }
+
+    /// Sets the ARSN of the last frame sent, e.g. to continue after a restart
+    pub fn set_send_arsn(&mut self, arsn: u64) -> Result<(), Error> {
+        self.send_arsn = self.check_arsn(arsn)?;
+        Ok(())
+    }
+
+    /// Sets the ARSN of the last frame accepted, e.g. to continue after a restart.
+    /// With None, the next frame is accepted with any ARSN.
+    pub fn set_recv_arsn(&mut self, arsn: Option<u64>) -> Result<(), Error> {
+        self.recv_arsn = arsn.map(|arsn| self.check_arsn(arsn)).transpose()?;
+        Ok(())
+    }
+
+    fn check_arsn(&self, arsn: u64) -> Result<u64, Error> {
+        if arsn & !self.arsn_mask() != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "SDLS: SPI {}: ARSN {arsn} does not fit into {} bytes",
+                    self.spi, self.arsn_length
+                ),
+            ));
+        }
+        Ok(arsn)
+    }
+
+    fn arsn_mask(&self) -> u64 {
+        if self.arsn_length == MAX_ARSN_LEN {
+            u64::MAX
+        } else {
+            (1u64 << (8 * self.arsn_length)) - 1
+        }
+    }
+
+    fn check_length(&self, secured: &Range<usize>) -> Result<(), Error> {
+        let overhead = self.header_length() + self.trailer_length();
+        if secured.len() < overhead {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "SDLS: SPI {}: data field of {} bytes too short for security header and trailer",
+                    self.spi,
+                    secured.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, frame: &mut [u8], secured: Range<usize>) -> Result<(), Error> {
+        self.check_length(&secured)?;
+
+        increment(&mut self.iv);
+        self.send_arsn = self.send_arsn.wrapping_add(1) & self.arsn_mask();
+
+        let hdr_start = secured.start;
+        let data_start = hdr_start + self.header_length();
+        let mac_start = secured.end - MAC_LEN;
+
+        frame[hdr_start..hdr_start + SPI_LEN].copy_from_slice(&self.spi.to_be_bytes());
+        frame[hdr_start + SPI_LEN..hdr_start + SPI_LEN + IV_LEN].copy_from_slice(&self.iv);
+        frame[hdr_start + SPI_LEN + IV_LEN..data_start]
+            .copy_from_slice(&self.send_arsn.to_be_bytes()[MAX_ARSN_LEN - self.arsn_length..]);
+
+        let nonce = Nonce::from_slice(&self.iv);
+        let (head, rest) = frame.split_at_mut(data_start);
+        let (data, trailer) = rest.split_at_mut(mac_start - data_start);
+        let tag = match self.service {
+            ServiceType::AuthenticatedEncryption => {
+                self.cipher.encrypt_in_place_detached(nonce, head, data)
+            }
+            ServiceType::Authentication => {
+                let aad = [&head[..], &data[..]].concat();
+                self.cipher.encrypt_in_place_detached(nonce, &aad, &mut [])
+            }
+        }
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("SDLS: SPI {}: {e}", self.spi)))?;
+        trailer[..MAC_LEN].copy_from_slice(&tag);
+        Ok(())
+    }
+
+    fn process(&mut self, frame: &mut [u8], secured: Range<usize>) -> Result<ProcessedFrame, Error> {
+        self.check_length(&secured)?;
+
+        let hdr_start = secured.start;
+        let data_start = hdr_start + self.header_length();
+        let mac_start = secured.end - MAC_LEN;
+
+        let arsn = frame[hdr_start + SPI_LEN + IV_LEN..data_start]
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        if self.arsn_length > 0 {
+            if let Some(last) = self.recv_arsn {
+                let diff = arsn.wrapping_sub(last) & self.arsn_mask();
+                if diff == 0 || diff > self.arsn_window {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "SDLS: SPI {}: ARSN {arsn} rejected, last accepted was {last}",
+                            self.spi
+                        ),
+                    ));
+                }
+            }
+        }
+
+        let mut iv = [0u8; IV_LEN];
+        iv.copy_from_slice(&frame[hdr_start + SPI_LEN..hdr_start + SPI_LEN + IV_LEN]);
+        let nonce = Nonce::from_slice(&iv);
+        let (head, rest) = frame.split_at_mut(data_start);
+        let (data, trailer) = rest.split_at_mut(mac_start - data_start);
+        let tag = Tag::from_slice(&trailer[..MAC_LEN]);
+        match self.service {
+            ServiceType::AuthenticatedEncryption => {
+                self.cipher.decrypt_in_place_detached(nonce, head, data, tag)
+            }
+            ServiceType::Authentication => {
+                let aad = [&head[..], &data[..]].concat();
+                self.cipher.decrypt_in_place_detached(nonce, &aad, &mut [], tag)
+            }
+        }
+        .map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("SDLS: SPI {}: MAC verification failed", self.spi),
+            )
+        })?;
+
+        if self.arsn_length > 0 {
+            self.recv_arsn = Some(arsn);
+        }
+        Ok(ProcessedFrame {
+            spi: self.spi,
+            arsn,
+            data: data_start..mac_start,
+        })
+    }
+}
+
+/// Increment the IV as a big endian counter
+fn increment(iv: &mut [u8; IV_LEN]) {
+    for b in iv.iter_mut().rev() {
+        *b = b.wrapping_add(1);
+        if *b != 0 {
+            break;
+        }
+    }
+}
+
+/// The result of processing a protected frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessedFrame {
+    pub spi: u16,
+    pub arsn: u64,
+    /// The position of the (decrypted) data in the frame
+    pub data: Range<usize>,
+}
+
+/// The security associations known, by their SPI
+#[derive(Debug, Default)]
+pub struct SecurityAssociationTable {
+    associations: BTreeMap<u16, SecurityAssociation>,
+}
+
+impl SecurityAssociationTable {
+    pub fn new() -> SecurityAssociationTable {
+        SecurityAssociationTable::default()
+    }
+
+    /// Create the table from the config, reading all key files
+    pub fn from_config(config: &SdlsConfig) -> Result<SecurityAssociationTable, Error> {
+        let mut table = SecurityAssociationTable::new();
+        for sa in &config.security_associations {
+            table.insert(SecurityAssociation::from_config(sa)?)?;
+        }
+        Ok(table)
+    }
+
+    /// Add a security association. Fails, if the SPI is already used.
+    pub fn insert(&mut self, sa: SecurityAssociation) -> Result<(), Error> {
+        if self.associations.contains_key(&sa.spi) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("SDLS: duplicate SPI {}", sa.spi),
+            ));
+        }
+        self.associations.insert(sa.spi, sa);
+        Ok(())
+    }
+
+    pub fn get(&self, spi: u16) -> Option<&SecurityAssociation> {
+        self.associations.get(&spi)
+    }
+
+    /// The security association with the SPI, e.g. to restore its ARSNs. Fails,
+    /// if the SPI is unknown.
+    pub fn get_mut(&mut self, spi: u16) -> Result<&mut SecurityAssociation, Error> {
+        self.associations.get_mut(&spi).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, format!("SDLS: unknown SPI {spi}"))
+        })
+    }
+
+    /// Create the data field for a frame protected with the given SPI: the data
+    /// surrounded by space for the security header and trailer, which are filled
+    /// by [SecurityAssociationTable::apply_security]
+    pub fn prepare_data_field(&self, spi: u16, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let sa = self.get(spi).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, format!("SDLS: unknown SPI {spi}"))
+        })?;
+        let mut field = vec![0u8; sa.header_length()];
+        field.extend_from_slice(data);
+        field.resize(field.len() + sa.trailer_length(), 0);
+        Ok(field)
+    }
+
+    /// Protect the frame in place with the given SPI. The data field must contain
+    /// the space for the security header and trailer. The FECF is updated.
+    pub fn apply_security(
+        &mut self,
+        spi: u16,
+        frame: &mut [u8],
+        frame_type: FrameType,
+    ) -> Result<(), Error> {
+        let (secured, len) = frame_type.secured_range(frame)?;
+        self.get_mut(spi)?.apply(frame, secured)?;
+
+        if frame_type.has_fecf() {
+            let crc = calc_crc(&frame[..len - 2]);
+            frame[len - 2..len].copy_from_slice(&crc.to_be_bytes());
+        }
+        Ok(())
+    }
+
+    /// Verify a protected frame and decrypt its data in place. The SPI is taken from
+    /// the security header. Any FECF has to be checked before, as it does not match
+    /// the decrypted frame.
+    pub fn process_security(
+        &mut self,
+        frame: &mut [u8],
+        frame_type: FrameType,
+    ) -> Result<ProcessedFrame, Error> {
+        let (secured, _) = frame_type.secured_range(frame)?;
+        if secured.len() < SPI_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "SDLS: data field too short for security header".to_string(),
+            ));
+        }
+        let spi = u16::from_be_bytes([frame[secured.start], frame[secured.start + 1]]);
+        self.get_mut(spi)?.process(frame, secured)
+    }
+}
@@ -0,0 +1,307 @@
+//! Segmentation and reassembly of space packets by their sequence flags.
+//!
+//! The [Reassembler] joins the data of FIRST, CONTINUATION and LAST segments per APID.
+//! The sequence counts of a group have to be continuous, groups which can not be
+//! completed (missing segments, timeout, size limit) are reported as
+//! [ReassemblyEvent::Incomplete]. The [Segmenter] splits large user data into
+//! correctly flagged packets for the upload.
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ccsds_packet::{CcsdsPacket, ErrorControl};
+use crate::pus_types::{HexBytes, PktID, SegFlags, SSC};
+
+const SSC_MODULUS: u16 = 0x4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReassemblyConfig {
+    /// The maximum length of the reassembled data
+    pub max_length: usize,
+    /// The maximum time between the first and the last segment of a group
+    pub timeout: Duration,
+}
+
+impl Default for ReassemblyConfig {
+    fn default() -> Self {
+        ReassemblyConfig {
+            max_length: 1024 * 1024,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The data of a complete group of segments or of an unsegmented packet
+#[derive(Debug, Clone)]
+pub struct ReassembledPacket {
+    pub pkt_id: PktID,
+    /// The sequence count of the first segment
+    pub first_ssc: u16,
+    /// The number of packets the data has been assembled from
+    pub segments: usize,
+    pub data: HexBytes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncompleteReason {
+    /// A CONTINUATION or LAST segment was received without a FIRST segment
+    MissingFirst,
+    /// The sequence count of a segment does not follow the previous one
+    SequenceGap { expected: u16, received: u16 },
+    /// A FIRST segment or an unsegmented packet was received before the LAST segment
+    Interrupted,
+    /// The group exceeded the maximum length
+    TooLarge,
+    /// The LAST segment was not received within the timeout
+    Timeout,
+    /// The group was still open on [Reassembler::flush]
+    Flushed,
+}
+
+#[derive(Debug, Clone)]
+pub enum ReassemblyEvent {
+    Complete(ReassembledPacket),
+    /// A group has been discarded. The segments received so far are not delivered,
+    /// further segments of the group are reported as [IncompleteReason::MissingFirst].
+    Incomplete {
+        apid: u16,
+        /// The sequence count of the first segment of the group, if it was received
+        first_ssc: Option<u16>,
+        segments: usize,
+        length: usize,
+        reason: IncompleteReason,
+    },
+}
+
+#[derive(Debug)]
+struct Group {
+    pkt_id: PktID,
+    first_ssc: u16,
+    last_ssc: u16,
+    segments: usize,
+    data: Vec<u8>,
+    started: Instant,
+}
+
+impl Group {
+    fn incomplete(self, reason: IncompleteReason) -> ReassemblyEvent {
+        ReassemblyEvent::Incomplete {
+            apid: self.pkt_id.apid.raw(),
+            first_ssc: Some(self.first_ssc),
+            segments: self.segments,
+            length: self.data.len(),
+            reason,
+        }
+    }
+}
+
+/// Reassembles segmented packets per APID. The packets are expected with their
+/// error control already checked and removed.
+#[derive(Debug)]
+pub struct Reassembler {
+    config: ReassemblyConfig,
+    groups: BTreeMap<u16, Group>,
+}
+
+impl Reassembler {
+    pub fn new(config: ReassemblyConfig) -> Reassembler {
+        Reassembler {
+            config,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// The number of APIDs with an open group
+    pub fn pending(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Processes a packet. Unsegmented packets are returned directly as complete
+    /// packets, segments are collected until the LAST segment has been received.
+    pub fn push(&mut self, pkt: CcsdsPacket) -> Vec<ReassemblyEvent> {
+        let apid = pkt.pkt_id.apid.raw();
+        let ssc = pkt.ssc.ssc();
+        let mut events = Vec::new();
+
+        match pkt.ssc.flags() {
+            SegFlags::Unsegmented | SegFlags::First => {
+                if let Some(group) = self.groups.remove(&apid) {
+                    events.push(group.incomplete(IncompleteReason::Interrupted));
+                }
+                let group = Group {
+                    pkt_id: pkt.pkt_id,
+                    first_ssc: ssc,
+                    last_ssc: ssc,
+                    segments: 1,
+                    data: pkt.data.0,
+                    started: Instant::now(),
+                };
+                if group.data.len() > self.config.max_length {
+                    events.push(group.incomplete(IncompleteReason::TooLarge));
+                } else if pkt.ssc.flags() == SegFlags::Unsegmented {
+                    events.push(Self::complete(group));
+                } else {
+                    self.groups.insert(apid, group);
+                }
+            }
+            flags @ (SegFlags::Continuation | SegFlags::Last) => {
+                let mut group = match self.groups.remove(&apid) {
+                    Some(group) => group,
+                    None => {
+                        events.push(ReassemblyEvent::Incomplete {
+                            apid,
+                            first_ssc: None,
+                            segments: 1,
+                            length: pkt.data.len(),
+                            reason: IncompleteReason::MissingFirst,
+                        });
+                        return events;
+                    }
+                };
+
+                let expected = (group.last_ssc + 1) % SSC_MODULUS;
+                if ssc != expected {
+                    events.push(group.incomplete(IncompleteReason::SequenceGap {
+                        expected,
+                        received: ssc,
+                    }));
+                    return events;
+                }
+
+                group.last_ssc = ssc;
+                group.segments += 1;
+                group.data.extend_from_slice(&pkt.data.0);
+
+                if group.data.len() > self.config.max_length {
+                    events.push(group.incomplete(IncompleteReason::TooLarge));
+                } else if flags == SegFlags::Last {
+                    events.push(Self::complete(group));
+                } else {
+                    self.groups.insert(apid, group);
+                }
+            }
+        }
+        events
+    }
+
+    /// Discards the groups which have not been completed within the timeout
+    pub fn check_timeouts(&mut self, now: Instant) -> Vec<ReassemblyEvent> {
+        let timeout = self.config.timeout;
+        let expired: Vec<u16> = self
+            .groups
+            .iter()
+            .filter(|(_, group)| now.saturating_duration_since(group.started) >= timeout)
+            .map(|(apid, _)| *apid)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|apid| self.groups.remove(&apid))
+            .map(|group| group.incomplete(IncompleteReason::Timeout))
+            .collect()
+    }
+
+    /// Discards all open groups, e.g. at the end of a pass
+    pub fn flush(&mut self) -> Vec<ReassemblyEvent> {
+        std::mem::take(&mut self.groups)
+            .into_values()
+            .map(|group| group.incomplete(IncompleteReason::Flushed))
+            .collect()
+    }
+
+    fn complete(group: Group) -> ReassemblyEvent {
+        ReassemblyEvent::Complete(ReassembledPacket {
+            pkt_id: group.pkt_id,
+            first_ssc: group.first_ssc,
+            segments: group.segments,
+            data: HexBytes(group.data),
+        })
+    }
+}
+
+/// Splits user data into packets of one APID, continuing the sequence count over
+/// all packets created
+#[derive(Debug, Clone)]
+pub struct Segmenter {
+    pkt_id: PktID,
+    max_data_length: usize,
+    error_control: ErrorControl,
+    ssc: u16,
+}
+
+impl Segmenter {
+    /// Creates a segmenter for packets with at most `max_data_length` bytes of data.
+    /// The error control is not included, it is appended when encoding the packets,
+    /// so the data and the error control together have to fit into a packet.
+    pub fn new(
+        pkt_id: PktID,
+        max_data_length: usize,
+        ec: ErrorControl,
+    ) -> Result<Segmenter, Error> {
+        if max_data_length == 0
+            || max_data_length + ec.field_length() > CcsdsPacket::MAX_DATA_LENGTH
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Segmenter: invalid maximum data length {max_data_length} with {ec:?}"),
+            ));
+        }
+        Ok(Segmenter {
+            pkt_id,
+            max_data_length,
+            error_control: ec,
+            ssc: 0,
+        })
+    }
+
+    /// The error control the packets are encoded with
+    pub fn error_control(&self) -> ErrorControl {
+        self.error_control
+    }
+
+    /// Start with the given sequence count
+    pub fn ssc(mut self, ssc: u16) -> Self {
+        self.ssc = ssc % SSC_MODULUS;
+        self
+    }
+
+    /// The sequence count of the next packet
+    pub fn next_ssc(&self) -> u16 {
+        self.ssc
+    }
+
+    /// Splits the data into packets. Data which fits into one packet is sent
+    /// unsegmented.
+    pub fn segment(&mut self, data: &[u8]) -> Vec<CcsdsPacket> {
+        if data.len() <= self.max_data_length {
+            return vec![self.packet(SegFlags::Unsegmented, data)];
+        }
+
+        let count = data.len().div_ceil(self.max_data_length);
+        data.chunks(self.max_data_length)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let flags = if i == 0 {
+                    SegFlags::First
+                } else if i == count - 1 {
+                    SegFlags::Last
+                } else {
+                    SegFlags::Continuation
+                };
+                self.packet(flags, chunk)
+            })
+            .collect()
+    }
+
+    fn packet(&mut self, flags: SegFlags, data: &[u8]) -> CcsdsPacket {
+        let pkt = CcsdsPacket {
+            pkt_id: self.pkt_id.clone(),
+            ssc: SSC::new(flags, self.ssc),
+            data: HexBytes(data.to_vec()),
+        };
+        self.ssc = (self.ssc + 1) % SSC_MODULUS;
+        pkt
+    }
+}
@@ -0,0 +1,161 @@
+//! Monitoring of the packet sequence counts.
+//!
+//! The [SequenceMonitor] tracks the 14 bit sequence count per APID. A packet with a
+//! count behind the last one (within the reorder window) is reported as out of order,
+//! a repetition of the last count as duplicate. All other jumps are gaps, taking the
+//! wraparound of the counter into account. Statistics are kept per APID.
+use std::collections::BTreeMap;
+
+use crate::ccsds_packet::FastCcsdsPacket;
+use crate::pus_types::SSC;
+
+const SSC_MODULUS: u16 = 0x4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceEvent {
+    /// `missing` packets between the last and the received one have not been received
+    Gap {
+        apid: u16,
+        expected: u16,
+        received: u16,
+        missing: u16,
+    },
+    /// The packet has the same count as the last one
+    Duplicate { apid: u16, ssc: u16 },
+    /// The packet is older than the last one
+    OutOfOrder { apid: u16, last: u16, received: u16 },
+}
+
+/// Cumulative statistics of an APID
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SequenceStatistics {
+    pub packets: u64,
+    /// The sum of the missing packets of all gaps
+    pub missing: u64,
+    pub gaps: u64,
+    pub duplicates: u64,
+    pub out_of_order: u64,
+    /// The count of the last packet in sequence
+    pub last_ssc: Option<u16>,
+}
+
+impl SequenceStatistics {
+    fn add(&mut self, other: &SequenceStatistics) {
+        self.packets += other.packets;
+        self.missing += other.missing;
+        self.gaps += other.gaps;
+        self.duplicates += other.duplicates;
+        self.out_of_order += other.out_of_order;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SequenceMonitor {
+    reorder_window: u16,
+    apids: BTreeMap<u16, SequenceStatistics>,
+}
+
+impl SequenceMonitor {
+    /// The default number of counts behind the last packet which are considered as
+    /// out of order instead of a gap
+    pub const DEFAULT_REORDER_WINDOW: u16 = 64;
+
+    pub fn new() -> SequenceMonitor {
+        SequenceMonitor {
+            reorder_window: Self::DEFAULT_REORDER_WINDOW,
+            apids: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the reorder window. With 0, every backward jump is reported as gap.
+    pub fn reorder_window(mut self, window: u16) -> Self {
+        self.reorder_window = window.min(SSC_MODULUS / 2);
+        self
+    }
+
+    /// Checks the sequence count of a packet. The first packet of an APID is
+    /// always in sequence.
+    pub fn check(&mut self, apid: u16, ssc: u16) -> Option<SequenceEvent> {
+        let ssc = ssc % SSC_MODULUS;
+        let stats = self.apids.entry(apid).or_default();
+        stats.packets += 1;
+
+        let last = match stats.last_ssc {
+            Some(last) => last,
+            None => {
+                stats.last_ssc = Some(ssc);
+                return None;
+            }
+        };
+
+        let expected = (last + 1) % SSC_MODULUS;
+        if ssc == expected {
+            stats.last_ssc = Some(ssc);
+            return None;
+        }
+        if ssc == last {
+            stats.duplicates += 1;
+            return Some(SequenceEvent::Duplicate { apid, ssc });
+        }
+
+        let behind = (last + SSC_MODULUS - ssc) % SSC_MODULUS;
+        if behind <= self.reorder_window {
+            stats.out_of_order += 1;
+            return Some(SequenceEvent::OutOfOrder {
+                apid,
+                last,
+                received: ssc,
+            });
+        }
+
+        let missing = (ssc + SSC_MODULUS - expected) % SSC_MODULUS;
+        stats.gaps += 1;
+        stats.missing += missing as u64;
+        stats.last_ssc = Some(ssc);
+        Some(SequenceEvent::Gap {
+            apid,
+            expected,
+            received: ssc,
+            missing,
+        })
+    }
+
+    /// Checks the sequence count of the packet
+    pub fn check_packet(&mut self, pkt: &FastCcsdsPacket) -> Option<SequenceEvent> {
+        let ssc = SSC::new_from_bytes(&pkt.hdr[2..4]);
+        self.check(pkt.apid(), ssc.ssc())
+    }
+
+    pub fn statistics(&self, apid: u16) -> Option<&SequenceStatistics> {
+        self.apids.get(&apid)
+    }
+
+    /// The statistics of all APIDs seen so far
+    pub fn apids(&self) -> impl Iterator<Item = (u16, &SequenceStatistics)> {
+        self.apids.iter().map(|(apid, stats)| (*apid, stats))
+    }
+
+    /// The statistics summed over all APIDs. The last count is not set.
+    pub fn total(&self) -> SequenceStatistics {
+        let mut total = SequenceStatistics::default();
+        for stats in self.apids.values() {
+            total.add(stats);
+        }
+        total
+    }
+
+    /// Forget the APID, so its next packet is in sequence again
+    pub fn reset_apid(&mut self, apid: u16) {
+        self.apids.remove(&apid);
+    }
+
+    pub fn reset(&mut self) {
+        self.apids.clear();
+    }
+}
+
+impl Default for SequenceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
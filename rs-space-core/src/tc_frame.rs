@@ -0,0 +1,448 @@
+//! TC Space Data Link Protocol transfer frames (CCSDS 232.0-B) including the TC
+//! segment header.
+//!
+//! [TCFrame] is a zero-copy view over the bytes of a frame, [TCFrameBuilder] creates
+//! new frames. Packets can be put into frames with [TCFrameBuilder::frames_from_packet]
+//! (segmenting them if necessary) and extracted again with [TCFrame::packets] or,
+//! for segmented packets, with a [SegmentAssembler].
+use std::io::{Error, ErrorKind};
+
+use crate::ccsds_packet::{CcsdsPacket, FastCcsdsPacket};
+use crate::crc::calc_crc;
+use crate::pus_types::SegFlags;
+
+/// The transfer frame version number of TC frames
+pub const TC_VERSION: u8 = 0;
+/// The maximum length of a TC transfer frame
+pub const TC_MAX_FRAME_LEN: usize = 1024;
+
+/// The segment header in front of the data of a TC frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentHeader {
+    pub flags: SegFlags,
+    pub map_id: u8,
+}
+
+impl SegmentHeader {
+    pub const LEN: usize = 1;
+
+    pub fn new(flags: SegFlags, map_id: u8) -> SegmentHeader {
+        SegmentHeader {
+            flags,
+            map_id: map_id & 0x3F,
+        }
+    }
+
+    pub fn from_byte(b: u8) -> SegmentHeader {
+        SegmentHeader {
+            flags: SegFlags::from_bits(b >> 6),
+            map_id: b & 0x3F,
+        }
+    }
+
+    pub fn to_byte(&self) -> u8 {
+        (self.flags.to_bits() << 6) | (self.map_id & 0x3F)
+    }
+}
+
+/// A view on a TC transfer frame. Whether a frame has a segment header and a FECF
+/// are managed parameters of the virtual and physical channel and have to be provided.
+#[derive(Debug, Clone, Copy)]
+pub struct TCFrame<'a> {
+    data: &'a [u8],
+    has_segment_header: bool,
+    has_fecf: bool,
+}
+
+impl<'a> TCFrame<'a> {
+    pub const PRIMARY_HDR_LEN: usize = 5;
+    pub const FECF_LEN: usize = 2;
+
+    /// Creates a view on the frame at the start of `data`. The frame length is taken
+    /// from the header, additional bytes after the frame are ignored. Control command
+    /// frames never have a segment header.
+    pub fn new(data: &'a [u8], has_segment_header: bool, has_fecf: bool) -> Result<TCFrame<'a>, Error> {
+        if data.len() < Self::PRIMARY_HDR_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("TC Frame: frame too short: {} bytes", data.len()),
+            ));
+        }
+
+        let mut frame = TCFrame {
+            data,
+            has_segment_header,
+            has_fecf,
+        };
+
+        if frame.version() != TC_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("TC Frame: wrong version number: {}", frame.version()),
+            ));
+        }
+
+        let len = frame.frame_length();
+        if data.len() < len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "TC Frame: frame too short: {} bytes, frame length is {len}",
+                    data.len()
+                ),
+            ));
+        }
+        frame.data = &data[..len];
+        frame.has_segment_header = has_segment_header && !frame.control_command_flag();
+
+        let min_len = Self::PRIMARY_HDR_LEN + frame.seg_hdr_len() + frame.fecf_len();
+        if len < min_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("TC Frame: frame too short: {len} bytes, need at least {min_len}"),
+            ));
+        }
+
+        Ok(frame)
+    }
+
+    /// The complete frame
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 6
+    }
+
+    /// True for type B (bypass) frames, false for type A (sequence controlled) frames
+    pub fn bypass_flag(&self) -> bool {
+        (self.data[0] & 0x20) != 0
+    }
+
+    /// True for control command frames (type C), false for type D (data) frames
+    pub fn control_command_flag(&self) -> bool {
+        (self.data[0] & 0x10) != 0
+    }
+
+    pub fn scid(&self) -> u16 {
+        ((self.data[0] as u16 & 0x03) << 8) | self.data[1] as u16
+    }
+
+    pub fn vcid(&self) -> u8 {
+        self.data[2] >> 2
+    }
+
+    /// The total length of the frame in bytes
+    pub fn frame_length(&self) -> usize {
+        // the header contains the length - 1
+        (((self.data[2] as usize & 0x03) << 8) | self.data[3] as usize) + 1
+    }
+
+    pub fn frame_sequence_number(&self) -> u8 {
+        self.data[4]
+    }
+
+    fn seg_hdr_len(&self) -> usize {
+        if self.has_segment_header {
+            SegmentHeader::LEN
+        } else {
+            0
+        }
+    }
+
+    fn fecf_len(&self) -> usize {
+        if self.has_fecf {
+            Self::FECF_LEN
+        } else {
+            0
+        }
+    }
+
+    pub fn segment_header(&self) -> Option<SegmentHeader> {
+        if self.has_segment_header {
+            Some(SegmentHeader::from_byte(self.data[Self::PRIMARY_HDR_LEN]))
+        } else {
+            None
+        }
+    }
+
+    /// The data field of the frame including the segment header
+    pub fn data_field(&self) -> &'a [u8] {
+        &self.data[Self::PRIMARY_HDR_LEN..self.data.len() - self.fecf_len()]
+    }
+
+    /// The user data of the frame (the data field without segment header)
+    pub fn user_data(&self) -> &'a [u8] {
+        &self.data_field()[self.seg_hdr_len()..]
+    }
+
+    pub fn fecf(&self) -> Option<u16> {
+        if self.has_fecf {
+            let len = self.data.len();
+            Some(((self.data[len - 2] as u16) << 8) | self.data[len - 1] as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Calculates the CRC over the frame without the FECF
+    pub fn calc_fecf(&self) -> u16 {
+        calc_crc(&self.data[..self.data.len() - self.fecf_len()])
+    }
+
+    /// Checks the FECF. Frames without FECF are always valid.
+    pub fn check_fecf(&self) -> bool {
+        match self.fecf() {
+            Some(fecf) => fecf == self.calc_fecf(),
+            None => true,
+        }
+    }
+
+    /// Returns the packets contained in the frame. This only works for frames
+    /// containing complete packets (no segment header or unsegmented), for segmented
+    /// packets use a [SegmentAssembler].
+    pub fn packets(&self) -> Result<Vec<CcsdsPacket>, Error> {
+        if let Some(seg_hdr) = self.segment_header() {
+            if seg_hdr.flags != SegFlags::Unsegmented {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "TC Frame: frame contains a packet segment ({})",
+                        seg_hdr.flags
+                    ),
+                ));
+            }
+        }
+        packets_from_slice(self.user_data())
+    }
+}
+
+fn packets_from_slice(mut data: &[u8]) -> Result<Vec<CcsdsPacket>, Error> {
+    let mut res = Vec::new();
+    while !data.is_empty() {
+        let pkt = FastCcsdsPacket::from_slice(data)?;
+        data = &data[pkt.total_length()..];
+        res.push(CcsdsPacket::from_fast_ccsds_pkt(pkt));
+    }
+    Ok(res)
+}
+
+/// Creates TC transfer frames
+#[derive(Debug, Clone, Default)]
+pub struct TCFrameBuilder {
+    bypass: bool,
+    control_command: bool,
+    scid: u16,
+    vcid: u8,
+    sequence_number: u8,
+    segment_header: Option<SegmentHeader>,
+    fecf: bool,
+}
+
+impl TCFrameBuilder {
+    pub fn new(scid: u16, vcid: u8) -> TCFrameBuilder {
+        TCFrameBuilder {
+            scid: scid & 0x3FF,
+            vcid: vcid & 0x3F,
+            ..Default::default()
+        }
+    }
+
+    pub fn bypass(mut self, flag: bool) -> Self {
+        self.bypass = flag;
+        self
+    }
+
+    pub fn control_command(mut self, flag: bool) -> Self {
+        self.control_command = flag;
+        self
+    }
+
+    pub fn sequence_number(mut self, seq: u8) -> Self {
+        self.sequence_number = seq;
+        self
+    }
+
+    /// Use a segment header with the given MAP ID. The sequence flags are set to
+    /// unsegmented, [TCFrameBuilder::frames_from_packet] sets them as needed.
+    pub fn map_id(mut self, map_id: u8) -> Self {
+        self.segment_header = Some(SegmentHeader::new(SegFlags::Unsegmented, map_id));
+        self
+    }
+
+    pub fn segment_header(mut self, hdr: Option<SegmentHeader>) -> Self {
+        self.segment_header = hdr;
+        self
+    }
+
+    /// If set, a FECF is appended to the frame
+    pub fn fecf(mut self, fecf: bool) -> Self {
+        self.fecf = fecf;
+        self
+    }
+
+    /// The length of the frame with the given user data length
+    pub fn frame_length(&self, user_data_len: usize) -> usize {
+        let mut len = TCFrame::PRIMARY_HDR_LEN + user_data_len;
+        if self.segment_header.is_some() {
+            len += SegmentHeader::LEN;
+        }
+        if self.fecf {
+            len += TCFrame::FECF_LEN;
+        }
+        len
+    }
+
+    /// Builds the frame with the given user data
+    pub fn build(&self, user_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let len = self.frame_length(user_data.len());
+        if len > TC_MAX_FRAME_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("TC Frame: frame length {len} exceeds maximum of {TC_MAX_FRAME_LEN}"),
+            ));
+        }
+
+        let mut frame = Vec::with_capacity(len);
+        let b0 = (TC_VERSION << 6)
+            | ((self.bypass as u8) << 5)
+            | ((self.control_command as u8) << 4)
+            | ((self.scid >> 8) as u8 & 0x03);
+        frame.push(b0);
+        frame.push((self.scid & 0xFF) as u8);
+
+        let enc_len = (len - 1) as u16;
+        frame.push((self.vcid << 2) | ((enc_len >> 8) as u8 & 0x03));
+        frame.push((enc_len & 0xFF) as u8);
+        frame.push(self.sequence_number);
+
+        if let Some(seg_hdr) = &self.segment_header {
+            frame.push(seg_hdr.to_byte());
+        }
+        frame.extend_from_slice(user_data);
+
+        if self.fecf {
+            let crc = calc_crc(&frame);
+            frame.extend_from_slice(&crc.to_be_bytes());
+        }
+        Ok(frame)
+    }
+
+    /// Puts the packet into frames with a maximum length of `max_frame_len`. If the
+    /// packet does not fit into one frame, it is segmented, which requires a segment
+    /// header. The frame sequence number is incremented for every frame, the builder
+    /// is left with the sequence number for the next frame.
+    pub fn frames_from_packet(
+        &mut self,
+        pkt: &CcsdsPacket,
+        max_frame_len: usize,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let pkt_data = pkt.clone().to_fast_ccsds_pkt()?.to_vec();
+        let max_frame_len = max_frame_len.min(TC_MAX_FRAME_LEN);
+        let max_data = max_frame_len.saturating_sub(self.frame_length(0));
+        if max_data == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("TC Frame: maximum frame length {max_frame_len} too small"),
+            ));
+        }
+
+        if pkt_data.len() <= max_data {
+            if let Some(hdr) = &mut self.segment_header {
+                hdr.flags = SegFlags::Unsegmented;
+            }
+            let frame = self.build(&pkt_data)?;
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            return Ok(vec![frame]);
+        }
+
+        if self.segment_header.is_none() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "TC Frame: packet of {} bytes does not fit into a frame and no segment header is used",
+                    pkt_data.len()
+                ),
+            ));
+        }
+
+        let chunks: Vec<&[u8]> = pkt_data.chunks(max_data).collect();
+        let last = chunks.len() - 1;
+        let mut frames = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let flags = match i {
+                0 => SegFlags::First,
+                i if i == last => SegFlags::Last,
+                _ => SegFlags::Continuation,
+            };
+            if let Some(hdr) = &mut self.segment_header {
+                hdr.flags = flags;
+            }
+            frames.push(self.build(chunk)?);
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+        }
+        Ok(frames)
+    }
+}
+
+/// Reassembles packets from segmented TC frames of one MAP
+#[derive(Debug, Default)]
+pub struct SegmentAssembler {
+    buffer: Vec<u8>,
+    in_progress: bool,
+    discarded: u32,
+}
+
+impl SegmentAssembler {
+    pub fn new() -> SegmentAssembler {
+        SegmentAssembler::default()
+    }
+
+    /// The number of incomplete segmented packets discarded so far
+    pub fn discarded(&self) -> u32 {
+        self.discarded
+    }
+
+    /// Process the next frame of the MAP. Returns the packets completed with this
+    /// frame. An incomplete segmented packet is discarded, if a new packet starts
+    /// before it is finished (see [SegmentAssembler::discarded]).
+    pub fn process(&mut self, frame: &TCFrame) -> Result<Vec<CcsdsPacket>, Error> {
+        let flags = frame
+            .segment_header()
+            .map(|hdr| hdr.flags)
+            .unwrap_or(SegFlags::Unsegmented);
+
+        if self.in_progress && matches!(flags, SegFlags::First | SegFlags::Unsegmented) {
+            self.buffer.clear();
+            self.in_progress = false;
+            self.discarded += 1;
+        }
+
+        match flags {
+            SegFlags::Unsegmented => packets_from_slice(frame.user_data()),
+            SegFlags::First => {
+                self.buffer.extend_from_slice(frame.user_data());
+                self.in_progress = true;
+                Ok(Vec::new())
+            }
+            SegFlags::Continuation | SegFlags::Last => {
+                if !self.in_progress {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("TC Frame: {flags} segment without a first segment"),
+                    ));
+                }
+                self.buffer.extend_from_slice(frame.user_data());
+                if flags == SegFlags::Last {
+                    self.in_progress = false;
+                    let res = packets_from_slice(&self.buffer);
+                    self.buffer.clear();
+                    res
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+        }
+    }
+}
@@ -74,6 +74,11 @@ impl Time {
         }
     }
 
+    /// Returns the time as duration since the UNIX epoch
+    pub fn as_duration(&self) -> Duration {
+        self.time
+    }
+
     pub fn len(&self) -> usize {
         time_length(self.encoding)
     }
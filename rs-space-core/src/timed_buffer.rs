@@ -27,12 +27,16 @@ pub struct Receiver<T> {
 
 impl<T> Sender<T> {
     pub async fn send(&self, msg: T) {
-        let mut lock = self.values.lock().unwrap();
-        if lock.len() < self.capacity {
-            lock.push(msg);
-            return;
-        } else {
-            drop(lock);
+        loop {
+            // the lock must not be held across the await, otherwise the future is not Send
+            {
+                let mut lock = self.values.lock().unwrap();
+                if lock.len() < self.capacity {
+                    lock.push(msg);
+                    return;
+                }
+            }
+            // buffer is full, wait until the receiver has taken the values
             self.notify_read.notify_one();
             self.notify_write.notified().await
         }
@@ -0,0 +1,370 @@
+//! TM Space Data Link Protocol transfer frames (CCSDS 132.0-B).
+//!
+//! [TMFrame] is a zero-copy view over the bytes of a frame (e.g. the
+//! `SpaceLinkDataUnit` delivered by SLE RAF), [TMFrameBuilder] creates new frames.
+use std::io::{Error, ErrorKind};
+
+use crate::crc::calc_crc;
+
+/// The transfer frame version number of TM frames
+pub const TM_VERSION: u8 = 0;
+/// First header pointer: no packet starts in this frame
+pub const FHP_NO_PACKET_START: u16 = 0x7FF;
+/// First header pointer: the data field contains only idle data
+pub const FHP_IDLE: u16 = 0x7FE;
+/// The virtual channel usually used for idle (only idle data) frames
+pub const IDLE_VCID: u8 = 7;
+
+/// A view on a TM transfer frame. The presence of the secondary header and of the
+/// operational control field is indicated in the primary header, the presence of the
+/// FECF is a managed parameter of the physical channel and has to be provided.
+#[derive(Debug, Clone, Copy)]
+pub struct TMFrame<'a> {
+    data: &'a [u8],
+    has_fecf: bool,
+}
+
+impl<'a> TMFrame<'a> {
+    pub const PRIMARY_HDR_LEN: usize = 6;
+    pub const OCF_LEN: usize = 4;
+    pub const FECF_LEN: usize = 2;
+
+    /// Creates a view on the frame contained in `data`. The slice must contain exactly
+    /// one frame. Returns an error if the frame is too short for the fields indicated
+    /// in the header or the version is not the TM version.
+    pub fn new(data: &'a [u8], has_fecf: bool) -> Result<TMFrame<'a>, Error> {
+        if data.len() < Self::PRIMARY_HDR_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("TM Frame: frame too short: {} bytes", data.len()),
+            ));
+        }
+
+        let frame = TMFrame { data, has_fecf };
+
+        if frame.version() != TM_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("TM Frame: wrong version number: {}", frame.version()),
+            ));
+        }
+
+        let mut min_len = Self::PRIMARY_HDR_LEN + frame.trailer_len();
+        if frame.sec_hdr_flag() {
+            if data.len() <= Self::PRIMARY_HDR_LEN {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "TM Frame: frame too short for secondary header",
+                ));
+            }
+            min_len += frame.sec_hdr_len();
+        }
+        if data.len() < min_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "TM Frame: frame too short: {} bytes, need at least {min_len}",
+                    data.len()
+                ),
+            ));
+        }
+
+        Ok(frame)
+    }
+
+    /// The complete frame
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 6
+    }
+
+    pub fn scid(&self) -> u16 {
+        (((self.data[0] as u16) << 8 | self.data[1] as u16) >> 4) & 0x3FF
+    }
+
+    pub fn vcid(&self) -> u8 {
+        (self.data[1] >> 1) & 0x07
+    }
+
+    /// The master channel ID (version and spacecraft ID)
+    pub fn mcid(&self) -> u16 {
+        ((self.version() as u16) << 10) | self.scid()
+    }
+
+    pub fn ocf_flag(&self) -> bool {
+        (self.data[1] & 0x01) != 0
+    }
+
+    pub fn mc_frame_count(&self) -> u8 {
+        self.data[2]
+    }
+
+    pub fn vc_frame_count(&self) -> u8 {
+        self.data[3]
+    }
+
+    pub fn sec_hdr_flag(&self) -> bool {
+        (self.data[4] & 0x80) != 0
+    }
+
+    pub fn sync_flag(&self) -> bool {
+        (self.data[4] & 0x40) != 0
+    }
+
+    pub fn packet_order_flag(&self) -> bool {
+        (self.data[4] & 0x20) != 0
+    }
+
+    pub fn segment_length_id(&self) -> u8 {
+        (self.data[4] >> 3) & 0x03
+    }
+
+    pub fn first_header_pointer(&self) -> u16 {
+        ((self.data[4] as u16 & 0x07) << 8) | self.data[5] as u16
+    }
+
+    /// True, if the data field contains only idle data
+    pub fn is_idle(&self) -> bool {
+        !self.sync_flag() && self.first_header_pointer() == FHP_IDLE
+    }
+
+    fn sec_hdr_len(&self) -> usize {
+        if self.sec_hdr_flag() {
+            // the length field contains the total length - 1
+            (self.data[Self::PRIMARY_HDR_LEN] & 0x3F) as usize + 1
+        } else {
+            0
+        }
+    }
+
+    fn trailer_len(&self) -> usize {
+        let mut len = 0;
+        if self.ocf_flag() {
+            len += Self::OCF_LEN;
+        }
+        if self.has_fecf {
+            len += Self::FECF_LEN;
+        }
+        len
+    }
+
+    /// The secondary header including its identification field
+    pub fn secondary_header(&self) -> Option<&'a [u8]> {
+        if self.sec_hdr_flag() {
+            let start = Self::PRIMARY_HDR_LEN;
+            Some(&self.data[start..start + self.sec_hdr_len()])
+        } else {
+            None
+        }
+    }
+
+    /// The data field of the frame
+    pub fn data_field(&self) -> &'a [u8] {
+        let start = Self::PRIMARY_HDR_LEN + self.sec_hdr_len();
+        let end = self.data.len() - self.trailer_len();
+        &self.data[start..end]
+    }
+
+    /// The operational control field (e.g. a CLCW)
+    pub fn ocf(&self) -> Option<u32> {
+        if self.ocf_flag() {
+            let start = self.data.len() - self.trailer_len();
+            let mut arr = [0u8; 4];
+            arr.copy_from_slice(&self.data[start..start + Self::OCF_LEN]);
+            Some(u32::from_be_bytes(arr))
+        } else {
+            None
+        }
+    }
+
+    /// The frame error control field, if present
+    pub fn fecf(&self) -> Option<u16> {
+        if self.has_fecf {
+            let len = self.data.len();
+            Some(((self.data[len - 2] as u16) << 8) | self.data[len - 1] as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Calculates the CRC over the frame without the FECF
+    pub fn calc_fecf(&self) -> u16 {
+        let end = if self.has_fecf {
+            self.data.len() - Self::FECF_LEN
+        } else {
+            self.data.len()
+        };
+        calc_crc(&self.data[..end])
+    }
+
+    /// Checks the FECF. Frames without FECF are always valid.
+    pub fn check_fecf(&self) -> bool {
+        match self.fecf() {
+            Some(fecf) => fecf == self.calc_fecf(),
+            None => true,
+        }
+    }
+}
+
+/// Creates TM transfer frames. The frame length is determined by the secondary
+/// header, the data field and the trailer.
+#[derive(Debug, Clone, Default)]
+pub struct TMFrameBuilder {
+    scid: u16,
+    vcid: u8,
+    mc_frame_count: u8,
+    vc_frame_count: u8,
+    sync_flag: bool,
+    packet_order_flag: bool,
+    segment_length_id: u8,
+    first_header_pointer: u16,
+    secondary_header: Option<Vec<u8>>,
+    ocf: Option<u32>,
+    fecf: bool,
+}
+
+impl TMFrameBuilder {
+    pub fn new(scid: u16, vcid: u8) -> TMFrameBuilder {
+        TMFrameBuilder {
+            scid: scid & 0x3FF,
+            vcid: vcid & 0x07,
+            // must be 0b11 if the sync flag is not set
+            segment_length_id: 0b11,
+            ..Default::default()
+        }
+    }
+
+    pub fn mc_frame_count(mut self, cnt: u8) -> Self {
+        self.mc_frame_count = cnt;
+        self
+    }
+
+    pub fn vc_frame_count(mut self, cnt: u8) -> Self {
+        self.vc_frame_count = cnt;
+        self
+    }
+
+    pub fn sync_flag(mut self, flag: bool) -> Self {
+        self.sync_flag = flag;
+        self
+    }
+
+    pub fn packet_order_flag(mut self, flag: bool) -> Self {
+        self.packet_order_flag = flag;
+        self
+    }
+
+    pub fn segment_length_id(mut self, id: u8) -> Self {
+        self.segment_length_id = id & 0x03;
+        self
+    }
+
+    pub fn first_header_pointer(mut self, fhp: u16) -> Self {
+        self.first_header_pointer = fhp & 0x7FF;
+        self
+    }
+
+    /// Set the secondary header data, without the identification field (max. 63 bytes).
+    /// Version 1 is used for the identification field.
+    pub fn secondary_header(mut self, data: &[u8]) -> Self {
+        self.secondary_header = Some(data.to_vec());
+        self
+    }
+
+    pub fn ocf(mut self, ocf: u32) -> Self {
+        self.ocf = Some(ocf);
+        self
+    }
+
+    /// If set, a FECF is appended to the frame
+    pub fn fecf(mut self, fecf: bool) -> Self {
+        self.fecf = fecf;
+        self
+    }
+
+    /// The length of the frame with the given data field length
+    pub fn frame_length(&self, data_field_len: usize) -> usize {
+        let mut len = TMFrame::PRIMARY_HDR_LEN + data_field_len;
+        if let Some(sec_hdr) = &self.secondary_header {
+            len += sec_hdr.len() + 1;
+        }
+        if self.ocf.is_some() {
+            len += TMFrame::OCF_LEN;
+        }
+        if self.fecf {
+            len += TMFrame::FECF_LEN;
+        }
+        len
+    }
+
+    /// The length of the data field for a frame with the given total length
+    pub fn data_field_length(&self, frame_len: usize) -> Option<usize> {
+        frame_len.checked_sub(self.frame_length(0))
+    }
+
+    /// Builds the frame with the given data field
+    pub fn build(&self, data_field: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut frame = Vec::with_capacity(self.frame_length(data_field.len()));
+        self.build_into(data_field, &mut frame)?;
+        Ok(frame)
+    }
+
+    /// Builds the frame with the given data field and appends it to `frame`
+    pub fn build_into(&self, data_field: &[u8], frame: &mut Vec<u8>) -> Result<(), Error> {
+        let start = frame.len();
+
+        let id = ((TM_VERSION as u16) << 14)
+            | (self.scid << 4)
+            | ((self.vcid as u16) << 1)
+            | (self.ocf.is_some() as u16);
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.push(self.mc_frame_count);
+        frame.push(self.vc_frame_count);
+
+        let status = ((self.secondary_header.is_some() as u16) << 15)
+            | ((self.sync_flag as u16) << 14)
+            | ((self.packet_order_flag as u16) << 13)
+            | ((self.segment_length_id as u16) << 11)
+            | self.first_header_pointer;
+        frame.extend_from_slice(&status.to_be_bytes());
+
+        if let Some(sec_hdr) = &self.secondary_header {
+            if sec_hdr.is_empty() || sec_hdr.len() > 63 {
+                frame.truncate(start);
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "TM Frame: invalid secondary header length: {}",
+                        sec_hdr.len()
+                    ),
+                ));
+            }
+            // version 1 (0b00), length of the secondary header - 1
+            frame.push(sec_hdr.len() as u8);
+            frame.extend_from_slice(sec_hdr);
+        }
+
+        frame.extend_from_slice(data_field);
+
+        if let Some(ocf) = self.ocf {
+            frame.extend_from_slice(&ocf.to_be_bytes());
+        }
+
+        if self.fecf {
+            let crc = calc_crc(&frame[start..]);
+            frame.extend_from_slice(&crc.to_be_bytes());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,536 @@
+//! Unified Space Data Link Protocol transfer frames (CCSDS 732.1-B).
+//!
+//! USLP frames have a variable length primary header: the length of the VC frame
+//! count is given in the header, and truncated frames consist of only the first 4
+//! bytes of the header (indicated by the end of frame primary header flag) and the
+//! data field. The insert zone length, the FECF type and the length of truncated
+//! frames are managed parameters collected in a [USLPConfig].
+use std::io::{Error, ErrorKind};
+
+use crate::crc::{calc_crc, calc_crc32};
+
+/// The transfer frame version number of USLP frames (0b1100)
+pub const USLP_VERSION: u8 = 12;
+/// The maximum length of the VC frame count in bytes
+pub const MAX_VC_FRAME_COUNT_LEN: usize = 7;
+/// USLP protocol ID: space packets or encapsulation packets
+pub const UPID_PACKETS: u8 = 0;
+/// USLP protocol ID: COP-1 control commands
+pub const UPID_COP1: u8 = 1;
+/// USLP protocol ID: only idle data
+pub const UPID_IDLE: u8 = 31;
+/// First header pointer: no packet starts in this frame
+pub const FHP_NO_PACKET_START: u16 = 0xFFFF;
+
+/// The type of the frame error control field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FecfType {
+    #[default]
+    None,
+    Crc16,
+    Crc32,
+}
+
+impl FecfType {
+    pub fn len(&self) -> usize {
+        match self {
+            FecfType::None => 0,
+            FecfType::Crc16 => 2,
+            FecfType::Crc32 => 4,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == FecfType::None
+    }
+}
+
+/// The managed parameters of a USLP physical channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct USLPConfig {
+    /// The length of the insert zone, 0 if there is no insert zone
+    pub insert_zone_len: usize,
+    pub fecf: FecfType,
+    /// The length of truncated frames. Needed to parse truncated frames, as they
+    /// contain no frame length.
+    pub truncated_frame_len: Option<usize>,
+}
+
+/// The TFDZ construction rules of the transfer frame data field header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstructionRule {
+    /// Packets spanning multiple frames, the pointer is the first header pointer
+    PacketsSpanning = 0,
+    /// Start of a MAPA_SDU or VCA_SDU, the pointer gives the last valid octet
+    StartOfSdu = 1,
+    /// Continuing a MAPA_SDU or VCA_SDU, the pointer gives the last valid octet
+    ContinuingSdu = 2,
+    /// Octet stream
+    OctetStream = 3,
+    /// Starting segment of a variable length SDU
+    StartingSegment = 4,
+    /// Continuing segment
+    ContinuingSegment = 5,
+    /// Last segment
+    LastSegment = 6,
+    /// No segmentation, the data zone contains one complete SDU
+    NoSegmentation = 7,
+}
+
+impl ConstructionRule {
+    pub fn from_bits(val: u8) -> ConstructionRule {
+        match val & 0x07 {
+            0 => ConstructionRule::PacketsSpanning,
+            1 => ConstructionRule::StartOfSdu,
+            2 => ConstructionRule::ContinuingSdu,
+            3 => ConstructionRule::OctetStream,
+            4 => ConstructionRule::StartingSegment,
+            5 => ConstructionRule::ContinuingSegment,
+            6 => ConstructionRule::LastSegment,
+            _ => ConstructionRule::NoSegmentation,
+        }
+    }
+
+    /// True, if the TFDF header contains the first header / last valid octet pointer
+    pub fn has_pointer(&self) -> bool {
+        matches!(
+            self,
+            ConstructionRule::PacketsSpanning
+                | ConstructionRule::StartOfSdu
+                | ConstructionRule::ContinuingSdu
+        )
+    }
+
+    /// The length of the TFDF header for this rule
+    pub fn header_len(&self) -> usize {
+        if self.has_pointer() {
+            3
+        } else {
+            1
+        }
+    }
+}
+
+/// The transfer frame data field of a USLP frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tfdf<'a> {
+    pub construction_rule: ConstructionRule,
+    pub upid: u8,
+    /// First header pointer or last valid octet pointer, depending on the rule
+    pub pointer: Option<u16>,
+    pub data_zone: &'a [u8],
+}
+
+/// A view on a USLP transfer frame
+#[derive(Debug, Clone, Copy)]
+pub struct USLPFrame<'a> {
+    data: &'a [u8],
+    config: USLPConfig,
+}
+
+impl<'a> USLPFrame<'a> {
+    /// The length of a truncated primary header
+    pub const TRUNCATED_HDR_LEN: usize = 4;
+    /// The length of the primary header without the VC frame count
+    pub const MIN_HDR_LEN: usize = 7;
+    pub const OCF_LEN: usize = 4;
+
+    /// Creates a view on the frame at the start of `data`. The length of the frame is
+    /// taken from the header (or from the config for truncated frames), additional
+    /// bytes after the frame are ignored.
+    pub fn new(data: &'a [u8], config: &USLPConfig) -> Result<USLPFrame<'a>, Error> {
+        if data.len() < Self::TRUNCATED_HDR_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("USLP Frame: frame too short: {} bytes", data.len()),
+            ));
+        }
+
+        let mut frame = USLPFrame {
+            data,
+            config: *config,
+        };
+
+        if frame.version() != USLP_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("USLP Frame: wrong version number: {}", frame.version()),
+            ));
+        }
+
+        let len = if frame.is_truncated() {
+            match config.truncated_frame_len {
+                Some(len) => len,
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "USLP Frame: truncated frame, but no truncated frame length configured",
+                    ))
+                }
+            }
+        } else {
+            if data.len() < Self::MIN_HDR_LEN {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("USLP Frame: frame too short: {} bytes", data.len()),
+                ));
+            }
+            (((data[4] as usize) << 8) | data[5] as usize) + 1
+        };
+
+        if data.len() < len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "USLP Frame: frame too short: {} bytes, frame length is {len}",
+                    data.len()
+                ),
+            ));
+        }
+        frame.data = &data[..len];
+
+        // at least the TFDF header must be present
+        let min_len = frame.header_len() + frame.insert_zone_len() + frame.trailer_len() + 1;
+        if len < min_len {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("USLP Frame: frame too short: {len} bytes, need at least {min_len}"),
+            ));
+        }
+
+        Ok(frame)
+    }
+
+    /// The complete frame
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn version(&self) -> u8 {
+        self.data[0] >> 4
+    }
+
+    pub fn scid(&self) -> u16 {
+        ((self.data[0] as u16 & 0x0F) << 12)
+            | ((self.data[1] as u16) << 4)
+            | (self.data[2] as u16 >> 4)
+    }
+
+    /// False if the SCID is the source, true if it is the destination of the frame
+    pub fn source_dest_id(&self) -> bool {
+        (self.data[2] & 0x08) != 0
+    }
+
+    pub fn vcid(&self) -> u8 {
+        ((self.data[2] & 0x07) << 3) | (self.data[3] >> 5)
+    }
+
+    pub fn map_id(&self) -> u8 {
+        (self.data[3] >> 1) & 0x0F
+    }
+
+    /// The end of frame primary header flag, set for truncated frames
+    pub fn is_truncated(&self) -> bool {
+        (self.data[3] & 0x01) != 0
+    }
+
+    /// The total length of the frame
+    pub fn frame_length(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn bypass_flag(&self) -> bool {
+        !self.is_truncated() && (self.data[6] & 0x80) != 0
+    }
+
+    pub fn protocol_control_flag(&self) -> bool {
+        !self.is_truncated() && (self.data[6] & 0x40) != 0
+    }
+
+    pub fn ocf_flag(&self) -> bool {
+        !self.is_truncated() && (self.data[6] & 0x08) != 0
+    }
+
+    /// The length of the VC frame count in bytes
+    pub fn vc_frame_count_len(&self) -> usize {
+        if self.is_truncated() {
+            0
+        } else {
+            (self.data[6] & 0x07) as usize
+        }
+    }
+
+    /// The VC frame count, None if the frame contains no VC frame count
+    pub fn vc_frame_count(&self) -> Option<u64> {
+        let len = self.vc_frame_count_len();
+        if len == 0 {
+            return None;
+        }
+        let cnt = self.data[Self::MIN_HDR_LEN..Self::MIN_HDR_LEN + len]
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        Some(cnt)
+    }
+
+    fn header_len(&self) -> usize {
+        if self.is_truncated() {
+            Self::TRUNCATED_HDR_LEN
+        } else {
+            Self::MIN_HDR_LEN + self.vc_frame_count_len()
+        }
+    }
+
+    fn insert_zone_len(&self) -> usize {
+        if self.is_truncated() {
+            0
+        } else {
+            self.config.insert_zone_len
+        }
+    }
+
+    fn fecf_type(&self) -> FecfType {
+        if self.is_truncated() {
+            FecfType::None
+        } else {
+            self.config.fecf
+        }
+    }
+
+    fn trailer_len(&self) -> usize {
+        let mut len = self.fecf_type().len();
+        if self.ocf_flag() {
+            len += Self::OCF_LEN;
+        }
+        len
+    }
+
+    pub fn insert_zone(&self) -> Option<&'a [u8]> {
+        let len = self.insert_zone_len();
+        if len > 0 {
+            let start = self.header_len();
+            Some(&self.data[start..start + len])
+        } else {
+            None
+        }
+    }
+
+    /// The complete transfer frame data field including its header
+    pub fn tfdf_bytes(&self) -> &'a [u8] {
+        let start = self.header_len() + self.insert_zone_len();
+        let end = self.data.len() - self.trailer_len();
+        &self.data[start..end]
+    }
+
+    /// The decoded transfer frame data field
+    pub fn tfdf(&self) -> Result<Tfdf<'a>, Error> {
+        let df = self.tfdf_bytes();
+        let rule = ConstructionRule::from_bits(df[0] >> 5);
+        if df.len() < rule.header_len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "USLP Frame: data field too short for TFDF header",
+            ));
+        }
+        let pointer = if rule.has_pointer() {
+            Some(((df[1] as u16) << 8) | df[2] as u16)
+        } else {
+            None
+        };
+        Ok(Tfdf {
+            construction_rule: rule,
+            upid: df[0] & 0x1F,
+            pointer,
+            data_zone: &df[rule.header_len()..],
+        })
+    }
+
+    pub fn ocf(&self) -> Option<u32> {
+        if self.ocf_flag() {
+            let start = self.data.len() - self.fecf_type().len() - Self::OCF_LEN;
+            let mut arr = [0u8; 4];
+            arr.copy_from_slice(&self.data[start..start + Self::OCF_LEN]);
+            Some(u32::from_be_bytes(arr))
+        } else {
+            None
+        }
+    }
+
+    /// The FECF (16 or 32 bit, depending on the config), None if there is no FECF
+    pub fn fecf(&self) -> Option<u32> {
+        let len = self.fecf_type().len();
+        if len == 0 {
+            return None;
+        }
+        let val = self.data[self.data.len() - len..]
+            .iter()
+            .fold(0u32, |acc, b| (acc << 8) | *b as u32);
+        Some(val)
+    }
+
+    /// Calculates the FECF over the frame, None if there is no FECF
+    pub fn calc_fecf(&self) -> Option<u32> {
+        let end = self.data.len() - self.fecf_type().len();
+        match self.fecf_type() {
+            FecfType::None => None,
+            FecfType::Crc16 => Some(calc_crc(&self.data[..end]) as u32),
+            FecfType::Crc32 => Some(calc_crc32(&self.data[..end])),
+        }
+    }
+
+    /// Checks the FECF. Frames without FECF are always valid.
+    pub fn check_fecf(&self) -> bool {
+        self.fecf() == self.calc_fecf()
+    }
+}
+
+/// Creates USLP transfer frames
+#[derive(Debug, Clone, Default)]
+pub struct USLPFrameBuilder {
+    config: USLPConfig,
+    scid: u16,
+    source_dest_id: bool,
+    vcid: u8,
+    map_id: u8,
+    truncated: bool,
+    bypass: bool,
+    protocol_control: bool,
+    vc_frame_count_len: usize,
+    vc_frame_count: u64,
+    insert_zone: Vec<u8>,
+    ocf: Option<u32>,
+}
+
+impl USLPFrameBuilder {
+    pub fn new(scid: u16, vcid: u8, map_id: u8, config: &USLPConfig) -> USLPFrameBuilder {
+        USLPFrameBuilder {
+            config: *config,
+            scid,
+            vcid: vcid & 0x3F,
+            map_id: map_id & 0x0F,
+            insert_zone: vec![0; config.insert_zone_len],
+            ..Default::default()
+        }
+    }
+
+    pub fn source_dest_id(mut self, flag: bool) -> Self {
+        self.source_dest_id = flag;
+        self
+    }
+
+    /// Build truncated frames. Truncated frames have no VC frame count, insert zone,
+    /// OCF or FECF.
+    pub fn truncated(mut self, flag: bool) -> Self {
+        self.truncated = flag;
+        self
+    }
+
+    pub fn bypass(mut self, flag: bool) -> Self {
+        self.bypass = flag;
+        self
+    }
+
+    pub fn protocol_control(mut self, flag: bool) -> Self {
+        self.protocol_control = flag;
+        self
+    }
+
+    /// Set the VC frame count with the given length in bytes (0 to 7)
+    pub fn vc_frame_count(mut self, len: usize, cnt: u64) -> Self {
+        self.vc_frame_count_len = len.min(MAX_VC_FRAME_COUNT_LEN);
+        self.vc_frame_count = cnt;
+        self
+    }
+
+    /// Set the content of the insert zone. It is truncated or padded with 0 to the
+    /// configured insert zone length.
+    pub fn insert_zone(mut self, data: &[u8]) -> Self {
+        let len = self.config.insert_zone_len;
+        self.insert_zone = data.iter().copied().chain(std::iter::repeat(0)).take(len).collect();
+        self
+    }
+
+    pub fn ocf(mut self, ocf: Option<u32>) -> Self {
+        self.ocf = ocf;
+        self
+    }
+
+    /// The length of a frame with the given data zone length and construction rule
+    pub fn frame_length(&self, rule: ConstructionRule, data_zone_len: usize) -> usize {
+        let tfdf_len = rule.header_len() + data_zone_len;
+        if self.truncated {
+            return USLPFrame::TRUNCATED_HDR_LEN + tfdf_len;
+        }
+        let mut len = USLPFrame::MIN_HDR_LEN
+            + self.vc_frame_count_len
+            + self.config.insert_zone_len
+            + tfdf_len
+            + self.config.fecf.len();
+        if self.ocf.is_some() {
+            len += USLPFrame::OCF_LEN;
+        }
+        len
+    }
+
+    /// Builds a frame. The pointer is used for the construction rules which have a
+    /// pointer in the TFDF header and ignored otherwise.
+    pub fn build(
+        &self,
+        rule: ConstructionRule,
+        upid: u8,
+        pointer: u16,
+        data_zone: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let len = self.frame_length(rule, data_zone.len());
+        if len > 65536 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("USLP Frame: frame length {len} exceeds maximum of 65536"),
+            ));
+        }
+
+        let mut frame = Vec::with_capacity(len);
+        let id = ((USLP_VERSION as u32) << 28)
+            | ((self.scid as u32) << 12)
+            | ((self.source_dest_id as u32) << 11)
+            | ((self.vcid as u32) << 5)
+            | ((self.map_id as u32) << 1)
+            | self.truncated as u32;
+        frame.extend_from_slice(&id.to_be_bytes());
+
+        if !self.truncated {
+            frame.extend_from_slice(&((len - 1) as u16).to_be_bytes());
+            let flags = ((self.bypass as u8) << 7)
+                | ((self.protocol_control as u8) << 6)
+                | ((self.ocf.is_some() as u8) << 3)
+                | self.vc_frame_count_len as u8;
+            frame.push(flags);
+            let cnt = self.vc_frame_count.to_be_bytes();
+            frame.extend_from_slice(&cnt[8 - self.vc_frame_count_len..]);
+            frame.extend_from_slice(&self.insert_zone);
+        }
+
+        frame.push(((rule as u8) << 5) | (upid & 0x1F));
+        if rule.has_pointer() {
+            frame.extend_from_slice(&pointer.to_be_bytes());
+        }
+        frame.extend_from_slice(data_zone);
+
+        if !self.truncated {
+            if let Some(ocf) = self.ocf {
+                frame.extend_from_slice(&ocf.to_be_bytes());
+            }
+            match self.config.fecf {
+                FecfType::None => {}
+                FecfType::Crc16 => {
+                    let crc = calc_crc(&frame);
+                    frame.extend_from_slice(&crc.to_be_bytes());
+                }
+                FecfType::Crc32 => {
+                    let crc = calc_crc32(&frame);
+                    frame.extend_from_slice(&crc.to_be_bytes());
+                }
+            }
+        }
+        Ok(frame)
+    }
+}
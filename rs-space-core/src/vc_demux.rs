@@ -0,0 +1,650 @@
+//! Virtual channel demultiplexing and extraction of space packets from TM and AOS
+//! transfer frames.
+//!
+//! The [VCDemultiplexer] keeps a [PacketExtractor] per virtual channel. The extractors
+//! follow the first header pointers, join packets spanning several frames, drop idle
+//! packets and check the VC frame counters. After a gap (or an inconsistent first
+//! header pointer), the partially received packet is discarded and the extractor
+//! resynchronises on the next first header pointer. All discarded data is reported.
+//! Besides space packets, encapsulation packets (version 7) are extracted.
+use std::collections::BTreeMap;
+
+use crate::aos_frame::{AOSFrame, MPDU_IDLE};
+use crate::ccsds_packet::{CcsdsPacket, ErrorControlPolicy, FastCcsdsPacket};
+use crate::encap_packet::{self, EncapsulationPacket, ENCAP_VERSION};
+use crate::seq_monitor::{SequenceEvent, SequenceMonitor};
+use crate::tm_frame::{TMFrame, FHP_IDLE, FHP_NO_PACKET_START, IDLE_VCID};
+
+/// The APID of idle packets
+pub const IDLE_APID: u16 = 0x7FF;
+
+/// The common view on TM and AOS frames needed for packet extraction
+pub trait PacketFrame {
+    fn vcid(&self) -> u8;
+    /// The VC frame count
+    fn vc_frame_count(&self) -> u32;
+    /// The modulus of the VC frame count
+    fn vc_frame_count_modulus(&self) -> u32;
+    /// True, if the frame is on the idle virtual channel and is dropped completely.
+    /// Frames with only idle data on other virtual channels are counted.
+    fn is_idle_frame(&self) -> bool;
+    /// The first header pointer, None if the frame carries no packets
+    fn first_header_pointer(&self) -> Option<u16>;
+    /// The part of the data field containing the packets
+    fn packet_zone(&self) -> Option<&[u8]>;
+}
+
+impl PacketFrame for TMFrame<'_> {
+    fn vcid(&self) -> u8 {
+        TMFrame::vcid(self)
+    }
+
+    fn vc_frame_count(&self) -> u32 {
+        TMFrame::vc_frame_count(self) as u32
+    }
+
+    fn vc_frame_count_modulus(&self) -> u32 {
+        256
+    }
+
+    fn is_idle_frame(&self) -> bool {
+        TMFrame::vcid(self) == IDLE_VCID
+    }
+
+    fn first_header_pointer(&self) -> Option<u16> {
+        if self.sync_flag() {
+            None
+        } else {
+            Some(TMFrame::first_header_pointer(self))
+        }
+    }
+
+    fn packet_zone(&self) -> Option<&[u8]> {
+        if self.sync_flag() {
+            None
+        } else {
+            Some(self.data_field())
+        }
+    }
+}
+
+impl PacketFrame for AOSFrame<'_> {
+    fn vcid(&self) -> u8 {
+        AOSFrame::vcid(self)
+    }
+
+    fn vc_frame_count(&self) -> u32 {
+        self.extended_vc_frame_count()
+    }
+
+    fn vc_frame_count_modulus(&self) -> u32 {
+        if self.vc_frame_count_usage_flag() {
+            1 << 28
+        } else {
+            1 << 24
+        }
+    }
+
+    fn is_idle_frame(&self) -> bool {
+        self.is_idle()
+    }
+
+    fn first_header_pointer(&self) -> Option<u16> {
+        AOSFrame::first_header_pointer(self)
+    }
+
+    fn packet_zone(&self) -> Option<&[u8]> {
+        AOSFrame::packet_zone(self)
+    }
+}
+
+/// The results of the packet extraction
+#[derive(Debug, Clone)]
+pub enum ExtractorEvent {
+    /// A complete packet
+    Packet { vcid: u8, packet: CcsdsPacket },
+    /// A complete encapsulation packet. Idle packets are dropped.
+    Encapsulation {
+        vcid: u8,
+        packet: EncapsulationPacket,
+    },
+    /// The VC frame count is not the expected one, `lost_frames` frames are missing
+    FrameGap {
+        vcid: u8,
+        expected: u32,
+        received: u32,
+        lost_frames: u32,
+    },
+    /// Data which could not be assigned to a complete packet has been discarded
+    DataLost { vcid: u8, bytes: usize },
+    /// A packet has been discarded, because its error control field is wrong
+    ErrorControlFailed { vcid: u8, apid: u16 },
+    /// The sequence count of the preceding packet is not the expected one. Only
+    /// reported, if the [VCDemultiplexer] monitors the sequence counts.
+    Sequence { vcid: u8, event: SequenceEvent },
+}
+
+/// Extracts packets from the frames of one virtual channel
+#[derive(Debug, Clone)]
+pub struct PacketExtractor {
+    vcid: u8,
+    buffer: Vec<u8>,
+    in_sync: bool,
+    last_count: Option<u32>,
+    error_control: ErrorControlPolicy,
+}
+
+impl PacketExtractor {
+    pub fn new(vcid: u8) -> PacketExtractor {
+        PacketExtractor {
+            vcid,
+            buffer: Vec::new(),
+            in_sync: false,
+            last_count: None,
+            error_control: ErrorControlPolicy::default(),
+        }
+    }
+
+    /// The error control of the packets, which is checked and removed from the extracted
+    /// packets. Packets with a wrong error control field are reported and dropped.
+    /// Defaults to a CRC for all APIDs.
+    pub fn error_control(mut self, policy: ErrorControlPolicy) -> Self {
+        self.error_control = policy;
+        self
+    }
+
+    /// Reset the extractor, e.g. after a loss of frame lock. The partial packet is
+    /// discarded and reported.
+    pub fn reset(&mut self, events: &mut Vec<ExtractorEvent>) {
+        self.lose_sync(events);
+        self.last_count = None;
+    }
+
+    fn lose_sync(&mut self, events: &mut Vec<ExtractorEvent>) {
+        self.data_lost(self.buffer.len(), events);
+        self.buffer.clear();
+        self.in_sync = false;
+    }
+
+    fn data_lost(&self, bytes: usize, events: &mut Vec<ExtractorEvent>) {
+        if bytes > 0 {
+            events.push(ExtractorEvent::DataLost {
+                vcid: self.vcid,
+                bytes,
+            });
+        }
+    }
+
+    /// Process the next frame of the virtual channel, the results are appended to
+    /// `events`
+    pub fn process<F: PacketFrame>(&mut self, frame: &F, events: &mut Vec<ExtractorEvent>) {
+        self.check_count(frame, events);
+
+        let (Some(fhp), Some(zone)) = (frame.first_header_pointer(), frame.packet_zone()) else {
+            return;
+        };
+        // frames with only idle data carry no packet data
+        if fhp == FHP_IDLE || fhp == MPDU_IDLE {
+            return;
+        }
+
+        if fhp != FHP_NO_PACKET_START && fhp as usize >= zone.len() {
+            // invalid pointer, nothing in this frame can be trusted
+            self.lose_sync(events);
+            self.data_lost(zone.len(), events);
+            return;
+        }
+
+        if !self.in_sync {
+            if fhp == FHP_NO_PACKET_START {
+                self.data_lost(zone.len(), events);
+                return;
+            }
+            self.data_lost(fhp as usize, events);
+            self.in_sync = true;
+            self.extract(&zone[fhp as usize..], events);
+            return;
+        }
+
+        if fhp == FHP_NO_PACKET_START {
+            if self.buffer.is_empty() {
+                // the last packet ended with the previous frame, so a new one must start
+                self.lose_sync(events);
+                self.data_lost(zone.len(), events);
+                return;
+            }
+            // the whole zone continues the current packet
+            self.buffer.extend_from_slice(zone);
+            match self.pending_length() {
+                Some(len) if len < self.buffer.len() => {
+                    // the packet ends within the frame, but no new packet starts
+                    self.lose_sync(events);
+                }
+                Some(len) if len == self.buffer.len() => {
+                    let data = std::mem::take(&mut self.buffer);
+                    self.extract(&data, events);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        // the bytes before the first header pointer complete the current packet
+        let fhp = fhp as usize;
+        if !self.buffer.is_empty() || fhp > 0 {
+            self.buffer.extend_from_slice(&zone[..fhp]);
+            match self.pending_length() {
+                Some(len) if len == self.buffer.len() => {
+                    let data = std::mem::take(&mut self.buffer);
+                    self.extract(&data, events);
+                }
+                _ => {
+                    // the first header pointer does not match the packet length
+                    self.data_lost(self.buffer.len(), events);
+                    self.buffer.clear();
+                }
+            }
+        }
+        self.extract(&zone[fhp..], events);
+    }
+
+    fn check_count<F: PacketFrame>(&mut self, frame: &F, events: &mut Vec<ExtractorEvent>) {
+        let count = frame.vc_frame_count();
+        let modulus = frame.vc_frame_count_modulus();
+
+        if let Some(last) = self.last_count {
+            let expected = (last + 1) % modulus;
+            if count != expected {
+                let lost_frames = (count + modulus - expected) % modulus;
+                events.push(ExtractorEvent::FrameGap {
+                    vcid: self.vcid,
+                    expected,
+                    received: count,
+                    lost_frames,
+                });
+                self.lose_sync(events);
+            }
+        }
+        self.last_count = Some(count);
+    }
+
+    /// The total length of the packet in the buffer, if the header is complete
+    fn pending_length(&self) -> Option<usize> {
+        if is_encapsulation(&self.buffer) {
+            return encap_packet::packet_length(&self.buffer);
+        }
+        if self.buffer.len() < FastCcsdsPacket::HDR_LEN {
+            return None;
+        }
+        let len = ((self.buffer[4] as usize) << 8) | self.buffer[5] as usize;
+        Some(len + 1 + FastCcsdsPacket::HDR_LEN)
+    }
+
+    /// Extract all complete packets from the data, which must start with a packet
+    /// header. An incomplete packet at the end is kept in the buffer.
+    fn extract(&mut self, mut data: &[u8], events: &mut Vec<ExtractorEvent>) {
+        while !data.is_empty() {
+            if is_encapsulation(data) {
+                match EncapsulationPacket::from_slice(data) {
+                    Ok(pkt) => {
+                        data = &data[pkt.total_length()..];
+                        if !pkt.is_idle() {
+                            events.push(ExtractorEvent::Encapsulation {
+                                vcid: self.vcid,
+                                packet: pkt,
+                            });
+                        }
+                    }
+                    Err(_) => {
+                        self.buffer.extend_from_slice(data);
+                        return;
+                    }
+                }
+                continue;
+            }
+            match FastCcsdsPacket::from_slice(data) {
+                Ok(pkt) => {
+                    data = &data[pkt.total_length()..];
+                    let apid = pkt.apid();
+                    if apid == IDLE_APID {
+                        continue;
+                    }
+                    // too short to hold the error control field
+                    let ec = self.error_control.for_apid(apid);
+                    if pkt.data.len() < ec.field_length() {
+                        self.data_lost(pkt.total_length(), events);
+                        continue;
+                    }
+                    if !pkt.check_error_control(ec) {
+                        events.push(ExtractorEvent::ErrorControlFailed {
+                            vcid: self.vcid,
+                            apid,
+                        });
+                        continue;
+                    }
+                    events.push(ExtractorEvent::Packet {
+                        vcid: self.vcid,
+                        packet: CcsdsPacket::from_fast_ccsds_pkt_with(pkt, ec),
+                    });
+                }
+                Err(_) => {
+                    self.buffer.extend_from_slice(data);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// True, if the data starts with an encapsulation packet
+fn is_encapsulation(data: &[u8]) -> bool {
+    data.first().is_some_and(|b| b >> 5 == ENCAP_VERSION)
+}
+
+/// Distributes frames to one [PacketExtractor] per virtual channel
+#[derive(Debug, Clone, Default)]
+pub struct VCDemultiplexer {
+    extractors: BTreeMap<u8, PacketExtractor>,
+    error_control: ErrorControlPolicy,
+    monitor: Option<SequenceMonitor>,
+}
+
+impl VCDemultiplexer {
+    pub fn new() -> VCDemultiplexer {
+        VCDemultiplexer::default()
+    }
+
+    /// The error control of the packets on all virtual channels
+    pub fn error_control(mut self, policy: ErrorControlPolicy) -> Self {
+        self.error_control = policy;
+        self
+    }
+
+    /// Check the sequence counts of the extracted packets per APID over all virtual
+    /// channels
+    pub fn monitor_sequence_counts(mut self, monitor: SequenceMonitor) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// The sequence count monitor with the statistics, if enabled
+    pub fn sequence_monitor(&self) -> Option<&SequenceMonitor> {
+        self.monitor.as_ref()
+    }
+
+    /// Process a frame and return the extracted packets, gaps and data losses.
+    /// Frames on the idle virtual channel are dropped.
+    pub fn process<F: PacketFrame>(&mut self, frame: &F) -> Vec<ExtractorEvent> {
+        let mut events = Vec::new();
+        if frame.is_idle_frame() {
+            return events;
+        }
+        let vcid = frame.vcid();
+        self.extractors
+            .entry(vcid)
+            .or_insert_with(|| PacketExtractor::new(vcid).error_control(self.error_control.clone()))
+            .process(frame, &mut events);
+
+        if let Some(monitor) = self.monitor.as_mut() {
+            let mut checked = Vec::with_capacity(events.len());
+            for event in events {
+                let seq = match &event {
+                    ExtractorEvent::Packet { packet, .. } => {
+                        monitor.check(packet.pkt_id.apid.raw(), packet.ssc.ssc())
+                    }
+                    _ => None,
+                };
+                checked.push(event);
+                if let Some(event) = seq {
+                    checked.push(ExtractorEvent::Sequence { vcid, event });
+                }
+            }
+            events = checked;
+        }
+        events
+    }
+
+    /// Reset all extractors, e.g. after a loss of frame lock
+    pub fn reset(&mut self) -> Vec<ExtractorEvent> {
+        let mut events = Vec::new();
+        for extractor in self.extractors.values_mut() {
+            extractor.reset(&mut events);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pus_types::{CcsdsType, HexBytes, PktID, APID, SSC};
+    use crate::tm_frame::TMFrameBuilder;
+    use crate::vc_mux::{FrameFormat, VCGenerator};
+
+    const FORMAT: FrameFormat = FrameFormat::TM {
+        scid: 42,
+        ocf: false,
+        fecf: false,
+    };
+    // a packet zone of 58 bytes
+    const FRAME_LEN: usize = 64;
+
+    fn packet(apid: u16, ssc: u16, len: usize) -> CcsdsPacket {
+        CcsdsPacket {
+            pkt_id: PktID::new(0, CcsdsType::TM, false, APID::new(apid)),
+            ssc: SSC::new_unseg(ssc),
+            data: HexBytes((0..len).map(|i| (i + ssc as usize) as u8).collect()),
+        }
+    }
+
+    /// The frames for the packets on VC 1
+    fn frames(packets: &[CcsdsPacket]) -> Vec<Vec<u8>> {
+        let mut gen = VCGenerator::new(FORMAT, 1, FRAME_LEN).unwrap();
+        for pkt in packets {
+            gen.push(pkt.clone()).unwrap();
+        }
+        std::iter::from_fn(|| gen.next_frame(0, 0)).collect()
+    }
+
+    /// The frames for the packets on VC 1, starting with the given VC frame count
+    fn frames_from(count: u8, packets: &[CcsdsPacket]) -> Vec<Vec<u8>> {
+        let mut frames = frames(packets);
+        for (i, frame) in frames.iter_mut().enumerate() {
+            frame[3] = count.wrapping_add(i as u8);
+        }
+        frames
+    }
+
+    fn process(demux: &mut VCDemultiplexer, frames: &[Vec<u8>]) -> Vec<ExtractorEvent> {
+        frames
+            .iter()
+            .flat_map(|f| demux.process(&TMFrame::new(f, false).unwrap()))
+            .collect()
+    }
+
+    fn assert_packet(event: &ExtractorEvent, expected: &CcsdsPacket) {
+        match event {
+            ExtractorEvent::Packet { vcid: 1, packet } => {
+                assert_eq!(packet.pkt_id.apid.raw(), expected.pkt_id.apid.raw());
+                assert_eq!(packet.ssc.ssc(), expected.ssc.ssc());
+                assert_eq!(packet.data.0, expected.data.0);
+            }
+            _ => panic!("expected packet {}, got {event:?}", expected.ssc.ssc()),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        // small packets, packets spanning two and several frames
+        let packets = [
+            packet(10, 0, 10),
+            packet(10, 1, 80),
+            packet(11, 2, 3),
+            packet(10, 3, 200),
+            packet(11, 4, 1),
+        ];
+        let frames = frames(&packets);
+        assert!(frames.len() > 5);
+
+        let events = process(&mut VCDemultiplexer::new(), &frames);
+        assert_eq!(events.len(), packets.len());
+        for (event, pkt) in events.iter().zip(&packets) {
+            assert_packet(event, pkt);
+        }
+    }
+
+    #[test]
+    fn dropped_frame() {
+        // packets of 48 bytes: frame 0 contains packet 0 and the start of packet 1,
+        // frame 1 the rest of packet 1 and the start of packet 2
+        let packets: Vec<_> = (0..6).map(|i| packet(10, i, 40)).collect();
+        let mut frames = frames(&packets);
+        frames.remove(1);
+
+        let events = process(&mut VCDemultiplexer::new(), &frames);
+        assert_eq!(events.len(), 7);
+        assert_packet(&events[0], &packets[0]);
+        assert!(matches!(
+            events[1],
+            ExtractorEvent::FrameGap {
+                vcid: 1,
+                expected: 1,
+                received: 2,
+                lost_frames: 1,
+            }
+        ));
+        // the start of packet 1 and the end of packet 2 before the first header
+        // pointer of frame 2
+        assert!(matches!(
+            events[2],
+            ExtractorEvent::DataLost { vcid: 1, bytes: 10 }
+        ));
+        assert!(matches!(
+            events[3],
+            ExtractorEvent::DataLost { vcid: 1, bytes: 28 }
+        ));
+        // resynchronised on the first header pointer
+        for (event, pkt) in events[4..].iter().zip(&packets[3..]) {
+            assert_packet(event, pkt);
+        }
+    }
+
+    #[test]
+    fn idle_data_frame_on_data_vc() {
+        // a packet spanning two frames with a frame with only idle data in between
+        let pkt = packet(10, 0, 80);
+        let mut frames = frames(std::slice::from_ref(&pkt));
+        assert_eq!(frames.len(), 2);
+        frames[1][3] = 2;
+        let idle = TMFrameBuilder::new(42, 1)
+            .vc_frame_count(1)
+            .first_header_pointer(FHP_IDLE)
+            .build(&[0x55; FRAME_LEN - TMFrame::PRIMARY_HDR_LEN])
+            .unwrap();
+        frames.insert(1, idle);
+
+        let events = process(&mut VCDemultiplexer::new(), &frames);
+        assert_eq!(events.len(), 1);
+        assert_packet(&events[0], &pkt);
+    }
+
+    #[test]
+    fn idle_vc_is_not_counted() {
+        let packets = [packet(10, 0, 10), packet(10, 1, 10)];
+        let mut frames = frames(&packets[..1]);
+        frames.extend(frames_from(1, &packets[1..]));
+        let idle = TMFrameBuilder::new(42, IDLE_VCID)
+            .first_header_pointer(FHP_IDLE)
+            .build(&[0x55; FRAME_LEN - TMFrame::PRIMARY_HDR_LEN])
+            .unwrap();
+        frames.insert(1, idle);
+
+        let events = process(&mut VCDemultiplexer::new(), &frames);
+        assert_eq!(events.len(), 2);
+        assert_packet(&events[0], &packets[0]);
+        assert_packet(&events[1], &packets[1]);
+    }
+
+    #[test]
+    fn continuation_after_complete_packet() {
+        // packet 0 ends exactly at the end of frame 0, the following frame claims
+        // to continue a packet
+        let zone = FRAME_LEN - TMFrame::PRIMARY_HDR_LEN;
+        let first = packet(10, 0, zone - FastCcsdsPacket::HDR_LEN - 2);
+        let mut frames = frames(std::slice::from_ref(&first));
+        assert_eq!(frames.len(), 1);
+        let continuation = TMFrameBuilder::new(42, 1)
+            .vc_frame_count(1)
+            .first_header_pointer(FHP_NO_PACKET_START)
+            .build(&vec![0xAA; zone])
+            .unwrap();
+        frames.push(continuation);
+        let next = packet(10, 1, 10);
+        frames.extend(frames_from(2, std::slice::from_ref(&next)));
+
+        let events = process(&mut VCDemultiplexer::new(), &frames);
+        assert_eq!(events.len(), 3);
+        assert_packet(&events[0], &first);
+        assert!(matches!(
+            events[1],
+            ExtractorEvent::DataLost { vcid: 1, bytes } if bytes == zone
+        ));
+        assert_packet(&events[2], &next);
+    }
+
+    #[test]
+    fn invalid_first_header_pointers() {
+        let packets = [packet(10, 0, 80), packet(10, 1, 10)];
+        let mut frames = frames(&packets);
+        assert_eq!(frames.len(), 2);
+
+        // the pointer does not match the end of the spanning packet
+        let mut wrong = frames.clone();
+        wrong[1][5] += 1;
+        let events = process(&mut VCDemultiplexer::new(), &wrong);
+        assert!(matches!(
+            events[0],
+            ExtractorEvent::DataLost { vcid: 1, .. }
+        ));
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, ExtractorEvent::Packet { .. })));
+
+        // the pointer is beyond the packet zone, the start of the packet from
+        // frame 0 and the whole zone of frame 1 are lost
+        frames[1][4] = 0x07;
+        frames[1][5] = 0xF0;
+        let zone = FRAME_LEN - TMFrame::PRIMARY_HDR_LEN;
+        let events = process(&mut VCDemultiplexer::new(), &frames);
+        assert_eq!(events.len(), 2);
+        for event in events {
+            assert!(matches!(
+                event,
+                ExtractorEvent::DataLost { vcid: 1, bytes } if bytes == zone
+            ));
+        }
+    }
+
+    #[test]
+    fn encapsulation_and_space_packets() {
+        let encap = EncapsulationPacket::new(encap_packet::PID_IPE, (0..100).collect());
+        let packets = [packet(10, 0, 30), packet(10, 1, 20)];
+        let mut gen = VCGenerator::new(FORMAT, 1, FRAME_LEN).unwrap();
+        gen.push(packets[0].clone()).unwrap();
+        gen.push_encoded(encap.to_vec().unwrap());
+        gen.push(packets[1].clone()).unwrap();
+        let frames: Vec<_> = std::iter::from_fn(|| gen.next_frame(0, 0)).collect();
+
+        let events = process(&mut VCDemultiplexer::new(), &frames);
+        assert_eq!(events.len(), 3);
+        assert_packet(&events[0], &packets[0]);
+        match &events[1] {
+            ExtractorEvent::Encapsulation { vcid: 1, packet } => {
+                assert_eq!(packet.protocol_id, encap_packet::PID_IPE);
+                assert_eq!(packet.data.0, encap.data.0);
+            }
+            event => panic!("expected encapsulation packet, got {event:?}"),
+        }
+        assert_packet(&events[2], &packets[1]);
+    }
+}
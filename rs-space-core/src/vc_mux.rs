@@ -0,0 +1,418 @@
+//! Packing of space packets into TM and AOS transfer frames.
+//!
+//! A [VCGenerator] packs the packets of one virtual channel into fixed length frames,
+//! setting the first header pointer and the VC frame count. Frames which are not
+//! completely filled are padded with idle packets. The [MCMultiplexer] schedules
+//! several virtual channels by priority and emits idle frames if no data is queued.
+//! Optionally, it randomizes the frames it emits.
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+
+use crate::aos_frame::{AOSConfig, AOSFrame, AOSFrameBuilder, AOS_IDLE_VCID};
+use crate::ccsds_packet::{CcsdsPacket, ErrorControlPolicy, FastCcsdsPacket};
+use crate::clcw::Clcw;
+use crate::randomizer::Randomizer;
+use crate::tm_frame::{TMFrameBuilder, FHP_IDLE, FHP_NO_PACKET_START, IDLE_VCID};
+use crate::vc_demux::IDLE_APID;
+
+/// The fill pattern used for idle packets and idle frames
+pub const IDLE_FILL: u8 = 0x55;
+
+/// The frame format to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    TM { scid: u16, ocf: bool, fecf: bool },
+    AOS { scid: u8, config: AOSConfig },
+}
+
+impl FrameFormat {
+    /// The length of the packet zone in a frame with the given total length
+    pub fn packet_zone_length(&self, frame_len: usize) -> Option<usize> {
+        match self {
+            FrameFormat::TM { scid, ocf, fecf } => {
+                tm_builder(*scid, 0, ocf.then_some(0), *fecf).data_field_length(frame_len)
+            }
+            FrameFormat::AOS { scid, config } => AOSFrameBuilder::new(*scid, 0, config)
+                .data_field_length(frame_len)
+                .and_then(|l| l.checked_sub(AOSFrame::MPDU_HDR_LEN)),
+        }
+    }
+
+    /// The modulus of the VC frame count
+    pub fn vc_frame_count_modulus(&self) -> u32 {
+        match self {
+            FrameFormat::TM { .. } => 256,
+            FrameFormat::AOS { .. } => 1 << 24,
+        }
+    }
+
+    /// The largest allowed VCID, the highest one is reserved for idle frames
+    pub fn max_vcid(&self) -> u8 {
+        match self {
+            FrameFormat::TM { .. } => IDLE_VCID - 1,
+            FrameFormat::AOS { .. } => AOS_IDLE_VCID - 1,
+        }
+    }
+
+    /// Builds a frame with the packet zone. For TM frames, the MC frame count is
+    /// used, for AOS frames it is ignored.
+    fn build(
+        &self,
+        vcid: u8,
+        mc_frame_count: u8,
+        vc_frame_count: u32,
+        first_header_pointer: u16,
+        ocf: u32,
+        packet_zone: &[u8],
+    ) -> Vec<u8> {
+        match self {
+            FrameFormat::TM {
+                scid,
+                ocf: has_ocf,
+                fecf,
+            } => tm_builder(*scid, vcid, has_ocf.then_some(ocf), *fecf)
+                .mc_frame_count(mc_frame_count)
+                .vc_frame_count(vc_frame_count as u8)
+                .first_header_pointer(first_header_pointer)
+                .build(packet_zone)
+                // building only fails for invalid secondary headers, which are not used
+                .expect("TM frame without secondary header"),
+            FrameFormat::AOS { scid, config } => AOSFrameBuilder::new(*scid, vcid, config)
+                .vc_frame_count(vc_frame_count)
+                .ocf(ocf)
+                .build_m_pdu(first_header_pointer, packet_zone),
+        }
+    }
+
+    /// Builds an idle frame
+    fn build_idle(
+        &self,
+        mc_frame_count: u8,
+        vc_frame_count: u32,
+        ocf: u32,
+        frame_len: usize,
+    ) -> Vec<u8> {
+        match self {
+            FrameFormat::TM { .. } => {
+                let len = self.packet_zone_length(frame_len).unwrap_or(0);
+                self.build(
+                    IDLE_VCID,
+                    mc_frame_count,
+                    vc_frame_count,
+                    FHP_IDLE,
+                    ocf,
+                    &vec![IDLE_FILL; len],
+                )
+            }
+            FrameFormat::AOS { scid, config } => {
+                let builder = AOSFrameBuilder::new(*scid, AOS_IDLE_VCID, config)
+                    .vc_frame_count(vc_frame_count)
+                    .ocf(ocf);
+                let len = builder.data_field_length(frame_len).unwrap_or(0);
+                builder.build(&vec![IDLE_FILL; len])
+            }
+        }
+    }
+}
+
+fn tm_builder(scid: u16, vcid: u8, ocf: Option<u32>, fecf: bool) -> TMFrameBuilder {
+    let builder = TMFrameBuilder::new(scid, vcid).fecf(fecf);
+    match ocf {
+        Some(ocf) => builder.ocf(ocf),
+        None => builder,
+    }
+}
+
+/// Packs the packets of one virtual channel into frames
+#[derive(Debug, Clone)]
+pub struct VCGenerator {
+    format: FrameFormat,
+    vcid: u8,
+    zone_len: usize,
+    vc_frame_count: u32,
+    // encoded packets, flagged true for user data and false for idle packets
+    queue: VecDeque<(Vec<u8>, bool)>,
+    // the number of bytes of the first packet in the queue already sent
+    offset: usize,
+    queued_packets: usize,
+    idle_ssc: u16,
+    error_control: ErrorControlPolicy,
+}
+
+impl VCGenerator {
+    /// The minimum length of an idle packet
+    const MIN_IDLE_LEN: usize = FastCcsdsPacket::HDR_LEN + 1;
+
+    pub fn new(format: FrameFormat, vcid: u8, frame_len: usize) -> Result<VCGenerator, Error> {
+        if vcid > format.max_vcid() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("VC Generator: invalid VCID {vcid}"),
+            ));
+        }
+        let zone_len = match format.packet_zone_length(frame_len) {
+            Some(len) if len > 0 && len < FHP_IDLE as usize => len,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("VC Generator: invalid frame length {frame_len}"),
+                ))
+            }
+        };
+
+        Ok(VCGenerator {
+            format,
+            vcid,
+            zone_len,
+            vc_frame_count: 0,
+            queue: VecDeque::new(),
+            offset: 0,
+            queued_packets: 0,
+            idle_ssc: 0,
+            error_control: ErrorControlPolicy::default(),
+        })
+    }
+
+    /// The error control appended to the packets. Defaults to a CRC for all APIDs.
+    pub fn error_control(mut self, policy: ErrorControlPolicy) -> Self {
+        self.error_control = policy;
+        self
+    }
+
+    pub fn vcid(&self) -> u8 {
+        self.vcid
+    }
+
+    pub fn vc_frame_count(&self) -> u32 {
+        self.vc_frame_count
+    }
+
+    /// The number of user packets which are not yet completely sent
+    pub fn queued_packets(&self) -> usize {
+        self.queued_packets
+    }
+
+    /// True, if user packets are waiting to be sent
+    pub fn has_data(&self) -> bool {
+        self.queued_packets > 0
+    }
+
+    /// Queue a packet for sending. The error control of the APID is appended to the
+    /// packet data.
+    pub fn push(&mut self, pkt: CcsdsPacket) -> Result<(), Error> {
+        let ec = self.error_control.for_apid(pkt.pkt_id.apid.raw());
+        self.push_encoded(pkt.to_fast_ccsds_pkt_with(ec)?.to_vec());
+        Ok(())
+    }
+
+    /// Queue an already encoded packet for sending
+    pub fn push_encoded(&mut self, pkt: Vec<u8>) {
+        self.queue.push_back((pkt, true));
+        self.queued_packets += 1;
+    }
+
+    fn push_idle(&mut self, len: usize) {
+        let len = len.max(Self::MIN_IDLE_LEN);
+        let mut pkt = Vec::with_capacity(len);
+        // version 0, TM, no secondary header, unsegmented
+        pkt.extend_from_slice(&IDLE_APID.to_be_bytes());
+        pkt.extend_from_slice(&(0xC000 | self.idle_ssc).to_be_bytes());
+        pkt.extend_from_slice(&((len - FastCcsdsPacket::HDR_LEN - 1) as u16).to_be_bytes());
+        pkt.resize(len, IDLE_FILL);
+
+        self.idle_ssc = (self.idle_ssc + 1) & 0x3FFF;
+        self.queue.push_back((pkt, false));
+    }
+
+    /// Creates the next frame, if user packets are queued. Remaining space in the
+    /// frame is filled with idle packets. For TM frames, the MC frame count is
+    /// used, for AOS frames it is ignored. The OCF is only used if the format
+    /// contains one.
+    pub fn next_frame(&mut self, mc_frame_count: u8, ocf: u32) -> Option<Vec<u8>> {
+        if !self.has_data() {
+            return None;
+        }
+
+        let mut zone = Vec::with_capacity(self.zone_len);
+        let mut fhp = None;
+        while zone.len() < self.zone_len {
+            if self.queue.is_empty() {
+                self.push_idle(self.zone_len - zone.len());
+            }
+            if self.offset == 0 && fhp.is_none() {
+                fhp = Some(zone.len() as u16);
+            }
+
+            let (pkt, user) = &self.queue[0];
+            let n = (pkt.len() - self.offset).min(self.zone_len - zone.len());
+            zone.extend_from_slice(&pkt[self.offset..self.offset + n]);
+            self.offset += n;
+
+            if self.offset == pkt.len() {
+                if *user {
+                    self.queued_packets -= 1;
+                }
+                self.queue.pop_front();
+                self.offset = 0;
+            }
+        }
+
+        let frame = self.format.build(
+            self.vcid,
+            mc_frame_count,
+            self.vc_frame_count,
+            fhp.unwrap_or(FHP_NO_PACKET_START),
+            ocf,
+            &zone,
+        );
+        self.vc_frame_count = (self.vc_frame_count + 1) % self.format.vc_frame_count_modulus();
+        Some(frame)
+    }
+}
+
+/// Multiplexes several virtual channels into one master channel
+#[derive(Debug, Clone)]
+pub struct MCMultiplexer {
+    format: FrameFormat,
+    frame_len: usize,
+    // the virtual channels with their priority
+    vcs: Vec<(VCGenerator, u8)>,
+    last_served: usize,
+    mc_frame_count: u8,
+    idle_frame_count: u32,
+    ocf: u32,
+    randomizer: Option<Randomizer>,
+    error_control: ErrorControlPolicy,
+}
+
+impl MCMultiplexer {
+    pub fn new(format: FrameFormat, frame_len: usize) -> MCMultiplexer {
+        MCMultiplexer {
+            format,
+            frame_len,
+            vcs: Vec::new(),
+            last_served: 0,
+            mc_frame_count: 0,
+            idle_frame_count: 0,
+            ocf: 0,
+            randomizer: None,
+            error_control: ErrorControlPolicy::default(),
+        }
+    }
+
+    /// Add a virtual channel. VCs with a higher priority value are served first,
+    /// VCs with the same priority are served round robin.
+    pub fn add_vc(&mut self, vcid: u8, priority: u8) -> Result<(), Error> {
+        if self.vcs.iter().any(|(vc, _)| vc.vcid() == vcid) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("MC Multiplexer: VCID {vcid} already added"),
+            ));
+        }
+        let vc = VCGenerator::new(self.format, vcid, self.frame_len)?
+            .error_control(self.error_control.clone());
+        self.vcs.push((vc, priority));
+        Ok(())
+    }
+
+    /// Set the OCF used for all following frames, e.g. the CLCW
+    pub fn set_ocf(&mut self, ocf: u32) {
+        self.ocf = ocf;
+    }
+
+    /// Set the CLCW reported in the OCF of all following frames
+    pub fn set_clcw(&mut self, clcw: &Clcw) {
+        self.ocf = clcw.to_ocf();
+    }
+
+    /// Set the randomizer applied to all following frames, None to switch
+    /// randomization off
+    pub fn set_randomizer(&mut self, randomizer: Option<Randomizer>) {
+        self.randomizer = randomizer;
+    }
+
+    /// Set the error control appended to the packets of all virtual channels
+    pub fn set_error_control(&mut self, policy: ErrorControlPolicy) {
+        for (vc, _) in self.vcs.iter_mut() {
+            vc.error_control = policy.clone();
+        }
+        self.error_control = policy;
+    }
+
+    pub fn mc_frame_count(&self) -> u8 {
+        self.mc_frame_count
+    }
+
+    /// Queue a packet on the given virtual channel
+    pub fn push(&mut self, vcid: u8, pkt: CcsdsPacket) -> Result<(), Error> {
+        self.vc_mut(vcid)?.push(pkt)
+    }
+
+    /// Queue an already encoded packet on the given virtual channel
+    pub fn push_encoded(&mut self, vcid: u8, pkt: Vec<u8>) -> Result<(), Error> {
+        self.vc_mut(vcid)?.push_encoded(pkt);
+        Ok(())
+    }
+
+    fn vc_mut(&mut self, vcid: u8) -> Result<&mut VCGenerator, Error> {
+        self.vcs
+            .iter_mut()
+            .find(|(vc, _)| vc.vcid() == vcid)
+            .map(|(vc, _)| vc)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("MC Multiplexer: unknown VCID {vcid}"),
+                )
+            })
+    }
+
+    /// True, if any virtual channel has queued packets
+    pub fn has_data(&self) -> bool {
+        self.vcs.iter().any(|(vc, _)| vc.has_data())
+    }
+
+    /// Returns the next frame of the master channel. This is a frame of the virtual
+    /// channel with the highest priority which has data queued, or an idle frame.
+    pub fn next_frame(&mut self) -> Vec<u8> {
+        let mut frame = self.next_plain_frame();
+        if let Some(randomizer) = self.randomizer {
+            randomizer.apply(&mut frame);
+        }
+        frame
+    }
+
+    fn next_plain_frame(&mut self) -> Vec<u8> {
+        let cnt = self.mc_frame_count;
+        self.mc_frame_count = self.mc_frame_count.wrapping_add(1);
+
+        if let Some(idx) = self.schedule() {
+            self.last_served = idx;
+            if let Some(frame) = self.vcs[idx].0.next_frame(cnt, self.ocf) {
+                return frame;
+            }
+        }
+
+        let frame = self
+            .format
+            .build_idle(cnt, self.idle_frame_count, self.ocf, self.frame_len);
+        self.idle_frame_count = (self.idle_frame_count + 1) % self.format.vc_frame_count_modulus();
+        frame
+    }
+
+    /// Selects the VC to serve next
+    fn schedule(&self) -> Option<usize> {
+        let prio = self
+            .vcs
+            .iter()
+            .filter(|(vc, _)| vc.has_data())
+            .map(|(_, prio)| *prio)
+            .max()?;
+
+        // round robin, starting after the last served VC
+        let n = self.vcs.len();
+        (1..=n)
+            .map(|i| (self.last_served + i) % n)
+            .find(|&i| self.vcs[i].1 == prio && self.vcs[i].0.has_data())
+    }
+}
@@ -0,0 +1,250 @@
+use rs_space_core::crc::{calc_crc, calc_crc32};
+use rs_space_core::uslp_frame::*;
+
+// Frames assembled by hand following the field layout of CCSDS 732.1-B:
+// TFVN 0b1100, SCID 0x00AB, source/destination ID 0, VCID 5, MAP ID 3
+const HDR_ID: [u8; 4] = [0xC0, 0x0A, 0xB0, 0xA6];
+
+#[test]
+fn decode_full_header() {
+    // frame length 12 (encoded 11), bypass flag set, no OCF, VC frame count length 2,
+    // TFDF with rule 0b111 (no segmentation) and UPID 0
+    let frame = [
+        0xC0, 0x0A, 0xB0, 0xA6, 0x00, 0x0B, 0x82, 0x01, 0x02, 0xE0, 0xDE, 0xAD,
+    ];
+    let config = USLPConfig::default();
+    let f = USLPFrame::new(&frame, &config).unwrap();
+
+    assert_eq!(f.version(), USLP_VERSION);
+    assert_eq!(f.scid(), 0xAB);
+    assert!(!f.source_dest_id());
+    assert_eq!(f.vcid(), 5);
+    assert_eq!(f.map_id(), 3);
+    assert!(!f.is_truncated());
+    assert_eq!(f.frame_length(), 12);
+    assert!(f.bypass_flag());
+    assert!(!f.protocol_control_flag());
+    assert!(!f.ocf_flag());
+    assert_eq!(f.vc_frame_count_len(), 2);
+    assert_eq!(f.vc_frame_count(), Some(0x0102));
+    assert_eq!(f.insert_zone(), None);
+    assert_eq!(f.ocf(), None);
+    assert_eq!(f.fecf(), None);
+
+    let tfdf = f.tfdf().unwrap();
+    assert_eq!(tfdf.construction_rule, ConstructionRule::NoSegmentation);
+    assert_eq!(tfdf.upid, UPID_PACKETS);
+    assert_eq!(tfdf.pointer, None);
+    assert_eq!(tfdf.data_zone, &[0xDE, 0xAD]);
+
+    let built = USLPFrameBuilder::new(0xAB, 5, 3, &config)
+        .bypass(true)
+        .vc_frame_count(2, 0x0102)
+        .build(ConstructionRule::NoSegmentation, UPID_PACKETS, 0, &[0xDE, 0xAD])
+        .unwrap();
+    assert_eq!(built, frame);
+}
+
+#[test]
+fn decode_truncated() {
+    // end of frame primary header flag set, TFDF with rule 0b011 (octet stream)
+    let frame = [0xC0, 0x0A, 0xB0, 0xA7, 0x60, 0x01, 0x02, 0x03];
+    let config = USLPConfig {
+        truncated_frame_len: Some(frame.len()),
+        ..Default::default()
+    };
+    let f = USLPFrame::new(&frame, &config).unwrap();
+
+    assert!(f.is_truncated());
+    assert_eq!(f.scid(), 0xAB);
+    assert_eq!(f.vcid(), 5);
+    assert_eq!(f.map_id(), 3);
+    assert_eq!(f.vc_frame_count(), None);
+    assert!(!f.ocf_flag());
+    assert!(f.check_fecf());
+
+    let tfdf = f.tfdf().unwrap();
+    assert_eq!(tfdf.construction_rule, ConstructionRule::OctetStream);
+    assert_eq!(tfdf.data_zone, &[0x01, 0x02, 0x03]);
+
+    let built = USLPFrameBuilder::new(0xAB, 5, 3, &config)
+        .truncated(true)
+        .build(ConstructionRule::OctetStream, 0, 0, &[0x01, 0x02, 0x03])
+        .unwrap();
+    assert_eq!(built, frame);
+}
+
+#[test]
+fn truncated_needs_configured_length() {
+    let frame = [0xC0, 0x0A, 0xB0, 0xA7, 0x60, 0x01, 0x02, 0x03];
+    assert!(USLPFrame::new(&frame, &USLPConfig::default()).is_err());
+}
+
+// The 32 bit CRC as defined by its generator polynomial
+// x^32 + x^23 + x^21 + x^11 + x^2 + 1 with the shift register preset to all ones,
+// processed bit by bit, to cross check the table driven implementation
+fn crc32_bitwise(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        for i in (0..8).rev() {
+            let feedback = ((crc >> 31) ^ (*byte as u32 >> i)) & 1;
+            crc <<= 1;
+            if feedback != 0 {
+                crc ^= 0x00A0_0805;
+            }
+        }
+    }
+    crc
+}
+
+#[test]
+fn crc_check_values() {
+    // check values over the ASCII string "123456789"
+    assert_eq!(calc_crc(b"123456789"), 0x29B1);
+    assert_eq!(calc_crc32(b"123456789"), 0x9239_3BE9);
+    assert_eq!(crc32_bitwise(b"123456789"), 0x9239_3BE9);
+
+    let data: Vec<u8> = (0..=255).collect();
+    assert_eq!(calc_crc32(&data), crc32_bitwise(&data));
+    assert_eq!(calc_crc32(&[]), 0xFFFF_FFFF);
+
+    // without final XOR, the CRC over the data with the appended CRC is 0
+    let mut protected = data.clone();
+    protected.extend_from_slice(&calc_crc32(&data).to_be_bytes());
+    assert_eq!(calc_crc32(&protected), 0);
+}
+
+#[test]
+fn decode_frame_with_crc32_fecf() {
+    // frame length 16 (encoded 15), bypass flag set, no VC frame count, TFDF with
+    // rule 0b111 and UPID 0, 4 data bytes and the 32 bit FECF over the preceding
+    // 12 bytes, calculated with the generator polynomial of CCSDS 732.1-B
+    let frame = [
+        0xC0, 0x0A, 0xB0, 0xA6, 0x00, 0x0F, 0x80, 0xE0, 0xDE, 0xAD, 0xBE, 0xEF, 0xCB, 0x95, 0xBE,
+        0x55,
+    ];
+    assert_eq!(crc32_bitwise(&frame[..12]), 0xCB95_BE55);
+
+    let config = USLPConfig {
+        fecf: FecfType::Crc32,
+        ..Default::default()
+    };
+    let f = USLPFrame::new(&frame, &config).unwrap();
+    assert_eq!(f.frame_length(), 16);
+    assert_eq!(f.vc_frame_count(), None);
+    assert_eq!(f.fecf(), Some(0xCB95_BE55));
+    assert_eq!(f.calc_fecf(), Some(0xCB95_BE55));
+    assert!(f.check_fecf());
+    assert_eq!(f.tfdf().unwrap().data_zone, &[0xDE, 0xAD, 0xBE, 0xEF]);
+
+    let built = USLPFrameBuilder::new(0xAB, 5, 3, &config)
+        .bypass(true)
+        .build(
+            ConstructionRule::NoSegmentation,
+            UPID_PACKETS,
+            0,
+            &[0xDE, 0xAD, 0xBE, 0xEF],
+        )
+        .unwrap();
+    assert_eq!(built, frame);
+}
+
+#[test]
+fn round_trip_all_fields() {
+    for fecf in [FecfType::None, FecfType::Crc16, FecfType::Crc32] {
+        let config = USLPConfig {
+            insert_zone_len: 4,
+            fecf,
+            truncated_frame_len: None,
+        };
+        let data: Vec<u8> = (0..100).collect();
+
+        let frame = USLPFrameBuilder::new(0xFFFF, 63, 15, &config)
+            .source_dest_id(true)
+            .protocol_control(true)
+            .vc_frame_count(7, 0x00FF_EEDD_CCBB_AA99)
+            .insert_zone(&[1, 2, 3, 4])
+            .ocf(Some(0x0102_0304))
+            .build(ConstructionRule::PacketsSpanning, UPID_PACKETS, 17, &data)
+            .unwrap();
+
+        let f = USLPFrame::new(&frame, &config).unwrap();
+        assert_eq!(f.frame_length(), frame.len());
+        assert_eq!(f.scid(), 0xFFFF);
+        assert!(f.source_dest_id());
+        assert_eq!(f.vcid(), 63);
+        assert_eq!(f.map_id(), 15);
+        assert!(!f.bypass_flag());
+        assert!(f.protocol_control_flag());
+        assert_eq!(f.vc_frame_count(), Some(0x00FF_EEDD_CCBB_AA99));
+        assert_eq!(f.insert_zone(), Some(&[1u8, 2, 3, 4][..]));
+        assert_eq!(f.ocf(), Some(0x0102_0304));
+        assert!(f.check_fecf());
+        assert_eq!(f.fecf().is_some(), fecf != FecfType::None);
+
+        let tfdf = f.tfdf().unwrap();
+        assert_eq!(tfdf.construction_rule, ConstructionRule::PacketsSpanning);
+        assert_eq!(tfdf.pointer, Some(17));
+        assert_eq!(tfdf.data_zone, &data[..]);
+    }
+}
+
+#[test]
+fn round_trip_vc_frame_count_lengths() {
+    let config = USLPConfig::default();
+    for len in 0..=MAX_VC_FRAME_COUNT_LEN {
+        let cnt = if len == 0 { 0 } else { (1u64 << (len * 8)) - 2 };
+        let frame = USLPFrameBuilder::new(1, 2, 0, &config)
+            .vc_frame_count(len, cnt)
+            .build(ConstructionRule::StartOfSdu, 5, 0x1234, &[0xAA; 10])
+            .unwrap();
+
+        let f = USLPFrame::new(&frame, &config).unwrap();
+        assert_eq!(f.vc_frame_count_len(), len);
+        assert_eq!(f.vc_frame_count(), if len == 0 { None } else { Some(cnt) });
+        let tfdf = f.tfdf().unwrap();
+        assert_eq!(tfdf.pointer, Some(0x1234));
+        assert_eq!(tfdf.upid, 5);
+        assert_eq!(tfdf.data_zone, &[0xAA; 10]);
+    }
+}
+
+#[test]
+fn detects_corrupted_fecf() {
+    for fecf in [FecfType::Crc16, FecfType::Crc32] {
+        let config = USLPConfig {
+            fecf,
+            ..Default::default()
+        };
+        let mut frame = USLPFrameBuilder::new(0xAB, 5, 3, &config)
+            .build(ConstructionRule::NoSegmentation, 0, 0, &[0x55; 20])
+            .unwrap();
+        assert!(USLPFrame::new(&frame, &config).unwrap().check_fecf());
+
+        frame[12] ^= 0x10;
+        assert!(!USLPFrame::new(&frame, &config).unwrap().check_fecf());
+    }
+}
+
+#[test]
+fn ignores_trailing_data() {
+    let config = USLPConfig::default();
+    let mut frame = USLPFrameBuilder::new(0xAB, 5, 3, &config)
+        .build(ConstructionRule::NoSegmentation, 0, 0, &[1, 2, 3])
+        .unwrap();
+    let len = frame.len();
+    frame.extend_from_slice(&HDR_ID);
+
+    let f = USLPFrame::new(&frame, &config).unwrap();
+    assert_eq!(f.frame_length(), len);
+    assert_eq!(f.tfdf().unwrap().data_zone, &[1, 2, 3]);
+}
+
+#[test]
+fn rejects_wrong_version_and_short_frames() {
+    let config = USLPConfig::default();
+    assert!(USLPFrame::new(&[0x00, 0x0A, 0xB0, 0xA6, 0x00, 0x07, 0x00, 0xE0], &config).is_err());
+    // frame length in the header is larger than the data
+    assert!(USLPFrame::new(&[0xC0, 0x0A, 0xB0, 0xA6, 0x00, 0x20, 0x00, 0xE0], &config).is_err());
+    assert!(USLPFrame::new(&HDR_ID, &config).is_err());
+}
@@ -31,6 +31,7 @@ pub mod types {
 /// TML is the TCP/IP message layer for SLE. All SLE PDUs are ASN1 encoded messages, which are
 /// transmitted via the TML messages defined in this module
 pub mod tml {
+    pub mod codec;
     pub mod config;
     pub mod message;
 }
@@ -39,10 +40,13 @@ pub mod tml {
 pub mod raf {
     pub mod asn1;
     pub mod config;
+    pub mod frame_archive;
+    pub mod frame_sink;
     pub mod provider;
     pub mod provider_state;
     pub mod state;
     pub mod user;
+    pub mod user_supervisor;
 }
 /// This module contains the general SLE configuration values.
 pub mod sle {
@@ -57,5 +61,8 @@ pub mod user {
 /// Contains the configuration and callback interfaces for the SLE Provider.
 pub mod provider {
     pub mod config;
+    pub mod events;
+    pub mod frame_source;
     pub mod raf_interface;
+    pub mod supervisor;
 }
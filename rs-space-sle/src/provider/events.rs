@@ -0,0 +1,177 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::asn1::UnbindReason;
+use crate::provider::raf_interface::ProviderNotifier;
+use crate::raf::asn1::RafGetReturnResult;
+use crate::types::sle::{PeerAbortDiagnostic, SleVersion};
+
+/// The notifications of a provider as values, so that they can be processed as
+/// an asynchronous stream (e.g. to forward them to a monitoring and control system).
+/// Every event except [ProviderEvent::EventsDropped] carries the service instance
+/// identifier it belongs to.
+#[derive(Debug, Clone)]
+pub enum ProviderEvent {
+    ConnectionAccepted { sii: String, peer: SocketAddr },
+    ConnectionClosed { sii: String },
+    BindSucceeded { sii: String, peer: String, version: SleVersion },
+    UnbindSucceeded { sii: String, reason: UnbindReason },
+    StartSucceeded { sii: String },
+    StopSucceeded { sii: String },
+    PeerAbort { sii: String, diagnostic: PeerAbortDiagnostic },
+    AuthenticationFailed { sii: String, peer: String, operation: String },
+    HeartbeatTimeout { sii: String },
+    ProtocolAbort { sii: String, reason: String },
+    GetParameter { sii: String, parameter: i64, result: RafGetReturnResult },
+    BufferSent { sii: String, frames: usize, notifications: usize },
+    BufferDiscarded { sii: String, frames: usize, notifications: usize },
+    /// Number of events dropped since the last [ProviderEvent::EventsDropped],
+    /// because the receiver did not keep up
+    EventsDropped { count: u64 },
+}
+
+/// A [ProviderNotifier] which converts all notifications into [ProviderEvent]s and
+/// sends them to a channel.
+///
+/// The channel is bounded by `capacity`. As the notifier methods are called
+/// synchronously from within the provider tasks and must not block them, events
+/// which don't fit into the channel are dropped and counted. The number of dropped
+/// events is reported with a [ProviderEvent::EventsDropped] as soon as there is
+/// room in the channel again, and the total is available from [EventStreamNotifier::dropped_events].
+pub struct EventStreamNotifier {
+    chan: Sender<ProviderEvent>,
+    // dropped events not yet reported with an EventsDropped event
+    pending: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl EventStreamNotifier {
+    /// Create a new notifier together with the receiving end of the event stream
+    pub fn new(capacity: usize) -> (EventStreamNotifier, Receiver<ProviderEvent>) {
+        let (sender, receiver) = channel(capacity);
+        let notifier = EventStreamNotifier {
+            chan: sender,
+            pending: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        };
+        (notifier, receiver)
+    }
+
+    /// Total number of events dropped because the channel was full
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn send(&self, event: ProviderEvent) {
+        let pending = self.pending.swap(0, Ordering::Relaxed);
+        if pending > 0 {
+            if let Err(TrySendError::Full(_)) =
+                self.chan.try_send(ProviderEvent::EventsDropped { count: pending })
+            {
+                self.pending.fetch_add(pending, Ordering::Relaxed);
+            }
+        }
+        // if nobody is listening anymore, the event is dropped without counting it
+        if let Err(TrySendError::Full(_)) = self.chan.try_send(event) {
+            self.pending.fetch_add(1, Ordering::Relaxed);
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl ProviderNotifier for EventStreamNotifier {
+    fn peer_abort(&self, sii: &str, diagnostic: &PeerAbortDiagnostic) {
+        self.send(ProviderEvent::PeerAbort {
+            sii: sii.to_string(),
+            diagnostic: *diagnostic,
+        });
+    }
+
+    fn bind_succeeded(&self, peer: &str, sii: &str, version: SleVersion) {
+        self.send(ProviderEvent::BindSucceeded {
+            sii: sii.to_string(),
+            peer: peer.to_string(),
+            version,
+        });
+    }
+
+    fn unbind_succeeded(&self, sii: &str, reason: UnbindReason) {
+        self.send(ProviderEvent::UnbindSucceeded {
+            sii: sii.to_string(),
+            reason,
+        });
+    }
+
+    fn start_succeeded(&self, sii: &str) {
+        self.send(ProviderEvent::StartSucceeded {
+            sii: sii.to_string(),
+        });
+    }
+
+    fn stop_succeeded(&self, sii: &str) {
+        self.send(ProviderEvent::StopSucceeded {
+            sii: sii.to_string(),
+        });
+    }
+
+    fn connection_accepted(&self, sii: &str, peer: &SocketAddr) {
+        self.send(ProviderEvent::ConnectionAccepted {
+            sii: sii.to_string(),
+            peer: *peer,
+        });
+    }
+
+    fn connection_closed(&self, sii: &str) {
+        self.send(ProviderEvent::ConnectionClosed {
+            sii: sii.to_string(),
+        });
+    }
+
+    fn authentication_failed(&self, sii: &str, peer: &str, operation: &str) {
+        self.send(ProviderEvent::AuthenticationFailed {
+            sii: sii.to_string(),
+            peer: peer.to_string(),
+            operation: operation.to_string(),
+        });
+    }
+
+    fn heartbeat_timeout(&self, sii: &str) {
+        self.send(ProviderEvent::HeartbeatTimeout {
+            sii: sii.to_string(),
+        });
+    }
+
+    fn protocol_abort(&self, sii: &str, reason: &str) {
+        self.send(ProviderEvent::ProtocolAbort {
+            sii: sii.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn get_parameter(&self, sii: &str, parameter: i64, result: &RafGetReturnResult) {
+        self.send(ProviderEvent::GetParameter {
+            sii: sii.to_string(),
+            parameter,
+            result: result.clone(),
+        });
+    }
+
+    fn buffer_sent(&self, sii: &str, frames: usize, notifications: usize) {
+        self.send(ProviderEvent::BufferSent {
+            sii: sii.to_string(),
+            frames,
+            notifications,
+        });
+    }
+
+    fn buffer_discarded(&self, sii: &str, frames: usize, notifications: usize) {
+        self.send(ProviderEvent::BufferDiscarded {
+            sii: sii.to_string(),
+            frames,
+            notifications,
+        });
+    }
+}
@@ -0,0 +1,632 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use log::{debug, info, warn};
+use rs_space_core::frame_sync::{FrameSyncConfig, FrameSynchronizer, SyncEvent, SyncState};
+use rs_space_core::randomizer::Randomizer;
+use rs_space_core::reed_solomon::{RSStatus, ReedSolomon};
+use rs_space_core::time::{Time, TimeEncoding};
+use rs_space_core::tm_frame::{TMFrame, TMFrameBuilder, FHP_IDLE, FHP_NO_PACKET_START, IDLE_VCID};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, BufReader};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::select;
+use tokio::time::{interval, sleep, timeout, Instant, Interval, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use crate::provider::supervisor::RAFInstanceHandle;
+use crate::raf::asn1::{FrameQuality, LockStatus, SleFrame};
+use crate::raf::config::{FrameSourceConfig, GeneratorPattern};
+use crate::raf::frame_archive::read_record;
+use crate::raf::state::RAFState;
+
+/// The attached sync marker in front of a CADU
+pub const ASM: [u8; 4] = [0x1A, 0xCF, 0xFC, 0x1D];
+
+const ERT_ENCODING: TimeEncoding = TimeEncoding::CDS8;
+const MAX_DATAGRAM_SIZE: usize = 65536;
+/// Minimum time between two attempts to connect to a TCP frame source
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Maximum time to wait for the connection to a TCP frame source
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The items delivered by a [FrameSource]
+pub enum SourceEvent {
+    Frame(SleFrame),
+    /// A synchronized CADU stream lost the frame lock
+    SyncLost,
+}
+
+/// A source of frames to be delivered by a RAF provider, created from a [FrameSourceConfig].
+///
+/// Live sources (network streams and the generator) deliver frames regardless of the
+/// state of the service instance, frames are dropped while it is not ACTIVE. Recorded
+/// sources (files and archives) wait for the service instance to become ACTIVE, so that
+/// no recorded frame is lost.
+pub enum FrameSource {
+    RawFile(RawFileSource),
+    Tcp(StreamSource),
+    Udp(DatagramSource),
+    Archive(ArchiveSource),
+    Generator(GeneratorSource),
+}
+
+impl FrameSource {
+    /// Open the frame source described by the config
+    pub async fn open(config: &FrameSourceConfig) -> Result<FrameSource, String> {
+        match config {
+            FrameSourceConfig::RawFile {
+                path,
+                frame_length,
+                bit_rate,
+            } => Ok(FrameSource::RawFile(
+                RawFileSource::open(path, *frame_length, *bit_rate).await?,
+            )),
+            FrameSourceConfig::Tcp {
+                address,
+                frame_length,
+                with_asm,
+                sync,
+                randomizer,
+                reed_solomon,
+            } => {
+                let extractor = CaduExtractor::new(
+                    *frame_length,
+                    *with_asm,
+                    *sync,
+                    *randomizer,
+                    *reed_solomon,
+                )?;
+                Ok(FrameSource::Tcp(StreamSource::connect(address, extractor).await?))
+            }
+            FrameSourceConfig::Udp {
+                address,
+                frame_length,
+                with_asm,
+                sync,
+                randomizer,
+                reed_solomon,
+            } => {
+                let extractor = CaduExtractor::new(
+                    *frame_length,
+                    *with_asm,
+                    *sync,
+                    *randomizer,
+                    *reed_solomon,
+                )?;
+                Ok(FrameSource::Udp(DatagramSource::bind(address, extractor).await?))
+            }
+            FrameSourceConfig::Archive { path, realtime } => Ok(FrameSource::Archive(
+                ArchiveSource::open(path, *realtime).await?,
+            )),
+            FrameSourceConfig::Generator {
+                frame_length,
+                bit_rate,
+                spacecraft_id,
+                pattern,
+            } => Ok(FrameSource::Generator(GeneratorSource::new(
+                *frame_length,
+                *bit_rate,
+                *spacecraft_id,
+                *pattern,
+            )?)),
+        }
+    }
+
+    /// Returns true, if the source delivers frames in real time and does not wait
+    /// for the service instance to become ACTIVE
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self,
+            FrameSource::Tcp(_) | FrameSource::Udp(_) | FrameSource::Generator(_)
+        )
+    }
+
+    /// Returns the next frame or loss of lock, None if the source is exhausted
+    pub async fn next_event(&mut self) -> Result<Option<SourceEvent>, String> {
+        match self {
+            FrameSource::RawFile(src) => Ok(src.next_frame().await?.map(SourceEvent::Frame)),
+            FrameSource::Tcp(src) => src.next_event().await,
+            FrameSource::Udp(src) => src.next_event().await,
+            FrameSource::Archive(src) => Ok(src.next_frame().await?.map(SourceEvent::Frame)),
+            FrameSource::Generator(src) => Ok(Some(SourceEvent::Frame(src.next_frame().await))),
+        }
+    }
+}
+
+/// Reads the frames from the source described by the config and sends them via the
+/// service instance of the handle until the source is exhausted or the token is
+/// cancelled.
+pub async fn run_frame_source(
+    config: FrameSourceConfig,
+    mut handle: RAFInstanceHandle,
+    cancel: CancellationToken,
+) -> Result<(), String> {
+    let mut source = select! {
+        res = FrameSource::open(&config) => res?,
+        _ = cancel.cancelled() => return Ok(()),
+    };
+    let live = source.is_live();
+
+    info!("Frame source for {} started: {config:?}", handle.sii());
+
+    loop {
+        let event = select! {
+            res = source.next_event() => res?,
+            _ = cancel.cancelled() => return Ok(()),
+        };
+
+        let frame = match event {
+            Some(SourceEvent::Frame(frame)) => frame,
+            Some(SourceEvent::SyncLost) => {
+                if handle.state() == RAFState::Active {
+                    // only the frame lock is known from the stream
+                    if let Err(err) = handle
+                        .notify_sync_loss(
+                            &Time::now(ERT_ENCODING),
+                            LockStatus::Unknown,
+                            LockStatus::Unknown,
+                            LockStatus::Unknown,
+                        )
+                        .await
+                    {
+                        debug!("Sync loss not notified: {err}");
+                    }
+                }
+                continue;
+            }
+            None => {
+                info!("Frame source for {} is exhausted", handle.sii());
+                return Ok(());
+            }
+        };
+
+        if live {
+            if handle.state() != RAFState::Active {
+                continue;
+            }
+        } else {
+            let active = select! {
+                active = handle.wait_active() => active,
+                _ = cancel.cancelled() => return Ok(()),
+            };
+            if !active {
+                return Ok(());
+            }
+        }
+
+        if let Err(err) = handle.send_frame(frame).await {
+            debug!("Frame dropped: {err}");
+        }
+    }
+}
+
+/// Creates the pacing interval for the given frame length and bit rate. A bit rate of 0
+/// means the frames are delivered as fast as possible.
+fn pacing(frame_length: usize, bit_rate: u64) -> Option<Interval> {
+    if bit_rate == 0 {
+        return None;
+    }
+    let period = Duration::from_secs_f64((frame_length * 8) as f64 / bit_rate as f64);
+    let mut ival = interval(period);
+    ival.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    Some(ival)
+}
+
+fn new_frame(data: Bytes) -> SleFrame {
+    SleFrame {
+        earth_receive_time: Time::now(ERT_ENCODING),
+        delivered_frame_quality: FrameQuality::Good,
+        data,
+    }
+}
+
+/// A file of raw frames with a fixed length, without any headers or sync markers
+pub struct RawFileSource {
+    file: BufReader<File>,
+    frame_length: usize,
+    pacing: Option<Interval>,
+}
+
+impl RawFileSource {
+    async fn open(path: &str, frame_length: usize, bit_rate: u64) -> Result<RawFileSource, String> {
+        if frame_length == 0 {
+            return Err("Frame length of raw frame file must not be 0".to_string());
+        }
+        let file = File::open(path)
+            .await
+            .map_err(|e| format!("Could not open frame file {path}: {e}"))?;
+        Ok(RawFileSource {
+            file: BufReader::new(file),
+            frame_length,
+            pacing: pacing(frame_length, bit_rate),
+        })
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<SleFrame>, String> {
+        if let Some(ival) = &mut self.pacing {
+            ival.tick().await;
+        }
+
+        let mut buf = vec![0u8; self.frame_length];
+        match self.file.read_exact(&mut buf).await {
+            Ok(_) => Ok(Some(new_frame(Bytes::from(buf)))),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(format!("Error reading frame file: {e}")),
+        }
+    }
+}
+
+/// Extracts CADUs of a fixed length from a byte stream. If the CADUs have an attached
+/// sync marker, the stream is synchronized with a [FrameSynchronizer], otherwise it is
+/// cut into frames. If a randomizer is given, the extracted frames are derandomized.
+/// With Reed-Solomon, the check symbols are removed and frames with uncorrectable
+/// errors are marked as erred.
+struct CaduExtractor {
+    buffer: BytesMut,
+    sync: Option<FrameSynchronizer>,
+    events: VecDeque<SyncEvent>,
+    frame_length: usize,
+    randomizer: Option<Randomizer>,
+    reed_solomon: Option<ReedSolomon>,
+}
+
+impl CaduExtractor {
+    fn new(
+        frame_length: usize,
+        with_asm: bool,
+        sync_config: FrameSyncConfig,
+        randomizer: Option<Randomizer>,
+        reed_solomon: Option<ReedSolomon>,
+    ) -> Result<CaduExtractor, String> {
+        if frame_length == 0 {
+            return Err("Frame length of frame source must not be 0".to_string());
+        }
+        if let Some(rs) = &reed_solomon {
+            rs.check_frame_length(frame_length)
+                .map_err(|e| format!("Invalid Reed-Solomon config of frame source: {e}"))?;
+        }
+        let codeblock_length = match &reed_solomon {
+            Some(rs) => rs.codeblock_length(frame_length),
+            None => frame_length,
+        };
+        let sync = if with_asm {
+            Some(
+                FrameSynchronizer::new(codeblock_length, sync_config)
+                    .map_err(|e| format!("Invalid frame sync config of frame source: {e}"))?,
+            )
+        } else {
+            None
+        };
+
+        Ok(CaduExtractor {
+            buffer: BytesMut::with_capacity(4 * codeblock_length),
+            sync,
+            events: VecDeque::new(),
+            frame_length,
+            randomizer,
+            reed_solomon,
+        })
+    }
+
+    /// The length of the frame including the Reed-Solomon check symbols
+    fn codeblock_length(&self) -> usize {
+        match &self.reed_solomon {
+            Some(rs) => rs.codeblock_length(self.frame_length),
+            None => self.frame_length,
+        }
+    }
+
+    fn extend(&mut self, data: &[u8]) {
+        match &mut self.sync {
+            Some(sync) => self.events.extend(sync.push(data)),
+            None => self.buffer.extend_from_slice(data),
+        }
+    }
+
+    /// Drop an incomplete frame. With sync markers, the synchronizer keeps its state,
+    /// as it finds the next marker anyway.
+    fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Drop all buffered data and search for the sync marker again. Returns true,
+    /// if the stream was in lock.
+    fn reset(&mut self) -> bool {
+        self.buffer.clear();
+        self.events.clear();
+        match &mut self.sync {
+            Some(sync) => {
+                let locked = sync.state().is_locked();
+                sync.reset();
+                locked
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the next complete frame or a loss of lock, if available
+    fn next_event(&mut self) -> Option<SourceEvent> {
+        let (mut frame, state) = if self.sync.is_some() {
+            loop {
+                match self.events.pop_front()? {
+                    SyncEvent::Frame(frame) => {
+                        break (BytesMut::from(&frame.data[..]), frame.state);
+                    }
+                    SyncEvent::LockAcquired => info!("CADU stream in lock"),
+                    SyncEvent::LockLost => {
+                        warn!("Lost lock on CADU stream, searching for sync marker");
+                        return Some(SourceEvent::SyncLost);
+                    }
+                }
+            }
+        } else {
+            let len = self.codeblock_length();
+            if self.buffer.len() < len {
+                return None;
+            }
+            (self.buffer.split_to(len), SyncState::Lock)
+        };
+
+        if let Some(randomizer) = self.randomizer {
+            randomizer.apply(&mut frame);
+        }
+
+        // frames without verified marker are only good, if Reed-Solomon confirms them
+        let mut quality = if state == SyncState::Lock {
+            FrameQuality::Good
+        } else {
+            FrameQuality::Undetermined
+        };
+        if let Some(rs) = &self.reed_solomon {
+            match rs.decode(&mut frame) {
+                Ok(RSStatus::Corrected(n)) => {
+                    if n > 0 {
+                        debug!("Reed-Solomon corrected {n} symbols");
+                    }
+                    quality = FrameQuality::Good;
+                }
+                Ok(RSStatus::Uncorrectable) | Err(_) => quality = FrameQuality::Erred,
+            }
+            frame.truncate(self.frame_length);
+        }
+
+        let mut frame = new_frame(frame.freeze());
+        frame.delivered_frame_quality = quality;
+        Some(SourceEvent::Frame(frame))
+    }
+}
+
+/// CADUs received via a TCP connection. If the connection is closed, e.g. because
+/// the front end restarts, it is re-established.
+pub struct StreamSource {
+    address: String,
+    stream: Option<TcpStream>,
+    extractor: CaduExtractor,
+}
+
+impl StreamSource {
+    async fn connect(address: &str, extractor: CaduExtractor) -> Result<StreamSource, String> {
+        let stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Could not connect to frame source {address}: {e}"))?;
+        info!("Connected to frame source {address}");
+        Ok(StreamSource {
+            address: address.to_string(),
+            stream: Some(stream),
+            extractor,
+        })
+    }
+
+    /// Connect again after the connection was lost, until it succeeds
+    async fn reconnect(&mut self) {
+        loop {
+            sleep(RECONNECT_DELAY).await;
+            match timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.address)).await {
+                Ok(Ok(stream)) => {
+                    info!("Reconnected to frame source {}", self.address);
+                    self.stream = Some(stream);
+                    return;
+                }
+                Ok(Err(e)) => debug!("Could not reconnect to frame source {}: {e}", self.address),
+                Err(_) => debug!("Timeout reconnecting to frame source {}", self.address),
+            }
+        }
+    }
+
+    /// Drop the connection. The data received so far does not continue on the next
+    /// connection, so a loss of lock is reported if the stream was in lock.
+    fn disconnect(&mut self) -> Option<SourceEvent> {
+        self.stream = None;
+        self.extractor.reset().then_some(SourceEvent::SyncLost)
+    }
+
+    /// Returns the next frame or loss of lock. Never ends, the connection is
+    /// re-established if it is closed.
+    async fn next_event(&mut self) -> Result<Option<SourceEvent>, String> {
+        let mut buf = [0u8; 8192];
+        loop {
+            if let Some(event) = self.extractor.next_event() {
+                return Ok(Some(event));
+            }
+
+            let Some(stream) = self.stream.as_mut() else {
+                self.reconnect().await;
+                continue;
+            };
+            match stream.read(&mut buf).await {
+                Ok(0) => warn!("Frame source {} closed the connection", self.address),
+                Ok(len) => {
+                    self.extractor.extend(&buf[..len]);
+                    continue;
+                }
+                Err(e) => warn!("Error reading from frame source {}: {e}", self.address),
+            }
+            if let Some(event) = self.disconnect() {
+                return Ok(Some(event));
+            }
+        }
+    }
+}
+
+/// CADUs received as UDP datagrams. A datagram can contain several CADUs, but a
+/// CADU must not span multiple datagrams.
+pub struct DatagramSource {
+    socket: UdpSocket,
+    extractor: CaduExtractor,
+}
+
+impl DatagramSource {
+    async fn bind(address: &str, extractor: CaduExtractor) -> Result<DatagramSource, String> {
+        let socket = UdpSocket::bind(address)
+            .await
+            .map_err(|e| format!("Could not bind UDP frame source to {address}: {e}"))?;
+        info!("Listening for frames on UDP {address}");
+        Ok(DatagramSource {
+            socket,
+            extractor,
+        })
+    }
+
+    async fn next_event(&mut self) -> Result<Option<SourceEvent>, String> {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        loop {
+            if let Some(event) = self.extractor.next_event() {
+                return Ok(Some(event));
+            }
+
+            // left over bytes of the last datagram are no complete CADU
+            self.extractor.clear();
+
+            let len = self
+                .socket
+                .recv(&mut buf)
+                .await
+                .map_err(|e| format!("Error receiving from UDP frame source: {e}"))?;
+            self.extractor.extend(&buf[..len]);
+        }
+    }
+}
+
+/// Frames from a frame archive (see [crate::raf::frame_archive]) with their
+/// recorded earth receive times and qualities
+pub struct ArchiveSource {
+    file: BufReader<File>,
+    realtime: bool,
+    /// The ERT of the first frame and the instant it was delivered
+    start: Option<(Duration, Instant)>,
+}
+
+impl ArchiveSource {
+    async fn open(path: &str, realtime: bool) -> Result<ArchiveSource, String> {
+        let file = File::open(path)
+            .await
+            .map_err(|e| format!("Could not open frame archive {path}: {e}"))?;
+        Ok(ArchiveSource {
+            file: BufReader::new(file),
+            realtime,
+            start: None,
+        })
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<SleFrame>, String> {
+        let Some(record) = read_record(&mut self.file).await? else {
+            return Ok(None);
+        };
+
+        if self.realtime {
+            let ert = record.frame.earth_receive_time.as_duration();
+            match self.start {
+                None => self.start = Some((ert, Instant::now())),
+                Some((first_ert, started)) => {
+                    // frames with an ERT before the first one are delivered immediately
+                    if let Some(offset) = ert.checked_sub(first_ert) {
+                        tokio::time::sleep_until(started + offset).await;
+                    }
+                }
+            }
+        }
+
+        Ok(Some(record.frame))
+    }
+}
+
+/// Generates TM transfer frames with a fixed bit rate
+pub struct GeneratorSource {
+    frame_length: usize,
+    spacecraft_id: u16,
+    pattern: GeneratorPattern,
+    pacing: Option<Interval>,
+    mc_count: u8,
+    counter: u8,
+}
+
+impl GeneratorSource {
+    fn new(
+        frame_length: usize,
+        bit_rate: u64,
+        spacecraft_id: u16,
+        pattern: GeneratorPattern,
+    ) -> Result<GeneratorSource, String> {
+        if frame_length <= TMFrame::PRIMARY_HDR_LEN {
+            return Err(format!(
+                "Frame length of generator must be larger than {}",
+                TMFrame::PRIMARY_HDR_LEN
+            ));
+        }
+        let source = GeneratorSource {
+            frame_length,
+            spacecraft_id,
+            pattern,
+            pacing: pacing(frame_length, bit_rate),
+            mc_count: 0,
+            counter: 0,
+        };
+        // build one frame, so an invalid configuration is reported here and not
+        // while generating
+        source
+            .build_frame(&vec![0; frame_length - TMFrame::PRIMARY_HDR_LEN])
+            .map_err(|e| format!("Invalid generator configuration: {e}"))?;
+        Ok(source)
+    }
+
+    fn build_frame(&self, data_field: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        let (vcid, fhp) = match self.pattern {
+            GeneratorPattern::IdleFrames => (IDLE_VCID, FHP_IDLE),
+            GeneratorPattern::Counter => (0, FHP_NO_PACKET_START),
+        };
+
+        // as there is only one virtual channel, MC and VC frame counts are the same
+        TMFrameBuilder::new(self.spacecraft_id, vcid)
+            .mc_frame_count(self.mc_count)
+            .vc_frame_count(self.mc_count)
+            .first_header_pointer(fhp)
+            .build(data_field)
+    }
+
+    async fn next_frame(&mut self) -> SleFrame {
+        if let Some(ival) = &mut self.pacing {
+            ival.tick().await;
+        }
+
+        let data_len = self.frame_length - TMFrame::PRIMARY_HDR_LEN;
+        let data_field: Vec<u8> = match self.pattern {
+            GeneratorPattern::IdleFrames => vec![0x55; data_len],
+            GeneratorPattern::Counter => (0..data_len)
+                .map(|_| {
+                    let val = self.counter;
+                    self.counter = self.counter.wrapping_add(1);
+                    val
+                })
+                .collect(),
+        };
+
+        let frame = self
+            .build_frame(&data_field)
+            .expect("generator configuration has been checked in GeneratorSource::new");
+
+        self.mc_count = self.mc_count.wrapping_add(1);
+        new_frame(Bytes::from(frame))
+    }
+}
@@ -1,5 +1,9 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
 use crate::types::sle::SleVersion;
 use crate::asn1::UnbindReason;
+use crate::raf::asn1::RafGetReturnResult;
 use crate::types::sle::{
     PeerAbortDiagnostic
 };
@@ -12,4 +16,86 @@ pub trait ProviderNotifier {
 
     fn start_succeeded(&self, sii: &str);
     fn stop_succeeded(&self, sii: &str);
+
+    // The following notifications have default implementations which ignore the
+    // notification, so that applications only need to implement what they are
+    // interested in.
+
+    /// A TCP connection from the given address has been accepted
+    fn connection_accepted(&self, _sii: &str, _peer: &SocketAddr) {}
+    /// The connection has been closed and all tasks of the provider terminated
+    fn connection_closed(&self, _sii: &str) {}
+
+    /// An SLE operation from the given peer failed authentication
+    fn authentication_failed(&self, _sii: &str, _peer: &str, _operation: &str) {}
+    /// No message (including heartbeats) has been received within the dead factor interval
+    fn heartbeat_timeout(&self, _sii: &str) {}
+    /// The connection has been aborted because of a TML protocol error (e.g. an
+    /// invalid context message, an undecodable message or a heartbeat timeout)
+    fn protocol_abort(&self, _sii: &str, _reason: &str) {}
+
+    /// A RAF GET-PARAMETER has been received and answered with the given result
+    fn get_parameter(&self, _sii: &str, _parameter: i64, _result: &RafGetReturnResult) {}
+
+    /// A transfer buffer with the given number of frames and notifications has been sent
+    fn buffer_sent(&self, _sii: &str, _frames: usize, _notifications: usize) {}
+    /// A transfer buffer could not be sent and the contained frames and notifications
+    /// have been discarded
+    fn buffer_discarded(&self, _sii: &str, _frames: usize, _notifications: usize) {}
+}
+
+/// Allows sharing one notifier between several providers (e.g. for all service
+/// instances of a [crate::provider::supervisor::ProviderSupervisor]).
+impl<T: ProviderNotifier + ?Sized> ProviderNotifier for Arc<T> {
+    fn peer_abort(&self, sii: &str, diagnostic: &PeerAbortDiagnostic) {
+        (**self).peer_abort(sii, diagnostic)
+    }
+
+    fn bind_succeeded(&self, peer: &str, sii: &str, version: SleVersion) {
+        (**self).bind_succeeded(peer, sii, version)
+    }
+
+    fn unbind_succeeded(&self, sii: &str, reason: UnbindReason) {
+        (**self).unbind_succeeded(sii, reason)
+    }
+
+    fn start_succeeded(&self, sii: &str) {
+        (**self).start_succeeded(sii)
+    }
+
+    fn stop_succeeded(&self, sii: &str) {
+        (**self).stop_succeeded(sii)
+    }
+
+    fn connection_accepted(&self, sii: &str, peer: &SocketAddr) {
+        (**self).connection_accepted(sii, peer)
+    }
+
+    fn connection_closed(&self, sii: &str) {
+        (**self).connection_closed(sii)
+    }
+
+    fn authentication_failed(&self, sii: &str, peer: &str, operation: &str) {
+        (**self).authentication_failed(sii, peer, operation)
+    }
+
+    fn heartbeat_timeout(&self, sii: &str) {
+        (**self).heartbeat_timeout(sii)
+    }
+
+    fn protocol_abort(&self, sii: &str, reason: &str) {
+        (**self).protocol_abort(sii, reason)
+    }
+
+    fn get_parameter(&self, sii: &str, parameter: i64, result: &RafGetReturnResult) {
+        (**self).get_parameter(sii, parameter, result)
+    }
+
+    fn buffer_sent(&self, sii: &str, frames: usize, notifications: usize) {
+        (**self).buffer_sent(sii, frames, notifications)
+    }
+
+    fn buffer_discarded(&self, sii: &str, frames: usize, notifications: usize) {
+        (**self).buffer_discarded(sii, frames, notifications)
+    }
 }
@@ -0,0 +1,255 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::select;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use rs_space_core::time::Time;
+
+use crate::provider::config::ProviderConfig;
+use crate::provider::raf_interface::ProviderNotifier;
+use crate::raf::asn1::{LockStatus, SleFrame};
+use crate::raf::config::RAFProviderConfig;
+use crate::raf::provider::{FrameSender, RAFProvider};
+use crate::raf::state::RAFState;
+use crate::sle::config::CommonConfig;
+
+/// Delay before a service instance is restarted after a failure to listen
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+/// After an UNBIND, the user is expected to release the connection within this time.
+/// Otherwise the connection is closed by the provider.
+const UNBIND_GRACE_TIME: Duration = Duration::from_secs(2);
+
+pub type SharedNotifier = Arc<dyn ProviderNotifier + Send + Sync>;
+
+/// A handle to a single service instance run by the [ProviderSupervisor]. It stays
+/// valid over restarts of the service instance and always refers to the currently
+/// running provider.
+#[derive(Clone)]
+pub struct RAFInstanceHandle {
+    sii: String,
+    sender: watch::Receiver<Option<FrameSender>>,
+}
+
+impl RAFInstanceHandle {
+    /// The service instance identifier of this instance
+    pub fn sii(&self) -> &str {
+        &self.sii
+    }
+
+    /// The current state of the service instance
+    pub fn state(&self) -> RAFState {
+        match &*self.sender.borrow() {
+            Some(sender) => sender.state(),
+            None => RAFState::Unbound,
+        }
+    }
+
+    fn current(&self) -> Result<FrameSender, String> {
+        match &*self.sender.borrow() {
+            Some(sender) => Ok(sender.clone()),
+            None => Err(format!("No connection established on {}", self.sii)),
+        }
+    }
+
+    /// Send a TM Transfer Frame via the currently running provider
+    pub async fn send_frame(&self, frame: SleFrame) -> Result<(), String> {
+        self.current()?.send_frame(frame).await
+    }
+
+    /// Send a loss of frame sync notification via the currently running provider
+    pub async fn notify_sync_loss(
+        &self,
+        time: &Time,
+        carrier_lock_status: LockStatus,
+        subcarrier_lock_status: LockStatus,
+        symbol_sync_lock_status: LockStatus,
+    ) -> Result<(), String> {
+        self.current()?
+            .notify_sync_loss(
+                time,
+                carrier_lock_status,
+                subcarrier_lock_status,
+                symbol_sync_lock_status,
+            )
+            .await
+    }
+
+    /// Wait until the service instance is ACTIVE. If the provider terminates in
+    /// between, this waits for the restarted one. Returns false, if the supervisor
+    /// has been shut down.
+    pub async fn wait_active(&mut self) -> bool {
+        loop {
+            let current = self.sender.borrow_and_update().clone();
+            match current {
+                Some(mut sender) => {
+                    select! {
+                        active = sender.wait_active() => {
+                            if active {
+                                return true;
+                            }
+                            // the provider terminated, wait for the next one
+                            if self.sender.changed().await.is_err() {
+                                return false;
+                            }
+                        }
+                        res = self.sender.changed() => {
+                            if res.is_err() {
+                                return false;
+                            }
+                        }
+                    }
+                }
+                None => {
+                    if self.sender.changed().await.is_err() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Runs all RAF service instances of a [ProviderConfig] concurrently. Every service
+/// instance is restarted after an UNBIND, a PEER ABORT or a connection loss, so that
+/// it is ready for the next BIND.
+pub struct ProviderSupervisor {
+    handles: Vec<RAFInstanceHandle>,
+    tasks: Vec<JoinHandle<()>>,
+    shutdown: CancellationToken,
+}
+
+impl ProviderSupervisor {
+    /// Start all service instances from the config. The notifier is shared between
+    /// all instances, the service instance identifier passed to it tells them apart.
+    pub fn start(config: &ProviderConfig, notifier: SharedNotifier) -> ProviderSupervisor {
+        let shutdown = CancellationToken::new();
+        let mut handles = Vec::with_capacity(config.rafs.len());
+        let mut tasks = Vec::with_capacity(config.rafs.len());
+
+        for raf_config in &config.rafs {
+            let (sender, receiver) = watch::channel(None);
+
+            handles.push(RAFInstanceHandle {
+                sii: raf_config.sii.clone(),
+                sender: receiver,
+            });
+
+            let common_config = config.common.clone();
+            let raf_config = raf_config.clone();
+            let notifier = notifier.clone();
+            let shutdown = shutdown.clone();
+
+            tasks.push(tokio::spawn(async move {
+                run_service_instance(common_config, raf_config, notifier, sender, shutdown).await;
+            }));
+        }
+
+        ProviderSupervisor {
+            handles,
+            tasks,
+            shutdown,
+        }
+    }
+
+    /// Returns the handles of all service instances
+    pub fn handles(&self) -> &[RAFInstanceHandle] {
+        &self.handles
+    }
+
+    /// Returns the handle of the service instance with the given identifier
+    pub fn handle(&self, sii: &str) -> Option<RAFInstanceHandle> {
+        self.handles.iter().find(|hdl| hdl.sii == sii).cloned()
+    }
+
+    /// Terminate all service instances
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Wait until all service instances have terminated
+    pub async fn wait_for_termination(&mut self) {
+        for task in self.tasks.drain(..) {
+            let _ = task.await;
+        }
+    }
+}
+
+async fn run_service_instance(
+    common_config: CommonConfig,
+    config: RAFProviderConfig,
+    notifier: SharedNotifier,
+    sender: watch::Sender<Option<FrameSender>>,
+    shutdown: CancellationToken,
+) {
+    loop {
+        info!(
+            "Starting SLE instance {} on TCP port {} (SLE Port {})",
+            config.sii, config.port, config.responder_port
+        );
+
+        let mut provider = RAFProvider::new(&common_config, &config);
+
+        let res = select! {
+            res = provider.run(Box::new(notifier.clone())) => res,
+            _ = shutdown.cancelled() => {
+                return;
+            }
+        };
+
+        match res {
+            Err(err) => {
+                error!("Provider {} run returned error: {err}", config.sii);
+            }
+            Ok(()) => {
+                let frame_sender = provider.frame_sender();
+                let mut state = frame_sender.as_ref().map(|s| s.state_watch());
+                sender.send_replace(frame_sender);
+
+                let terminate = select! {
+                    _ = provider.wait_for_termination() => false,
+                    _ = wait_unbound(&mut state) => {
+                        // give the user the chance to release the connection
+                        let _ = tokio::time::timeout(UNBIND_GRACE_TIME, provider.wait_for_termination()).await;
+                        false
+                    }
+                    _ = shutdown.cancelled() => true,
+                };
+
+                provider.stop().await;
+                provider.wait_for_termination().await;
+                sender.send_replace(None);
+
+                if terminate {
+                    return;
+                }
+                info!("Service instance {} terminated, restarting...", config.sii);
+                continue;
+            }
+        }
+
+        select! {
+            _ = tokio::time::sleep(RESTART_DELAY) => {}
+            _ = shutdown.cancelled() => {
+                return;
+            }
+        }
+    }
+}
+
+/// Waits until the provider has been bound and unbound again
+async fn wait_unbound(state: &mut Option<watch::Receiver<RAFState>>) {
+    if let Some(state) = state {
+        if state.wait_for(|st| *st != RAFState::Unbound).await.is_ok()
+            && state.wait_for(|st| *st == RAFState::Unbound).await.is_ok()
+        {
+            return;
+        }
+    }
+    // the provider terminated or has no state, so the termination is handled
+    // by waiting for the provider tasks
+    std::future::pending::<()>().await
+}
@@ -1,3 +1,6 @@
+use rs_space_core::frame_sync::FrameSyncConfig;
+use rs_space_core::randomizer::Randomizer;
+use rs_space_core::reed_solomon::ReedSolomon;
 use serde::{Deserialize, Serialize};
 
 use crate::raf::asn1::{AntennaId, AntennaIdExt, RafDeliveryMode};
@@ -12,6 +15,10 @@ pub struct RAFConfig {
     pub responder_port: String,
     pub version: SleVersion,
     pub sle_operation_timeout: u16,
+    pub reconnect: Option<ReconnectConfig>,
+    /// Where the received frames are delivered to. All sinks receive every frame.
+    #[serde(default)]
+    pub frame_sinks: Vec<FrameSinkConfig>,
 }
 
 impl Default for RAFConfig {
@@ -24,6 +31,32 @@ impl Default for RAFConfig {
             responder_port: "TMPORT".to_string(),
             version: SleVersion::V4,
             sle_operation_timeout: 30,
+            reconnect: None,
+            frame_sinks: Vec::new(),
+        }
+    }
+}
+
+/// Configuration of the automatic reconnect of a RAF user. The delay between two
+/// attempts starts with `initial_delay` and is multiplied by `backoff_factor` after
+/// every failed attempt, up to `max_delay`. All delays are in milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconnectConfig {
+    pub initial_delay: u32,
+    pub max_delay: u32,
+    pub backoff_factor: f64,
+    /// Maximum number of consecutive failed attempts before giving up. If not
+    /// set, the reconnect is tried forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            initial_delay: 1000,
+            max_delay: 60000,
+            backoff_factor: 2.0,
+            max_attempts: None,
         }
     }
 }
@@ -41,6 +74,7 @@ pub struct RAFProviderConfigExt {
     pub buffer_size: u16,
     pub latency: u32,
     pub antenna_id: AntennaIdExt,
+    pub frame_source: Option<FrameSourceConfig>,
 }
 
 impl Default for RAFProviderConfigExt {
@@ -57,6 +91,7 @@ impl Default for RAFProviderConfigExt {
             buffer_size: 100,
             latency: 500,
             antenna_id: AntennaIdExt::LocalForm("ANTENNA_1".to_string()),
+            frame_source: None,
         }
     }
 }
@@ -75,6 +110,7 @@ pub struct RAFProviderConfig {
     pub buffer_size: u16,
     pub latency: u32,
     pub antenna_id: AntennaId,
+    pub frame_source: Option<FrameSourceConfig>,
 }
 
 
@@ -95,9 +131,92 @@ impl TryFrom<&RAFProviderConfigExt> for RAFProviderConfig {
             sle_operation_timeout: value.sle_operation_timeout,
             buffer_size: value.buffer_size, 
             latency: value.latency, 
-            antenna_id: ant
+            antenna_id: ant,
+            frame_source: value.frame_source.clone(),
         })
     }
 }
 
 
+
+
+/// The pattern of frames generated by [FrameSourceConfig::Generator]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GeneratorPattern {
+    /// TM idle frames (VC 7, first header pointer indicating idle data)
+    IdleFrames,
+    /// The data field is filled with an incrementing byte counter
+    Counter,
+}
+
+/// Configures where a RAF provider gets its frames from. All frame lengths are
+/// the lengths of the transfer frames without attached sync marker, all bit rates
+/// are in bits per second.
+///
+/// CADUs with attached sync marker received from the network are synchronized with
+/// the given sync parameters, a loss of lock is notified to the user. The CADUs can
+/// be derandomized with the given randomizer and Reed-Solomon decoded. With
+/// Reed-Solomon, the CADUs contain the check symbols after the frame, frames which
+/// cannot be corrected are delivered with quality erred.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FrameSourceConfig {
+    /// A file containing raw frames of a fixed length, without any headers
+    RawFile {
+        path: String,
+        frame_length: usize,
+        bit_rate: u64,
+    },
+    /// A TCP connection to the given address, delivering CADUs. The connection is
+    /// re-established if it is closed.
+    Tcp {
+        address: String,
+        frame_length: usize,
+        with_asm: bool,
+        #[serde(default)]
+        sync: FrameSyncConfig,
+        #[serde(default)]
+        randomizer: Option<Randomizer>,
+        #[serde(default)]
+        reed_solomon: Option<ReedSolomon>,
+    },
+    /// UDP datagrams received on the given address, each containing one or more CADUs
+    Udp {
+        address: String,
+        frame_length: usize,
+        with_asm: bool,
+        #[serde(default)]
+        sync: FrameSyncConfig,
+        #[serde(default)]
+        randomizer: Option<Randomizer>,
+        #[serde(default)]
+        reed_solomon: Option<ReedSolomon>,
+    },
+    /// A frame archive (e.g. recorded by the raf_client) containing the frames together
+    /// with their earth receive times and qualities. If `realtime` is set, the frames
+    /// are replayed with the time differences of their ERTs.
+    Archive { path: String, realtime: bool },
+    /// Generates frames with the given pattern and bit rate
+    Generator {
+        frame_length: usize,
+        bit_rate: u64,
+        spacecraft_id: u16,
+        pattern: GeneratorPattern,
+    },
+}
+
+/// Configures a destination for the frames received by a RAF user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FrameSinkConfig {
+    /// Writes the frames to a file. Without header, the file contains only the
+    /// concatenated frames. With header, every frame is preceded by its earth receive
+    /// time, quality and data link continuity (see [crate::raf::frame_archive]).
+    File { path: String, with_header: bool },
+    /// Writes one JSON object per frame and line
+    JsonLines { path: String },
+    /// Forwards the frames via a TCP connection to the given address, optionally
+    /// with an attached sync marker in front of each frame
+    Tcp { address: String, with_asm: bool },
+    /// Sends every frame as a UDP datagram to the given address, optionally with an
+    /// attached sync marker in front of each frame
+    Udp { address: String, with_asm: bool },
+}
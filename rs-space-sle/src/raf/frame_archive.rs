@@ -0,0 +1,107 @@
+//! A simple file format for recorded frames. Each record consists of a header with
+//! the meta data delivered via SLE and the frame itself:
+//!
+//! | Field            | Size | Encoding                   |
+//! |------------------|------|----------------------------|
+//! | Earth receive time | 8  | CDS with 16 bit days, milliseconds and microseconds |
+//! | Frame quality    | 1    | 0 = good, 1 = erred, 2 = undetermined |
+//! | Data link continuity | 4 | signed 32 bit, big endian |
+//! | Frame length     | 4    | unsigned 32 bit, big endian |
+//! | Frame            | n    |                            |
+use bytes::Bytes;
+use rs_space_core::time::{time_length, Time, TimeEncoding};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::raf::asn1::{FrameQuality, SleFrame};
+
+const ERT_ENCODING: TimeEncoding = TimeEncoding::CDS8;
+
+/// The length of the record header in bytes
+pub const RECORD_HEADER_LEN: usize = time_length(ERT_ENCODING) + 1 + 4 + 4;
+
+/// Frames larger than this are considered a corrupted archive
+const MAX_FRAME_LEN: usize = 65536;
+
+/// A single record of a frame archive
+#[derive(Debug, Clone)]
+pub struct ArchiveRecord {
+    pub frame: SleFrame,
+    pub data_link_continuity: i32,
+}
+
+/// Encode the header of an archive record into the given buffer
+pub fn encode_record_header(
+    ert: &Time,
+    quality: FrameQuality,
+    data_link_continuity: i32,
+    frame_len: usize,
+    buf: &mut [u8; RECORD_HEADER_LEN],
+) -> Result<(), String> {
+    let t_len = time_length(ERT_ENCODING);
+    ert.encode_into(Some(ERT_ENCODING), &mut buf[..t_len])
+        .map_err(|e| format!("Error encoding earth receive time: {e}"))?;
+    buf[t_len] = quality as u8;
+    buf[t_len + 1..t_len + 5].copy_from_slice(&data_link_continuity.to_be_bytes());
+    buf[t_len + 5..t_len + 9].copy_from_slice(&(frame_len as u32).to_be_bytes());
+    Ok(())
+}
+
+/// Write a complete archive record
+pub async fn write_record<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    ert: &Time,
+    quality: FrameQuality,
+    data_link_continuity: i32,
+    data: &[u8],
+) -> Result<(), String> {
+    let mut hdr = [0u8; RECORD_HEADER_LEN];
+    encode_record_header(ert, quality, data_link_continuity, data.len(), &mut hdr)?;
+    writer
+        .write_all(&hdr)
+        .await
+        .map_err(|e| format!("Error writing archive record: {e}"))?;
+    writer
+        .write_all(data)
+        .await
+        .map_err(|e| format!("Error writing archive record: {e}"))
+}
+
+/// Read the next record from the archive. Returns None at the end of the archive.
+pub async fn read_record<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<ArchiveRecord>, String> {
+    let mut hdr = [0u8; RECORD_HEADER_LEN];
+    match reader.read_exact(&mut hdr).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(format!("Error reading archive record: {e}")),
+    }
+
+    let t_len = time_length(ERT_ENCODING);
+    let ert = Time::decode_from_enc(ERT_ENCODING, &hdr[..t_len])
+        .map_err(|e| format!("Error decoding earth receive time: {e}"))?;
+    let quality = FrameQuality::try_from(hdr[t_len] as i32)?;
+    let continuity = i32::from_be_bytes(hdr[t_len + 1..t_len + 5].try_into().unwrap());
+    let len = u32::from_be_bytes(hdr[t_len + 5..t_len + 9].try_into().unwrap()) as usize;
+
+    if len > MAX_FRAME_LEN {
+        return Err(format!(
+            "Archive record has invalid frame length {len}, archive is corrupted"
+        ));
+    }
+
+    let mut data = vec![0u8; len];
+    reader
+        .read_exact(&mut data)
+        .await
+        .map_err(|e| format!("Error reading frame from archive: {e}"))?;
+
+    Ok(Some(ArchiveRecord {
+        frame: SleFrame {
+            earth_receive_time: ert,
+            delivered_frame_quality: quality,
+            data: Bytes::from(data),
+        },
+        data_link_continuity: continuity,
+    }))
+}
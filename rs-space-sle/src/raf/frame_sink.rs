@@ -0,0 +1,406 @@
+//! Forwarding of the frames received by a RAF user to files, JSON lines files and
+//! TCP or UDP destinations.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use serde_json::json;
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::select;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::raf::asn1::{AntennaId, SleTMFrame};
+use crate::raf::config::FrameSinkConfig;
+use crate::raf::frame_archive::write_record;
+use crate::raf::state::FrameCallback;
+
+/// The attached sync marker put in front of forwarded frames
+const ASM: [u8; 4] = [0x1A, 0xCF, 0xFC, 0x1D];
+/// Number of frames buffered per sink. If a sink is slower than the frame rate,
+/// frames are dropped for this sink.
+const QUEUE_SIZE: usize = 1000;
+/// Minimum time between two attempts to connect to a TCP forwarding destination
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Maximum time to wait for the connection to a TCP forwarding destination
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Minimum time between two log messages about frames dropped for a sink
+const DROP_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A set of frame sinks, each running in its own task. The callback returned by
+/// [FrameSinks::callback] distributes the received frames to all sinks, so that it
+/// can be directly used for a [crate::raf::user::RAFUser].
+pub struct FrameSinks {
+    senders: Arc<Vec<SinkSender>>,
+    tasks: Vec<JoinHandle<()>>,
+    cancel: CancellationToken,
+}
+
+impl FrameSinks {
+    /// Open all sinks from the configs and start their tasks
+    pub async fn open(configs: &[FrameSinkConfig]) -> Result<FrameSinks, String> {
+        let cancel = CancellationToken::new();
+        let mut senders = Vec::with_capacity(configs.len());
+        let mut tasks = Vec::with_capacity(configs.len());
+
+        for config in configs {
+            let sink = FrameSink::open(config).await?;
+            let (sender, receiver) = channel(QUEUE_SIZE);
+            let name = sink.name();
+            info!("Opened frame sink {name}");
+
+            let cancel = cancel.clone();
+            tasks.push(tokio::spawn(run_sink(sink, receiver, cancel)));
+            senders.push(SinkSender::new(name, sender));
+        }
+
+        Ok(FrameSinks {
+            senders: Arc::new(senders),
+            tasks,
+            cancel,
+        })
+    }
+
+    /// Returns a callback which passes every frame to all sinks. It never blocks,
+    /// if a sink can't keep up, the frame is dropped for this sink. The dropped frames
+    /// are counted and logged at most every [DROP_REPORT_INTERVAL].
+    pub fn callback(&self) -> FrameCallback {
+        let senders = self.senders.clone();
+        Arc::new(move |frame: &SleTMFrame| {
+            for sender in senders.iter() {
+                sender.send(frame);
+            }
+        })
+    }
+
+    /// Close all sinks. Frames already queued are written before the sinks are closed.
+    pub async fn close(self) {
+        self.cancel.cancel();
+        for task in self.tasks {
+            let _ = task.await;
+        }
+        for sender in self.senders.iter() {
+            sender.report_drops();
+        }
+    }
+}
+
+/// The sending side of the queue of a sink, counting the frames dropped because the
+/// queue is full
+struct SinkSender {
+    name: String,
+    sender: Sender<SleTMFrame>,
+    // frames dropped since the last report
+    dropped: AtomicU64,
+    created: Instant,
+    // time of the last report in milliseconds since `created`
+    last_report: AtomicU64,
+}
+
+impl SinkSender {
+    fn new(name: String, sender: Sender<SleTMFrame>) -> SinkSender {
+        SinkSender {
+            name,
+            sender,
+            dropped: AtomicU64::new(0),
+            created: Instant::now(),
+            last_report: AtomicU64::new(0),
+        }
+    }
+
+    fn send(&self, frame: &SleTMFrame) {
+        if self.sender.try_send(frame.clone()).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if self.dropped.load(Ordering::Relaxed) > 0 {
+            let now = self.created.elapsed().as_millis() as u64;
+            let last = self.last_report.load(Ordering::Relaxed);
+            if now.saturating_sub(last) >= DROP_REPORT_INTERVAL.as_millis() as u64 {
+                self.last_report.store(now, Ordering::Relaxed);
+                self.report_drops();
+            }
+        }
+    }
+
+    fn report_drops(&self) {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!("{dropped} frames dropped for sink {}", self.name);
+        }
+    }
+}
+
+async fn run_sink(mut sink: FrameSink, mut receiver: Receiver<SleTMFrame>, cancel: CancellationToken) {
+    loop {
+        let frame = select! {
+            frame = receiver.recv() => frame,
+            _ = cancel.cancelled() => None,
+        };
+
+        match frame {
+            Some(frame) => {
+                if let Err(err) = sink.write(&frame).await {
+                    error!("Error writing frame to sink {}: {err}", sink.name());
+                }
+            }
+            None => break,
+        }
+    }
+
+    // write the frames which are still queued
+    while let Ok(frame) = receiver.try_recv() {
+        if let Err(err) = sink.write(&frame).await {
+            error!("Error writing frame to sink {}: {err}", sink.name());
+            break;
+        }
+    }
+
+    if let Err(err) = sink.flush().await {
+        error!("Error closing sink {}: {err}", sink.name());
+    }
+}
+
+enum FrameSink {
+    File {
+        path: String,
+        file: BufWriter<File>,
+        with_header: bool,
+    },
+    JsonLines {
+        path: String,
+        file: BufWriter<File>,
+    },
+    Tcp {
+        address: String,
+        stream: Option<TcpStream>,
+        with_asm: bool,
+        last_attempt: Option<Instant>,
+    },
+    Udp {
+        address: String,
+        socket: UdpSocket,
+        with_asm: bool,
+    },
+}
+
+impl FrameSink {
+    async fn open(config: &FrameSinkConfig) -> Result<FrameSink, String> {
+        match config {
+            FrameSinkConfig::File { path, with_header } => Ok(FrameSink::File {
+                path: path.clone(),
+                file: create_file(path).await?,
+                with_header: *with_header,
+            }),
+            FrameSinkConfig::JsonLines { path } => Ok(FrameSink::JsonLines {
+                path: path.clone(),
+                file: create_file(path).await?,
+            }),
+            FrameSinkConfig::Tcp { address, with_asm } => {
+                let mut sink = FrameSink::Tcp {
+                    address: address.clone(),
+                    stream: None,
+                    with_asm: *with_asm,
+                    last_attempt: None,
+                };
+                // the destination may not be up yet, the connection is retried with
+                // the next frame
+                if let Err(err) = sink.connect().await {
+                    warn!("{err}");
+                }
+                Ok(sink)
+            }
+            FrameSinkConfig::Udp { address, with_asm } => {
+                let socket = UdpSocket::bind("0.0.0.0:0")
+                    .await
+                    .map_err(|e| format!("Could not create UDP socket: {e}"))?;
+                socket
+                    .connect(address)
+                    .await
+                    .map_err(|e| format!("Could not set UDP destination {address}: {e}"))?;
+                Ok(FrameSink::Udp {
+                    address: address.clone(),
+                    socket,
+                    with_asm: *with_asm,
+                })
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            FrameSink::File { path, .. } => format!("file {path}"),
+            FrameSink::JsonLines { path, .. } => format!("JSON lines {path}"),
+            FrameSink::Tcp { address, .. } => format!("TCP {address}"),
+            FrameSink::Udp { address, .. } => format!("UDP {address}"),
+        }
+    }
+
+    async fn connect(&mut self) -> Result<(), String> {
+        if let FrameSink::Tcp {
+            address,
+            stream,
+            last_attempt,
+            ..
+        } = self
+        {
+            *last_attempt = Some(Instant::now());
+            match timeout(CONNECT_TIMEOUT, TcpStream::connect(address.as_str())).await {
+                Ok(Ok(s)) => {
+                    info!("Connected to frame destination {address}");
+                    *stream = Some(s);
+                }
+                Ok(Err(err)) => {
+                    return Err(format!(
+                        "Could not connect to frame destination {address}: {err}"
+                    ));
+                }
+                Err(_) => {
+                    return Err(format!(
+                        "Timeout connecting to frame destination {address}"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, frame: &SleTMFrame) -> Result<(), String> {
+        // frames are dropped while the TCP destination is not connected
+        if let FrameSink::Tcp {
+            stream: None,
+            last_attempt,
+            ..
+        } = self
+        {
+            let retry = last_attempt.is_none_or(|t| t.elapsed() >= RECONNECT_DELAY);
+            if !retry {
+                return Ok(());
+            }
+            self.connect().await?;
+        }
+
+        match self {
+            FrameSink::File {
+                file, with_header, ..
+            } => {
+                if *with_header {
+                    write_record(
+                        file,
+                        &frame.earth_receive_time,
+                        frame.delivered_frame_quality,
+                        frame.data_link_continuity,
+                        &frame.data,
+                    )
+                    .await
+                } else {
+                    write_all(file, &frame.data).await
+                }
+            }
+            FrameSink::JsonLines { file, .. } => {
+                let mut line = frame_to_json(frame).to_string();
+                line.push('\n');
+                write_all(file, line.as_bytes()).await
+            }
+            FrameSink::Tcp {
+                stream, with_asm, ..
+            } => {
+                let Some(conn) = stream.as_mut() else {
+                    return Ok(());
+                };
+                let res = if *with_asm {
+                    match write_all(conn, &ASM).await {
+                        Ok(()) => write_all(conn, &frame.data).await,
+                        Err(err) => Err(err),
+                    }
+                } else {
+                    write_all(conn, &frame.data).await
+                };
+                if res.is_err() {
+                    // reconnect with the next frame
+                    *stream = None;
+                }
+                res
+            }
+            FrameSink::Udp {
+                socket, with_asm, ..
+            } => {
+                let res = if *with_asm {
+                    let mut buf = Vec::with_capacity(ASM.len() + frame.data.len());
+                    buf.extend_from_slice(&ASM);
+                    buf.extend_from_slice(&frame.data);
+                    socket.send(&buf).await
+                } else {
+                    socket.send(&frame.data).await
+                };
+                match res {
+                    Ok(_) => Ok(()),
+                    // there is no one listening at the destination yet
+                    Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                        debug!("UDP frame destination not reachable: {err}");
+                        Ok(())
+                    }
+                    Err(err) => Err(format!("Error sending frame: {err}")),
+                }
+            }
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), String> {
+        match self {
+            FrameSink::File { file, .. } | FrameSink::JsonLines { file, .. } => file
+                .flush()
+                .await
+                .map_err(|e| format!("Error flushing file: {e}")),
+            FrameSink::Tcp {
+                stream: Some(stream),
+                ..
+            } => stream
+                .shutdown()
+                .await
+                .map_err(|e| format!("Error closing connection: {e}")),
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn create_file(path: &str) -> Result<BufWriter<File>, String> {
+    let file = File::create(path)
+        .await
+        .map_err(|e| format!("Could not create frame file {path}: {e}"))?;
+    Ok(BufWriter::new(file))
+}
+
+async fn write_all<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8]) -> Result<(), String> {
+    writer
+        .write_all(data)
+        .await
+        .map_err(|e| format!("Error writing frame: {e}"))
+}
+
+fn frame_to_json(frame: &SleTMFrame) -> serde_json::Value {
+    let ert = frame.earth_receive_time.as_duration();
+    let antenna = match &frame.antenna_id {
+        AntennaId::LocalForm(id) => String::from_utf8_lossy(id).to_string(),
+        AntennaId::GlobalForm(oid) => oid
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join("."),
+    };
+
+    json!({
+        "ert_secs": ert.as_secs(),
+        "ert_micros": ert.subsec_micros(),
+        "antenna_id": antenna,
+        "data_link_continuity": frame.data_link_continuity,
+        "quality": format!("{:?}", frame.delivered_frame_quality),
+        "data": hex::encode(&frame.data),
+    })
+}
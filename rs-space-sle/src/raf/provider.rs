@@ -22,6 +22,8 @@ use tokio::{
     },
     task::JoinHandle,
 };
+use futures::StreamExt;
+use tokio_util::codec::FramedRead;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
@@ -29,7 +31,7 @@ use crate::{
     provider::raf_interface::ProviderNotifier,
     raf::asn1::*,
     sle::config::{CommonConfig, SleAuthType},
-    tml::message::TMLMessage,
+    tml::{codec::TMLCodec, message::TMLMessage},
     types::{
         aul::{check_credentials, ISP1Credentials},
         sle::*,
@@ -57,8 +59,9 @@ pub enum SleMsg {
 
 type InternalState = Arc<Mutex<InternalRAFProviderState>>;
 
-type Notifier = Box<dyn ProviderNotifier + Send>;
+type Notifier = Arc<dyn ProviderNotifier + Send + Sync>;
 
+#[derive(Clone)]
 pub enum DataBufferElement {
     Frame(SleFrame),
     Notification(Notification),
@@ -86,7 +89,7 @@ struct Args {
     app_notifier: Notifier,
     chan: Sender<SleMsg>,
     rand: StdRng,
-    rx: OwnedReadHalf,
+    rx: FramedRead<OwnedReadHalf, TMLCodec>,
     interval: u16,
     dead_factor: u16,
     state_watch: tokio::sync::watch::Sender<RAFState>,
@@ -118,8 +121,10 @@ impl RAFProvider {
 
     pub async fn run(
         &mut self,
-        notifier: Box<dyn ProviderNotifier + Send>,
+        notifier: Box<dyn ProviderNotifier + Send + Sync>,
     ) -> tokio::io::Result<()> {
+        let notifier: Notifier = Arc::from(notifier);
+
         let listener =
             TcpListener::bind((self.raf_config.hostname.as_ref(), self.raf_config.port)).await?;
 
@@ -129,6 +134,8 @@ impl RAFProvider {
             "Connection on RAF instance {} from {}",
             self.raf_config.sii, peer
         );
+        notifier.connection_accepted(&self.raf_config.sii, &peer);
+        let notifier2 = notifier.clone();
 
         // a mpsc channel to send command messags to the writer task.
         let (sender, mut receiver) = channel::<SleMsg>(QUEUE_SIZE);
@@ -138,6 +145,7 @@ impl RAFProvider {
         // We need a writer and a reader task, so we split the socket into a
         // read and write half
         let (rx, tx) = socket.into_split();
+        let rx = FramedRead::new(rx, TMLCodec::from_config(&self.common_config.tml));
 
         // Create the timed buffer channel
         let (buf_sender, buf_receiver) = TimedBuffer::<DataBufferElement>::new(
@@ -152,6 +160,7 @@ impl RAFProvider {
         let cancel2 = self.cancel_token.clone();
         let cancel3 = self.cancel_token.clone();
         let cancel4 = self.cancel_token.clone();
+        let cancel5 = self.cancel_token.clone();
 
         let config2 = self.common_config.clone();
         let config3 = self.common_config.clone();
@@ -200,6 +209,7 @@ impl RAFProvider {
             match read_context_message(&mut args, server_timeout).await {
                 Err(err) => {
                     error!("Error reading SLE TML Context Message: {err}");
+                    args.app_notifier.protocol_abort(&args.raf_config.sii, &err);
                     cancel_clone.cancel();
                 }
                 Ok((interval, dead_factor)) => {
@@ -210,6 +220,7 @@ impl RAFProvider {
                     match read_pdus(&mut args).await {
                         Err(err) => {
                             error!("{err}");
+                            args.app_notifier.protocol_abort(&args.raf_config.sii, &err);
                             cancel_clone.cancel();
                         }
                         Ok(_) => {
@@ -218,6 +229,8 @@ impl RAFProvider {
                     }
                 }
             }
+
+            args.app_notifier.connection_closed(&args.raf_config.sii);
         });
 
         // The writer task. This listens on the mpsc channel for messages and reacts to them.
@@ -241,20 +254,36 @@ impl RAFProvider {
             let continuity = AtomicI32::new(-1);
 
             loop {
-                let frames = buf_receiver.recv().await;
+                let frames = select! {
+                    frames = buf_receiver.recv() => frames,
+                    _ = cancel5.cancelled() => {
+                        debug!("RAF provider for {} buffer task has been cancelled", raf_config4.sii);
+                        return;
+                    }
+                };
 
                 if !frames.is_empty() {
                     // prepare the frames
+                    let notifications = frames
+                        .iter()
+                        .filter(|elem| matches!(elem, DataBufferElement::Notification(_)))
+                        .count();
+                    let num_frames = frames.len() - notifications;
+
                     match convert_frames(&config4, &raf_config4, &mut rand, &continuity, frames) {
                         Err(err) => {
                             error!("Error encoding TM Frames: {err}");
+                            notifier2.buffer_discarded(&raf_config4.sii, num_frames, notifications);
                             continue;
                         }
                         Ok(trans) => {
                             let pdu = SleMsg::PDU(SlePdu::SleRafTransferBuffer(trans));
                             if let Err(err) = sender3.send(pdu).await {
                                 error!("Error sending TM Frames: {err}");
+                                notifier2.buffer_discarded(&raf_config4.sii, num_frames, notifications);
                                 cancel4.cancel();
+                            } else {
+                                notifier2.buffer_sent(&raf_config4.sii, num_frames, notifications);
                             }
                         }
                     }
@@ -279,20 +308,21 @@ impl RAFProvider {
         }
     }
 
+    /// Returns a [FrameSender] for this provider, which can be passed to other tasks.
+    /// Only available after [RAFProvider::run] has accepted a connection.
+    pub fn frame_sender(&self) -> Option<FrameSender> {
+        self.buffer_sender.as_ref().map(|chan| FrameSender {
+            sii: self.raf_config.sii.clone(),
+            raf_state: self.raf_state.clone(),
+            state_watch: self.state_watch.clone(),
+            buffer_sender: chan.clone(),
+        })
+    }
+
     /// Send a TM Transfer Frame via an established SLE service
     pub async fn send_frame(&self, frame: SleFrame) -> Result<(), String> {
-        if self.raf_state.load(Ordering::Relaxed) != RAFState::Active {
-            return Err(format!(
-                "Tried to send Frame while not in active state: {}",
-                self.raf_config.sii
-            ));
-        }
-
-        match &self.buffer_sender {
-            Some(chan) => {
-                chan.send(DataBufferElement::Frame(frame)).await;
-                Ok(())
-            }
+        match self.frame_sender() {
+            Some(sender) => sender.send_frame(frame).await,
             None => Err(format!(
                 "Tried to send TM Frame when no channel was established on {}",
                 self.raf_config.sii
@@ -307,27 +337,16 @@ impl RAFProvider {
         subcarrier_lock_status: LockStatus,
         symbol_sync_lock_status: LockStatus,
     ) -> Result<(), String> {
-        if self.raf_state.load(Ordering::Relaxed) != RAFState::Active {
-            return Err(format!(
-                "Tried to send Notification while not in active state: {}",
-                self.raf_config.sii
-            ));
-        }
-
-        match &self.buffer_sender {
-            Some(chan) => {
-                let time = crate::types::sle::Time::CcsdsFormat(to_ccsds_time(time)?);
-                chan.send(DataBufferElement::Notification(
-                    Notification::LossFrameSync {
-                        time: time,
-                        carrier_lock_status: (carrier_lock_status as i32).into(),
-                        subcarrier_lock_status: (subcarrier_lock_status as i32).into(),
-                        symbol_sync_lock_status: (symbol_sync_lock_status as i32).into(),
-                    },
-                ))
-                .await;
-
-                Ok(())
+        match self.frame_sender() {
+            Some(sender) => {
+                sender
+                    .notify_sync_loss(
+                        time,
+                        carrier_lock_status,
+                        subcarrier_lock_status,
+                        symbol_sync_lock_status,
+                    )
+                    .await
             }
             None => Err(format!(
                 "Tried to send TM Frame when no channel was established on {}",
@@ -348,13 +367,93 @@ impl RAFProvider {
     }
 }
 
+/// A cloneable handle to the transfer buffer of a running [RAFProvider]. It is used
+/// to send frames and notifications from other tasks than the one running the provider.
+#[derive(Clone)]
+pub struct FrameSender {
+    sii: String,
+    raf_state: Arc<AtomicRAFState>,
+    state_watch: tokio::sync::watch::Receiver<RAFState>,
+    buffer_sender: timed_buffer::Sender<DataBufferElement>,
+}
+
+impl FrameSender {
+    /// The service instance identifier of the provider
+    pub fn sii(&self) -> &str {
+        &self.sii
+    }
+
+    /// Returns the current state of the provider
+    pub fn state(&self) -> RAFState {
+        self.raf_state.load(Ordering::Relaxed)
+    }
+
+    /// Returns a receiver for the state changes of the provider
+    pub fn state_watch(&self) -> tokio::sync::watch::Receiver<RAFState> {
+        self.state_watch.clone()
+    }
+
+    /// Wait until the provider is in state ACTIVE. Returns false, if the provider
+    /// has terminated before.
+    pub async fn wait_active(&mut self) -> bool {
+        self.state_watch
+            .wait_for(|val| *val == RAFState::Active)
+            .await
+            .is_ok()
+    }
+
+    /// Send a TM Transfer Frame via an established SLE service
+    pub async fn send_frame(&self, frame: SleFrame) -> Result<(), String> {
+        if self.raf_state.load(Ordering::Relaxed) != RAFState::Active {
+            return Err(format!(
+                "Tried to send Frame while not in active state: {}",
+                self.sii
+            ));
+        }
+
+        self.buffer_sender
+            .send(DataBufferElement::Frame(frame))
+            .await;
+        Ok(())
+    }
+
+    pub async fn notify_sync_loss(
+        &self,
+        time: &Time,
+        carrier_lock_status: LockStatus,
+        subcarrier_lock_status: LockStatus,
+        symbol_sync_lock_status: LockStatus,
+    ) -> Result<(), String> {
+        if self.raf_state.load(Ordering::Relaxed) != RAFState::Active {
+            return Err(format!(
+                "Tried to send Notification while not in active state: {}",
+                self.sii
+            ));
+        }
+
+        let time = crate::types::sle::Time::CcsdsFormat(to_ccsds_time(time)?);
+        self.buffer_sender
+            .send(DataBufferElement::Notification(
+                Notification::LossFrameSync {
+                    time,
+                    carrier_lock_status: (carrier_lock_status as i32).into(),
+                    subcarrier_lock_status: (subcarrier_lock_status as i32).into(),
+                    symbol_sync_lock_status: (symbol_sync_lock_status as i32).into(),
+                },
+            ))
+            .await;
+
+        Ok(())
+    }
+}
+
 async fn read_context_message(
     args: &mut Args,
     server_startup_interval: Duration,
 ) -> Result<(u16, u16), String> {
     select! {
-        res = TMLMessage::async_read(&mut args.rx) => {
-            match res {
+        res = args.rx.next() => {
+            match res.unwrap_or_else(|| Err(std::io::ErrorKind::UnexpectedEof.into())) {
                 Err(err) => { return Err(format!("Error reading TML Context Message: {err}")); }
                 Ok(msg) => {
                     debug!("Read TML message {msg:?}");
@@ -387,12 +486,15 @@ async fn read_pdus(args: &mut Args) -> Result<(), String> {
         select! {
             biased;
 
-            res = TMLMessage::async_read(&mut args.rx) => {
+            res = args.rx.next() => {
                 match res {
-                    Err(err) => {
+                    None => {
+                        return Err("Error reading SLE TML Message: connection closed".to_string());
+                    }
+                    Some(Err(err)) => {
                         return Err(format!("Error reading SLE TML Message: {err}"));
                     }
-                    Ok(msg) => {
+                    Some(Ok(msg)) => {
                         if msg.is_heartbeat() {
                             debug!("SLE TML heartbeat received");
                         }
@@ -404,6 +506,7 @@ async fn read_pdus(args: &mut Args) -> Result<(), String> {
                 }
             }
             _ = tokio::time::sleep(timeout) => {
+                args.app_notifier.heartbeat_timeout(&args.raf_config.sii);
                 return Err(format!("Timeout waiting for heartbeat message"));
             }
             _ = args.cancel_token.cancelled() => {
@@ -445,6 +548,21 @@ fn check_authentication(config: &CommonConfig, state: InternalState, pdu: &SlePd
     }
 }
 
+fn notify_authentication_failure(args: &Args, pdu: &SlePdu) {
+    let peer = match pdu {
+        SlePdu::SleBindInvocation {
+            initiator_identifier,
+            ..
+        } => initiator_identifier.value.to_string(),
+        _ => {
+            let lock = args.state.lock().expect("Mutex lock failed");
+            lock.user().value.to_string()
+        }
+    };
+    args.app_notifier
+        .authentication_failed(&args.raf_config.sii, &peer, pdu.operation_name());
+}
+
 fn check_bind(config: &CommonConfig, pdu: &SlePdu) -> bool {
     match pdu {
         SlePdu::SleBindInvocation {
@@ -602,6 +720,7 @@ async fn process_sle_pdu(args: &mut Args, pdu: &SlePdu) {
             // check authentication
             if !check_authentication(&args.common_config, args.state.clone(), &pdu) {
                 error!("SLE PDU failed authentication");
+                notify_authentication_failure(args, pdu);
 
                 let credentials = new_credentials(&args.common_config, &mut args.rand);
 
@@ -637,6 +756,7 @@ async fn process_sle_pdu(args: &mut Args, pdu: &SlePdu) {
             // check authentication
             if !check_authentication(&args.common_config, args.state.clone(), &pdu) {
                 warn!("SLE PDU UNBIND failed authentication, ignoring PDU...");
+                notify_authentication_failure(args, pdu);
 
                 return;
             }
@@ -659,6 +779,7 @@ async fn process_sle_pdu(args: &mut Args, pdu: &SlePdu) {
             // check authentication
             if !check_authentication(&args.common_config, args.state.clone(), &pdu) {
                 error!("RAF START PDU failed authentication");
+                notify_authentication_failure(args, pdu);
 
                 let credentials = new_credentials(&args.common_config, &mut args.rand);
 
@@ -695,6 +816,7 @@ async fn process_sle_pdu(args: &mut Args, pdu: &SlePdu) {
             // check authentication
             if !check_authentication(&args.common_config, args.state.clone(), &pdu) {
                 error!("RAF STOP PDU failed authentication");
+                notify_authentication_failure(args, pdu);
 
                 let credentials = new_credentials(&args.common_config, &mut args.rand);
 
@@ -722,6 +844,7 @@ async fn process_sle_pdu(args: &mut Args, pdu: &SlePdu) {
             // check authentication
             if !check_authentication(&args.common_config, args.state.clone(), &pdu) {
                 error!("RAF GET PARAMETER PDU failed authentication");
+                notify_authentication_failure(args, pdu);
 
                 let credentials = new_credentials(&args.common_config, &mut args.rand);
 
@@ -1259,6 +1382,9 @@ async fn process_get_parameter(
         }
     };
 
+    args.app_notifier
+        .get_parameter(&args.raf_config.sii, parameter_name, &diag);
+
     // Create a SLE Ack PDU
     let credentials = new_credentials(&args.common_config, &mut args.rand);
     
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use log::{error, info, warn};
 
 use crate::asn1::{BindResult, SleResult};
@@ -15,7 +17,9 @@ pub enum RAFState {
     Active,
 }
 
-pub type FrameCallback = fn(&SleTMFrame);
+/// Called for every TM Transfer Frame received. As the callback is shared between the
+/// tasks of a RAF user, it can capture state (e.g. the senders of frame sinks).
+pub type FrameCallback = Arc<dyn Fn(&SleTMFrame) + Send + Sync>;
 
 // Example code from ChatGPT for Async function pointers:
 
@@ -35,13 +39,22 @@ pub type FrameCallback = fn(&SleTMFrame);
 //     let fut = func();
 // }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct InternalRAFState {
     state: RAFState,
     provider: VisibleString,
     frame_callback: FrameCallback,
 }
 
+impl std::fmt::Debug for InternalRAFState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InternalRAFState")
+            .field("state", &self.state)
+            .field("provider", &self.provider)
+            .finish_non_exhaustive()
+    }
+}
+
 impl InternalRAFState {
     pub fn new(frame_callback: FrameCallback) -> Self {
         InternalRAFState {
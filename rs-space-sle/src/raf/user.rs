@@ -11,6 +11,8 @@ use tokio::net::TcpStream;
 use tokio::select;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::task::JoinHandle;
+use futures::StreamExt;
+use tokio_util::codec::FramedRead;
 use tokio_util::sync::CancellationToken;
 
 use crate::asn1::*;
@@ -21,6 +23,7 @@ use crate::raf::config::RAFConfig;
 use crate::raf::state::{FrameCallback, InternalRAFState, RAFState};
 use crate::sle::config::{CommonConfig, SleAuthType};
 // use crate::pdu::PDU;
+use crate::tml::codec::TMLCodec;
 use crate::tml::config::TMLConfig;
 use crate::tml::message::TMLMessage;
 use crate::types::aul::{check_credentials, ISP1Credentials};
@@ -156,7 +159,8 @@ impl RAFUser {
                 }
             };
 
-        let (mut rx, mut tx) = sock.into_split();
+        let (rx, mut tx) = sock.into_split();
+        let mut rx = FramedRead::new(rx, TMLCodec::from_config(&self.common_config.tml));
 
         let (sender, mut receiver) = channel::<SleMsg>(QUEUE_SIZE);
         let sender2 = sender.clone();
@@ -184,13 +188,19 @@ impl RAFUser {
         let read_task = tokio::spawn(async move {
             loop {
                 select! {
-                    res = TMLMessage::async_read(&mut rx) => {
+                    res = rx.next() => {
                         match res {
-                            Err(err) => {
+                            None => {
+                                error!("SLE TML connection closed by peer");
+                                cancel1.cancel();
+                                break;
+                            }
+                            Some(Err(err)) => {
                                 error!("Error reading SLE TML message from socket: {}", err);
+                                cancel1.cancel();
                                 break;
                             }
-                            Ok(msg) => {
+                            Some(Ok(msg)) => {
                                 if msg.is_heartbeat() {
                                     debug!("SLE TML heartbeat received");
                                 }
@@ -205,6 +215,7 @@ impl RAFUser {
                         // we have a receive heartbeat timeout, so report the error and disconnect
                         error!("Heartbeat timeout on service instance {}, terminating connection", sii);
                         let _ = sender2.send(SleMsg::Stop).await;
+                        cancel1.cancel();
                         return;
                     }
                     _ = cancel1.cancelled() => {
@@ -279,6 +290,8 @@ impl RAFUser {
 
                 }
             }
+            // the connection is unusable, so also terminate the read task
+            cancel2.cancel();
         });
 
         // update self with the tasks
@@ -327,6 +340,7 @@ impl RAFUser {
             }
             _ = self.cancellation_token.cancelled() => {
                 debug!("RAF client for {} has been cancelled (BIND operation)", self.raf_config.sii);
+                return Err("BIND error: connection terminated while waiting for BIND RETURN".to_string());
             }
         }
         Ok(())
@@ -435,6 +449,7 @@ impl RAFUser {
             }
             _ = self.cancellation_token.cancelled() => {
                 debug!("RAF client for {} has been cancelled (RAF START operation)", self.raf_config.sii);
+                return Err("RAF START error: connection terminated while waiting for RAF START RETURN".to_string());
             }
         }
 
@@ -531,6 +546,18 @@ impl RAFUser {
     pub async fn cancel(&self) {
         self.cancellation_token.cancel();
     }
+
+    /// Wait until the connection of this client has terminated. This happens on
+    /// communication errors, heartbeat timeouts, a PEER ABORT from the provider or
+    /// when the client itself has been cancelled or unbound.
+    pub async fn wait_for_termination(&self) {
+        self.cancellation_token.cancelled().await
+    }
+
+    /// Returns true if the connection of this client has terminated
+    pub fn is_terminated(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
 }
 
 fn process_sle_msg(pdu: SlePdu, _state: InternalState) -> Result<TMLMessage, String> {
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use rs_space_core::time::Time;
+use tokio::select;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
+
+use crate::asn1::UnbindReason;
+use crate::raf::asn1::RequestedFrameQuality;
+use crate::raf::config::{RAFConfig, ReconnectConfig};
+use crate::raf::state::FrameCallback;
+use crate::raf::user::RAFUser;
+use crate::sle::config::CommonConfig;
+
+const QUEUE_SIZE: usize = 100;
+
+/// Events reported by the [RAFUserSupervisor] for every connection attempt
+#[derive(Debug, Clone)]
+pub enum SupervisorEvent {
+    /// A new attempt to connect, BIND and START is started
+    Connecting { attempt: u32 },
+    /// The attempt was successful, the service instance is ACTIVE again
+    Active { attempt: u32 },
+    /// The attempt failed with the given error
+    AttemptFailed { attempt: u32, error: String },
+    /// The connection to the provider has been lost
+    ConnectionLost,
+    /// The next attempt is started after the given delay
+    RetryScheduled { attempt: u32, delay: Duration },
+    /// The maximum number of attempts has been reached, the supervisor terminates
+    GaveUp { attempts: u32 },
+    /// The supervisor has been shut down and the service instance has been unbound
+    Stopped,
+}
+
+#[derive(Debug, Clone)]
+struct StartParameters {
+    start: Option<Time>,
+    stop: Option<Time>,
+    frame_quality: RequestedFrameQuality,
+}
+
+/// Calculates the delays between two reconnect attempts
+struct Backoff {
+    config: ReconnectConfig,
+    current: Duration,
+}
+
+impl Backoff {
+    fn new(config: &ReconnectConfig) -> Backoff {
+        Backoff {
+            config: config.clone(),
+            current: Duration::from_millis(config.initial_delay as u64),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = Duration::from_millis(self.config.initial_delay as u64);
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        let max = Duration::from_millis(self.config.max_delay as u64);
+        self.current = self.current.mul_f64(self.config.backoff_factor.max(1.0)).min(max);
+        delay.min(max)
+    }
+}
+
+/// Supervises a [RAFUser]. If the connection drops because of communication failures
+/// or a PEER ABORT from the provider, the supervisor reconnects, re-BINDs and re-STARTs
+/// the service instance with the last START parameters. Between the attempts, a
+/// configurable backoff is applied.
+pub struct RAFUserSupervisor {
+    common_config: CommonConfig,
+    raf_config: RAFConfig,
+    reconnect: ReconnectConfig,
+    frame_callback: FrameCallback,
+    events: Sender<SupervisorEvent>,
+    shutdown: CancellationToken,
+}
+
+impl RAFUserSupervisor {
+    /// Create a new supervisor. If the RAF config contains no reconnect configuration,
+    /// the default one is used. Returns the supervisor and the receiving end of the
+    /// event channel.
+    pub fn new(
+        common_config: &CommonConfig,
+        raf_config: &RAFConfig,
+        frame_callback: FrameCallback,
+    ) -> (RAFUserSupervisor, Receiver<SupervisorEvent>) {
+        let (sender, receiver) = channel(QUEUE_SIZE);
+
+        let supervisor = RAFUserSupervisor {
+            common_config: common_config.clone(),
+            raf_config: raf_config.clone(),
+            reconnect: raf_config.reconnect.clone().unwrap_or_default(),
+            frame_callback,
+            events: sender,
+            shutdown: CancellationToken::new(),
+        };
+
+        (supervisor, receiver)
+    }
+
+    /// Returns a token which can be used to shut down the supervisor. On shutdown,
+    /// the service instance is stopped and unbound.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Run the supervised service instance. The instance is bound and started with
+    /// the given parameters and kept running until the supervisor is shut down or
+    /// the maximum number of attempts is exceeded.
+    pub async fn run(
+        &mut self,
+        start: Option<Time>,
+        stop: Option<Time>,
+        frame_quality: RequestedFrameQuality,
+    ) -> Result<(), String> {
+        let params = StartParameters {
+            start,
+            stop,
+            frame_quality,
+        };
+        let mut backoff = Backoff::new(&self.reconnect);
+        let mut attempt: u32 = 0;
+
+        loop {
+            attempt += 1;
+            self.emit(SupervisorEvent::Connecting { attempt });
+
+            let mut raf = RAFUser::new(
+                &self.common_config,
+                &self.raf_config,
+                self.frame_callback.clone(),
+            );
+
+            let res = select! {
+                res = self.connect(&mut raf, &params) => res,
+                _ = self.shutdown.cancelled() => Err("supervisor has been shut down".to_string()),
+            };
+
+            match res {
+                Err(_) if self.shutdown.is_cancelled() => {
+                    raf.cancel().await;
+                    raf.stop_processing().await;
+                    self.emit(SupervisorEvent::Stopped);
+                    return Ok(());
+                }
+                Err(err) => {
+                    error!(
+                        "Attempt {attempt} to connect to {} failed: {err}",
+                        self.raf_config.sii
+                    );
+                    raf.cancel().await;
+                    raf.stop_processing().await;
+
+                    self.emit(SupervisorEvent::AttemptFailed {
+                        attempt,
+                        error: err.clone(),
+                    });
+
+                    if let Some(max) = self.reconnect.max_attempts {
+                        if attempt >= max {
+                            self.emit(SupervisorEvent::GaveUp { attempts: attempt });
+                            return Err(format!(
+                                "Giving up reconnecting to {} after {attempt} attempts: {err}",
+                                self.raf_config.sii
+                            ));
+                        }
+                    }
+                }
+                Ok(()) => {
+                    info!("Service instance {} is ACTIVE", self.raf_config.sii);
+                    self.emit(SupervisorEvent::Active { attempt });
+                    attempt = 0;
+                    backoff.reset();
+
+                    let shutdown = select! {
+                        _ = raf.wait_for_termination() => false,
+                        _ = self.shutdown.cancelled() => true,
+                    };
+
+                    if shutdown {
+                        self.terminate(&mut raf).await;
+                        self.emit(SupervisorEvent::Stopped);
+                        return Ok(());
+                    }
+
+                    warn!("Connection to {} lost", self.raf_config.sii);
+                    raf.stop_processing().await;
+                    self.emit(SupervisorEvent::ConnectionLost);
+                }
+            }
+
+            let delay = backoff.next_delay();
+            info!(
+                "Reconnecting to {} in {} ms",
+                self.raf_config.sii,
+                delay.as_millis()
+            );
+            self.emit(SupervisorEvent::RetryScheduled {
+                attempt: attempt + 1,
+                delay,
+            });
+
+            select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = self.shutdown.cancelled() => {
+                    self.emit(SupervisorEvent::Stopped);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    async fn connect(&self, raf: &mut RAFUser, params: &StartParameters) -> Result<(), String> {
+        raf.bind().await?;
+        raf.start(
+            params.start.clone(),
+            params.stop.clone(),
+            params.frame_quality,
+        )
+        .await?;
+
+        // the connection may have been lost right after the START RETURN
+        if raf.is_terminated() {
+            return Err("connection terminated during BIND/START".to_string());
+        }
+        Ok(())
+    }
+
+    async fn terminate(&self, raf: &mut RAFUser) {
+        info!("Stopping SLE RAF service {}...", self.raf_config.sii);
+        if let Err(err) = raf.stop().await {
+            error!("RAF STOP returned error: {err}");
+        }
+
+        info!("Sending SLE UNBIND for {}...", self.raf_config.sii);
+        if let Err(err) = raf.unbind(UnbindReason::End).await {
+            error!("UNBIND returned error: {err}");
+        }
+
+        raf.stop_processing().await;
+    }
+
+    fn emit(&self, event: SupervisorEvent) {
+        // the supervisor must not block on a slow or missing event listener, so
+        // events are dropped if the queue is full
+        if let Err(err) = self.events.try_send(event) {
+            debug!("Could not send supervisor event: {err}");
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use bytes::{Buf, BufMut, BytesMut};
+use tokio::io::{Error, ErrorKind};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::tml::config::TMLConfig;
+use crate::tml::message::{TMLMessage, TMLMessageType};
+
+/// The TML header: message type, 3 unused bytes and the length of the data
+const TML_HDR_LEN: usize = 8;
+
+/// Codec for TML messages, to be used with `FramedRead`/`FramedWrite` on the SLE
+/// sockets. Messages with more data than the configured maximum are rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TMLCodec {
+    max_length: u32,
+}
+
+impl TMLCodec {
+    pub fn new(max_length: u32) -> TMLCodec {
+        TMLCodec { max_length }
+    }
+
+    pub fn from_config(cfg: &TMLConfig) -> TMLCodec {
+        TMLCodec::new(cfg.max_message_length)
+    }
+
+    fn check_length(&self, len: u32) -> Result<(), Error> {
+        if len > self.max_length {
+            let msg = format!(
+                "TML Message: length {} exceeds maximum of {}",
+                len, self.max_length
+            );
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+        Ok(())
+    }
+}
+
+impl Default for TMLCodec {
+    fn default() -> Self {
+        TMLCodec::new(TMLConfig::DEFAULT_MAX_MESSAGE_LENGTH)
+    }
+}
+
+impl Decoder for TMLCodec {
+    type Item = TMLMessage;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < TML_HDR_LEN {
+            return Ok(None);
+        }
+
+        let msg_type = match TMLMessageType::try_from(src[0]) {
+            Ok(t) => t,
+            Err(_) => {
+                let msg = format!("TML Message: invalid message type {}", src[0]);
+                return Err(Error::new(ErrorKind::InvalidInput, msg));
+            }
+        };
+
+        let length = u32::from_be_bytes([src[4], src[5], src[6], src[7]]);
+        self.check_length(length)?;
+
+        let total = TML_HDR_LEN + length as usize;
+        if src.len() < total {
+            src.reserve(total - src.len());
+            return Ok(None);
+        }
+
+        src.advance(TML_HDR_LEN);
+        let data = src.split_to(length as usize).to_vec();
+        Ok(Some(TMLMessage {
+            msg_type,
+            length,
+            data,
+        }))
+    }
+}
+
+impl Encoder<TMLMessage> for TMLCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: TMLMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.encode(&item, dst)
+    }
+}
+
+impl Encoder<&TMLMessage> for TMLCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &TMLMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let len = u32::try_from(item.data.len()).unwrap_or(u32::MAX);
+        self.check_length(len)?;
+
+        dst.reserve(TML_HDR_LEN + item.data.len());
+        dst.put_u8(item.msg_type as u8);
+        dst.put_slice(&[0, 0, 0]);
+        dst.put_u32(len);
+        dst.put_slice(&item.data);
+        Ok(())
+    }
+}
@@ -15,6 +15,13 @@ pub struct TMLConfig {
     pub max_heartbeat: u16,
     pub min_dead_factor: u16,
     pub max_dead_factor: u16,
+    /// The maximum length of the data of a received or sent TML message
+    #[serde(default = "default_max_message_length")]
+    pub max_message_length: u32,
+}
+
+fn default_max_message_length() -> u32 {
+    TMLConfig::DEFAULT_MAX_MESSAGE_LENGTH
 }
 
 impl Default for TMLConfig {
@@ -27,11 +34,14 @@ impl Default for TMLConfig {
             max_heartbeat: 3600,
             min_dead_factor: 2,
             max_dead_factor: 60,
+            max_message_length: Self::DEFAULT_MAX_MESSAGE_LENGTH,
         }
     }
 }
 
 impl TMLConfig {
+    pub const DEFAULT_MAX_MESSAGE_LENGTH: u32 = 4 * 1024 * 1024;
+
     pub async fn read_from_file(filename: &Path) -> Result<TMLConfig, Error> {
         let content = read_to_string(filename).await?;
 
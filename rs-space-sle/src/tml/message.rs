@@ -28,7 +28,7 @@ impl TryFrom<u8> for TMLMessageType {
 #[derive(Debug)]
 pub struct TMLMessage {
     pub msg_type: TMLMessageType,
-    length: u32,
+    pub(crate) length: u32,
     pub data: Vec<u8>,
 }
 